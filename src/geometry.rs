@@ -1,43 +1,44 @@
 pub(crate) mod aligned_box;
+pub(crate) mod bvh;
 pub(crate) mod disk;
+pub(crate) mod hit;
+pub(crate) mod instance;
 pub(crate) mod matrix;
+pub(crate) mod mesh_check;
+pub(crate) mod motion;
 pub(crate) mod normal;
 pub(crate) mod plane;
 pub(crate) mod point;
+pub(crate) mod quaternion;
 pub(crate) mod ray;
 pub(crate) mod sphere;
 pub(crate) mod triangle;
+pub(crate) mod triangle_mesh;
+pub(crate) mod trs;
 pub(crate) mod vector;
 
+use aligned_box::AlignedBox;
+use hit::{Hit, Interval};
 use matrix::Matrix;
-use normal::Normal;
-use point::Point;
+use quaternion::Quaternion;
 use ray::Ray;
+use triangle_mesh::TriangleMesh;
 use vector::Vector;
 
-#[derive(Debug, Clone, Copy, PartialEq)]
-pub(crate) enum Intersection {
-    Intersect(f64),
-    TriangleIntesersect(f64, f64, f64),
-    DoesNotIntersect,
-}
-
-impl Intersection {
-    pub(crate) fn distance(&self) -> Option<f64> {
-        match self {
-            &Intersection::Intersect(distance) => Some(distance),
-            &Intersection::TriangleIntesersect(distance, _, _) => Some(distance),
-            Intersection::DoesNotIntersect => None,
-        }
-    }
-}
+// Objects are shared by the render threads.
+pub(crate) trait Intersect: Send + Sync {
+    // Returns the closest intersection with a distance inside `interval`.
+    fn intersect(&self, ray: &Ray, interval: Interval) -> Option<Hit>;
 
-pub(crate) trait Intersect {
-    fn intersect(&self, ray: &Ray) -> Intersection;
-}
+    // Box around every point the object can be hit at. Unbounded objects
+    // such as planes return `AlignedBox::infinite`.
+    fn bounds(&self) -> AlignedBox;
 
-pub(crate) trait NormalAtPoint {
-    fn normal_at_point(&self, point: &Point, intersection: Intersection) -> Normal;
+    // Copy of the object's triangles where they are at `time`, for objects
+    // made of triangles. Mesh exporters use it.
+    fn mesh_at(&self, _time: f64) -> Option<TriangleMesh> {
+        None
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq)]
@@ -51,7 +52,10 @@ pub(crate) enum Axis {
 pub(crate) enum Transformation {
     Translation(Vector),
     Rotation(Axis, f64),
+    #[cfg_attr(not(test), allow(dead_code))]
+    Quaternion(Quaternion),
     Scale(Vector),
+    Matrix(Matrix<4, 4>),
 }
 
 impl Transformation {
@@ -63,11 +67,39 @@ impl Transformation {
                 Axis::Z => Matrix::<4, 4>::rotation_z(angle.to_radians()),
             },
             Transformation::Translation(vector) => Matrix::<4, 4>::translation(vector),
+            Transformation::Quaternion(quaternion) => quaternion.to_matrix(),
             Transformation::Scale(vector) => Matrix::<4, 4>::scale(vector),
+            Transformation::Matrix(matrix) => matrix,
         }
     }
+
+    // Maps points from after the transformation back to before it. Objects
+    // accumulate it so textures can be evaluated in object space.
+    pub(crate) fn inverse_matrix(&self) -> Matrix<4, 4> {
+        self.transformation_to_matrix()
+            .inverse()
+            .unwrap_or_else(Matrix::identity)
+    }
 }
 
 pub(crate) trait Transform {
     fn transform(&mut self, tranform: Transformation);
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quaternion_transformation_inverts() {
+        let rotation = Quaternion::from_axis_angle(Vector::new(1.0, 0.0, 1.0), 0.9);
+        let transformation = Transformation::Quaternion(rotation);
+        let product = transformation.transformation_to_matrix() * transformation.inverse_matrix();
+        for i in 0..4 {
+            for j in 0..4 {
+                let expected = if i == j { 1.0 } else { 0.0 };
+                assert!((product[i][j] - expected).abs() < 1e-12);
+            }
+        }
+    }
+}
@@ -3,10 +3,8 @@ use crate::geometry::point::Point;
 use crate::geometry::ray::Ray;
 use crate::geometry::vector::Vector;
 
-use super::plane::Plane;
+use super::hit::{orthonormal_basis, Hit, Interval};
 use super::Intersect;
-use super::Intersection;
-use super::NormalAtPoint;
 
 #[derive(Debug, Clone, Copy)]
 pub(crate) struct AlignedBox {
@@ -19,81 +17,163 @@ impl AlignedBox {
         AlignedBox { min, max }
     }
 
-    pub(crate) fn from_dimensions(
-        center: Point,
-        width: f64,
-        height: f64,
-        length: f64,
-    ) -> AlignedBox {
-        let size_vector = Vector::new(width, height, length);
-        let center: Vector = center.into();
-        AlignedBox::new((center - size_vector).into(), (center + size_vector).into())
+    // Box that contains nothing, growing it by any point yields that point.
+    pub(crate) fn empty() -> AlignedBox {
+        AlignedBox::new(
+            Point::new(f64::INFINITY, f64::INFINITY, f64::INFINITY),
+            Point::new(f64::NEG_INFINITY, f64::NEG_INFINITY, f64::NEG_INFINITY),
+        )
     }
-}
 
-impl Intersect for AlignedBox {
-    fn intersect(&self, ray: &Ray) -> Intersection {
-        let mut tmin = (self.min.x - ray.origin.x) / ray.direction.x;
-        let mut tmax = (self.max.x - ray.origin.x) / ray.direction.x;
+    // Box that contains everything.
+    pub(crate) fn infinite() -> AlignedBox {
+        AlignedBox::new(
+            Point::new(f64::NEG_INFINITY, f64::NEG_INFINITY, f64::NEG_INFINITY),
+            Point::new(f64::INFINITY, f64::INFINITY, f64::INFINITY),
+        )
+    }
 
-        let swap_tmin_tmax = |tmin: &mut f64, tmax: &mut f64| {
-            if tmin > tmax {
-                std::mem::swap(tmin, tmax);
-            }
-        };
-        swap_tmin_tmax(&mut tmin, &mut tmax);
+    pub(crate) fn min(&self) -> Point {
+        self.min
+    }
 
-        let mut tymin = (self.min.y - ray.origin.y) / ray.direction.y;
-        let mut tymax = (self.max.y - ray.origin.y) / ray.direction.y;
+    pub(crate) fn max(&self) -> Point {
+        self.max
+    }
 
-        swap_tmin_tmax(&mut tymin, &mut tymax);
-        if tmin > tymax || tymin > tmax {
-            return Intersection::DoesNotIntersect;
-        }
+    pub(crate) fn is_finite(&self) -> bool {
+        [
+            self.min.x, self.min.y, self.min.z, self.max.x, self.max.y, self.max.z,
+        ]
+        .iter()
+        .all(|value| value.is_finite())
+    }
 
-        if tymin > tmin {
-            tmin = tymin;
-        }
+    // The eight corners, in no particular order.
+    pub(crate) fn corners(&self) -> [Point; 8] {
+        [0, 1, 2, 3, 4, 5, 6, 7].map(|i| {
+            Point::new(
+                if i & 1 == 0 { self.min.x } else { self.max.x },
+                if i & 2 == 0 { self.min.y } else { self.max.y },
+                if i & 4 == 0 { self.min.z } else { self.max.z },
+            )
+        })
+    }
 
-        if tymax < tmax {
-            tmax = tymax;
-        }
+    pub(crate) fn is_empty(&self) -> bool {
+        self.min.x > self.max.x || self.min.y > self.max.y || self.min.z > self.max.z
+    }
 
-        let mut tzmin = (self.min.z - ray.origin.z) / ray.direction.z;
-        let mut tzmax = (self.max.z - ray.origin.z) / ray.direction.z;
+    pub(crate) fn grow(&mut self, point: Point) {
+        self.min = Point::new(
+            self.min.x.min(point.x),
+            self.min.y.min(point.y),
+            self.min.z.min(point.z),
+        );
+        self.max = Point::new(
+            self.max.x.max(point.x),
+            self.max.y.max(point.y),
+            self.max.z.max(point.z),
+        );
+    }
 
-        swap_tmin_tmax(&mut tzmin, &mut tzmax);
-        if tmin > tzmax || tzmin > tmax {
-            return Intersection::DoesNotIntersect;
-        }
-        if tzmin > tmin {
-            tmin = tzmin;
-        }
+    pub(crate) fn union(&self, other: &AlignedBox) -> AlignedBox {
+        let mut result = *self;
+        result.grow(other.min);
+        result.grow(other.max);
+        result
+    }
 
-        if tmin.is_infinite() || tmin.is_nan() {
-            Intersection::DoesNotIntersect
-        } else {
-            Intersection::Intersect(tmin)
+    pub(crate) fn center(&self) -> Point {
+        Point::new(
+            (self.min.x + self.max.x) / 2.0,
+            (self.min.y + self.max.y) / 2.0,
+            (self.min.z + self.max.z) / 2.0,
+        )
+    }
+
+    pub(crate) fn size(&self) -> Vector {
+        self.max - self.min
+    }
+
+    pub(crate) fn surface_area(&self) -> f64 {
+        if self.is_empty() {
+            return 0.0;
         }
+        let size = self.size();
+        2.0 * (size.x * size.y + size.y * size.z + size.z * size.x)
+    }
+
+    // Slab test used by acceleration structures. `inverse_direction` is passed
+    // in so it is computed once per ray instead of once per box.
+    pub(crate) fn is_hit(&self, ray: &Ray, inverse_direction: Vector, interval: Interval) -> bool {
+        let tx1 = (self.min.x - ray.origin.x) * inverse_direction.x;
+        let tx2 = (self.max.x - ray.origin.x) * inverse_direction.x;
+        let ty1 = (self.min.y - ray.origin.y) * inverse_direction.y;
+        let ty2 = (self.max.y - ray.origin.y) * inverse_direction.y;
+        let tz1 = (self.min.z - ray.origin.z) * inverse_direction.z;
+        let tz2 = (self.max.z - ray.origin.z) * inverse_direction.z;
+
+        let t_enter = tx1.min(tx2).max(ty1.min(ty2)).max(tz1.min(tz2));
+        let t_exit = tx1.max(tx2).min(ty1.max(ty2)).min(tz1.max(tz2));
+        t_enter <= t_exit && t_exit >= interval.min && t_enter <= interval.max
     }
 }
 
-impl NormalAtPoint for AlignedBox {
-    fn normal_at_point(&self, point: &Point, _: Intersection) -> Normal {
-        if (point.x - self.min.x).abs() < 0.001 {
-            Normal::new(-1., 0., 0.)
-        } else if (point.x - self.max.x).abs() < 0.001 {
-            Normal::new(01., 0., 0.)
-        } else if (point.y - self.min.y).abs() < 0.001 {
-            Normal::new(0., -1., 0.)
-        } else if (point.y - self.max.y).abs() < 0.001 {
-            Normal::new(0., 1., 0.)
-        } else if (point.z - self.min.z).abs() < 0.001 {
-            Normal::new(0., 0., -1.)
-        } else if (point.z - self.max.z).abs() < 0.001 {
-            Normal::new(0., 0., 1.)
-        } else {
-            panic!("point is not in the box {:?}", point);
+impl Intersect for AlignedBox {
+    fn intersect(&self, ray: &Ray, interval: Interval) -> Option<Hit> {
+        let origin = [ray.origin.x, ray.origin.y, ray.origin.z];
+        let direction = [ray.direction.x, ray.direction.y, ray.direction.z];
+        let min = [self.min.x, self.min.y, self.min.z];
+        let max = [self.max.x, self.max.y, self.max.z];
+
+        // Distances where the ray enters and leaves the box, with the axis
+        // and side of the slab responsible for each.
+        let mut enter = (f64::NEG_INFINITY, 0, -1.0);
+        let mut exit = (f64::INFINITY, 0, 1.0);
+        for axis in 0..3 {
+            let mut t_near = (min[axis] - origin[axis]) / direction[axis];
+            let mut t_far = (max[axis] - origin[axis]) / direction[axis];
+            let mut near_side = -1.0;
+            if t_near > t_far {
+                std::mem::swap(&mut t_near, &mut t_far);
+                near_side = 1.0;
+            }
+            if t_near > enter.0 {
+                enter = (t_near, axis, near_side);
+            }
+            if t_far < exit.0 {
+                exit = (t_far, axis, -near_side);
+            }
         }
+
+        if enter.0 > exit.0 {
+            return None;
+        }
+        let (t, axis, side) = if interval.contains(enter.0) {
+            enter
+        } else if interval.contains(exit.0) {
+            exit
+        } else {
+            return None;
+        };
+        let normal = match axis {
+            0 => Normal::new(side, 0., 0.),
+            1 => Normal::new(0., side, 0.),
+            _ => Normal::new(0., 0., side),
+        };
+        // Every face is mapped to [0, 1], the basis vectors are axis aligned.
+        let (tangent, bitangent) = orthonormal_basis(normal);
+        let local = ray.at(t) - self.center();
+        let size = self.size();
+        let uv = (
+            0.5 + local.dot(tangent) / size.dot(tangent).abs(),
+            0.5 + local.dot(bitangent) / size.dot(bitangent).abs(),
+        );
+        Some(Hit::new(ray, t, normal).with_uv(uv).with_tangent(tangent))
+    }
+
+    fn bounds(&self) -> AlignedBox {
+        *self
     }
 }
@@ -0,0 +1,209 @@
+use super::aligned_box::AlignedBox;
+use super::hit::Interval;
+use super::point::Point;
+use super::ray::Ray;
+use super::vector::Vector;
+
+const MAX_LEAF_SIZE: usize = 4;
+const BIN_COUNT: usize = 12;
+
+#[derive(Debug, Clone, Copy)]
+struct BvhNode {
+    bounds: AlignedBox,
+    // For leaves the range of `Bvh::indices`, for inner nodes `start` is the
+    // index of the second child (the first one always follows its parent).
+    start: usize,
+    count: usize,
+}
+
+impl BvhNode {
+    fn is_leaf(&self) -> bool {
+        self.count > 0
+    }
+}
+
+// Bounding volume hierarchy over primitives identified by their index. The
+// tree only knows the primitive bounds, the actual intersection test is
+// supplied by the owner during traversal.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Bvh {
+    nodes: Vec<BvhNode>,
+    indices: Vec<usize>,
+}
+
+impl Bvh {
+    pub(crate) fn build(bounds: &[AlignedBox]) -> Bvh {
+        let mut bvh = Bvh {
+            nodes: Vec::with_capacity(bounds.len() * 2),
+            indices: (0..bounds.len()).collect(),
+        };
+        if !bounds.is_empty() {
+            let centers: Vec<Point> = bounds.iter().map(|b| b.center()).collect();
+            bvh.build_node(bounds, &centers, 0, bounds.len());
+        }
+        bvh
+    }
+
+    pub(crate) fn bounds(&self) -> AlignedBox {
+        self.nodes
+            .first()
+            .map(|node| node.bounds)
+            .unwrap_or_else(AlignedBox::empty)
+    }
+
+    fn build_node(&mut self, bounds: &[AlignedBox], centers: &[Point], start: usize, end: usize) {
+        let node_index = self.nodes.len();
+        let node_bounds = self.indices[start..end]
+            .iter()
+            .fold(AlignedBox::empty(), |acc, &i| acc.union(&bounds[i]));
+        self.nodes.push(BvhNode {
+            bounds: node_bounds,
+            start,
+            count: end - start,
+        });
+
+        let count = end - start;
+        if count <= MAX_LEAF_SIZE {
+            return;
+        }
+
+        let Some(middle) = self.split(bounds, centers, start, end, &node_bounds) else {
+            return;
+        };
+
+        self.build_node(bounds, centers, start, middle);
+        let second_child = self.nodes.len();
+        self.build_node(bounds, centers, middle, end);
+        self.nodes[node_index].start = second_child;
+        self.nodes[node_index].count = 0;
+    }
+
+    // Binned surface area heuristic. Returns the partition point or `None` if
+    // keeping the primitives in one leaf is cheaper.
+    fn split(
+        &mut self,
+        bounds: &[AlignedBox],
+        centers: &[Point],
+        start: usize,
+        end: usize,
+        node_bounds: &AlignedBox,
+    ) -> Option<usize> {
+        let mut center_bounds = AlignedBox::empty();
+        for &i in &self.indices[start..end] {
+            center_bounds.grow(centers[i]);
+        }
+
+        let mut best: Option<(usize, f64, f64)> = None;
+        for axis in 0..3 {
+            let low = component(center_bounds.min(), axis);
+            let high = component(center_bounds.max(), axis);
+            if high - low <= f64::EPSILON {
+                continue;
+            }
+
+            let mut bins = [(AlignedBox::empty(), 0usize); BIN_COUNT];
+            let scale = BIN_COUNT as f64 / (high - low);
+            for &i in &self.indices[start..end] {
+                let bin = bin_index(component(centers[i], axis), low, scale);
+                bins[bin].0 = bins[bin].0.union(&bounds[i]);
+                bins[bin].1 += 1;
+            }
+
+            for split in 1..BIN_COUNT {
+                let (left, left_count) = bins[..split]
+                    .iter()
+                    .fold((AlignedBox::empty(), 0), |(b, c), bin| {
+                        (b.union(&bin.0), c + bin.1)
+                    });
+                let (right, right_count) = bins[split..]
+                    .iter()
+                    .fold((AlignedBox::empty(), 0), |(b, c), bin| {
+                        (b.union(&bin.0), c + bin.1)
+                    });
+                if left_count == 0 || right_count == 0 {
+                    continue;
+                }
+                let cost = left.surface_area() * left_count as f64
+                    + right.surface_area() * right_count as f64;
+                if best.is_none_or(|(_, _, best_cost)| cost < best_cost) {
+                    let threshold = low + split as f64 / scale;
+                    best = Some((axis, threshold, cost));
+                }
+            }
+        }
+
+        let (axis, threshold, cost) = best?;
+        let leaf_cost = node_bounds.surface_area() * (end - start) as f64;
+        if cost >= leaf_cost && end - start <= MAX_LEAF_SIZE * 4 {
+            return None;
+        }
+
+        let slice = &mut self.indices[start..end];
+        let mut left = 0;
+        for i in 0..slice.len() {
+            if component(centers[slice[i]], axis) < threshold {
+                slice.swap(i, left);
+                left += 1;
+            }
+        }
+        if left == 0 || left == slice.len() {
+            left = slice.len() / 2;
+        }
+        Some(start + left)
+    }
+
+    // Visits every primitive whose node bounds are hit inside the interval.
+    // `test` receives the primitive index and the interval shrunk to the
+    // closest hit so far and returns the distance of a closer hit, if any.
+    // Returns the closest primitive with its distance.
+    pub(crate) fn intersect(
+        &self,
+        ray: &Ray,
+        interval: Interval,
+        mut test: impl FnMut(usize, Interval) -> Option<f64>,
+    ) -> Option<(usize, f64)> {
+        if self.nodes.is_empty() {
+            return None;
+        }
+        let direction = Vector::from(ray.direction);
+        let inverse_direction =
+            Vector::new(1.0 / direction.x, 1.0 / direction.y, 1.0 / direction.z);
+
+        let mut closest: Option<(usize, f64)> = None;
+        let mut interval = interval;
+        let mut stack = Vec::with_capacity(64);
+        stack.push(0);
+        while let Some(node_index) = stack.pop() {
+            let node = &self.nodes[node_index];
+            if !node.bounds.is_hit(ray, inverse_direction, interval) {
+                continue;
+            }
+            if node.is_leaf() {
+                for &primitive in &self.indices[node.start..node.start + node.count] {
+                    if let Some(t) = test(primitive, interval) {
+                        if interval.contains(t) {
+                            interval = interval.with_max(t);
+                            closest = Some((primitive, t));
+                        }
+                    }
+                }
+            } else {
+                stack.push(node.start);
+                stack.push(node_index + 1);
+            }
+        }
+        closest
+    }
+}
+
+fn component(point: Point, axis: usize) -> f64 {
+    match axis {
+        0 => point.x,
+        1 => point.y,
+        _ => point.z,
+    }
+}
+
+fn bin_index(value: f64, low: f64, scale: f64) -> usize {
+    (((value - low) * scale) as usize).min(BIN_COUNT - 1)
+}
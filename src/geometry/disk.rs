@@ -3,40 +3,107 @@ use crate::geometry::point::Point;
 use crate::geometry::ray::Ray;
 use crate::geometry::vector::Vector;
 
+use super::aligned_box::AlignedBox;
+use super::hit::{orthonormal_basis, Hit, Interval};
+use super::matrix::Matrix;
 use super::plane::Plane;
-use super::Intersect;
-use super::Intersection;
+use super::{Intersect, Transform, Transformation};
 
+#[cfg_attr(not(test), allow(dead_code))]
 pub(crate) struct Disk {
     center: Point,
     radius: f64,
     normal: Normal,
+    world_to_object: Matrix<4, 4>,
 }
 
+#[cfg_attr(not(test), allow(dead_code))]
 impl Disk {
     pub(crate) fn new(center: Point, radius: f64, normal: Normal) -> Disk {
         Disk {
             center,
             radius,
             normal,
+            world_to_object: Matrix::identity(),
         }
     }
 }
 
 impl Intersect for Disk {
-    fn intersect(&self, ray: &Ray) -> Intersection {
+    fn intersect(&self, ray: &Ray, interval: Interval) -> Option<Hit> {
         let plane = Plane::new(self.normal, self.center);
-        match dbg!(plane.intersect(ray)) {
-            Intersection::Intersect(t) if t > 0. => {
-                let point = ray.at(t);
-                let distance = (Vector::from(point) - Vector::from(self.center)).length();
-                if distance < self.radius {
-                    Intersection::Intersect(t)
-                } else {
-                    Intersection::DoesNotIntersect
-                }
-            }
-            _ => Intersection::DoesNotIntersect,
+        let hit = plane.intersect(ray, interval)?;
+        let local = hit.point - self.center;
+        if local.length() >= self.radius {
+            return None;
         }
+        // The plane maps in world units, rescale so the disk covers [0, 1].
+        let (tangent, bitangent) = orthonormal_basis(self.normal);
+        let diameter = 2.0 * self.radius;
+        let uv = (
+            0.5 + local.dot(tangent) / diameter,
+            0.5 + local.dot(bitangent) / diameter,
+        );
+        Some(hit.with_uv(uv).with_world_to_object(self.world_to_object))
+    }
+
+    // Along each axis the disk reaches `radius * sin` of the angle between
+    // the axis and the normal.
+    fn bounds(&self) -> AlignedBox {
+        let reach = |component: f64| self.radius * (1.0 - component * component).max(0.0).sqrt();
+        let extent = Vector::new(
+            reach(self.normal.x),
+            reach(self.normal.y),
+            reach(self.normal.z),
+        );
+        AlignedBox::new(self.center + -extent, self.center + extent)
+    }
+}
+
+impl Transform for Disk {
+    fn transform(&mut self, transform: Transformation) {
+        let matrix = transform.transformation_to_matrix();
+        let inverse = transform.inverse_matrix();
+        self.center = matrix * self.center;
+        self.normal = inverse.transpose() * self.normal;
+        // TODO: non-uniform scale turns the disk into an ellipse.
+        let (_, _, scale) = matrix.decompose();
+        self.radius *= scale.x.abs().max(scale.y.abs()).max(scale.z.abs());
+        self.world_to_object = self.world_to_object * inverse;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn down(x: f64, z: f64) -> Ray {
+        Ray::new(Point::new(x, 1.0, z), Normal::new(0.0, -1.0, 0.0))
+    }
+
+    #[test]
+    fn uv_covers_the_disk() {
+        let disk = Disk::new(Point::new(0.0, 0.0, 0.0), 2.0, Normal::new(0.0, 1.0, 0.0));
+        let center = disk
+            .intersect(&down(0.0, 0.0), Interval::positive())
+            .unwrap();
+        assert!((center.uv.0 - 0.5).abs() < 1e-12 && (center.uv.1 - 0.5).abs() < 1e-12);
+        let edge = disk
+            .intersect(&down(1.9, 0.0), Interval::positive())
+            .unwrap();
+        let offset = (edge.uv.0 - 0.5).hypot(edge.uv.1 - 0.5);
+        assert!((offset - 0.475).abs() < 1e-9);
+        assert!(disk
+            .intersect(&down(2.1, 0.0), Interval::positive())
+            .is_none());
+    }
+
+    #[test]
+    fn bounds_are_flat_along_the_normal() {
+        let disk = Disk::new(Point::new(1.0, 2.0, 3.0), 2.0, Normal::new(0.0, 1.0, 0.0));
+        let bounds = disk.bounds();
+        let (min, max) = (bounds.min(), bounds.max());
+        assert_eq!((min.x, min.y, min.z), (-1.0, 2.0, 1.0));
+        assert_eq!((max.x, max.y, max.z), (3.0, 2.0, 5.0));
     }
 }
@@ -0,0 +1,165 @@
+use super::matrix::Matrix;
+use super::normal::Normal;
+use super::point::Point;
+use super::ray::Ray;
+use super::vector::Vector;
+
+// Range of ray distances an intersection is accepted in, both ends exclusive.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct Interval {
+    pub(crate) min: f64,
+    pub(crate) max: f64,
+}
+
+impl Interval {
+    pub(crate) fn new(min: f64, max: f64) -> Interval {
+        Interval { min, max }
+    }
+
+    // Everything in front of the ray origin.
+    pub(crate) fn positive() -> Interval {
+        Interval::new(f64::EPSILON, f64::INFINITY)
+    }
+
+    pub(crate) fn contains(&self, t: f64) -> bool {
+        self.min < t && t < self.max
+    }
+
+    pub(crate) fn with_max(&self, max: f64) -> Interval {
+        Interval::new(self.min, max)
+    }
+}
+
+// Everything the renderer needs to know about a ray/surface intersection.
+// Both normals face against the incoming ray, `front_face` tells whether the
+// ray hit the outside of the surface. `object_point` is the hit point before
+// any transformation was applied to the object and `world_to_object` takes
+// world space points and vectors there. `time` is copied from the
+// ray so rays leaving the surface see the scene at the same moment. `color`
+// is the interpolated vertex color, RGB in [0, 1], of meshes that have one.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Hit {
+    pub(crate) distance: f64,
+    pub(crate) point: Point,
+    pub(crate) object_point: Point,
+    pub(crate) world_to_object: Matrix<4, 4>,
+    pub(crate) geometric_normal: Normal,
+    pub(crate) shading_normal: Normal,
+    pub(crate) uv: (f64, f64),
+    pub(crate) tangent: Vector,
+    pub(crate) bitangent: Vector,
+    pub(crate) front_face: bool,
+    pub(crate) primitive_id: usize,
+    pub(crate) time: f64,
+    pub(crate) color: Option<Vector>,
+}
+
+impl Hit {
+    // Creates a hit from the outward facing surface normal. The shading normal
+    // starts out equal to the geometric one with an arbitrary tangent frame.
+    pub(crate) fn new(ray: &Ray, distance: f64, outward_normal: Normal) -> Hit {
+        let front_face = Vector::from(ray.direction).dot(outward_normal.into()) < 0.0;
+        let normal = if front_face {
+            outward_normal
+        } else {
+            -outward_normal
+        };
+        let (tangent, bitangent) = orthonormal_basis(normal);
+        let point = ray.at(distance);
+        Hit {
+            distance,
+            point,
+            object_point: point,
+            world_to_object: Matrix::identity(),
+            geometric_normal: normal,
+            shading_normal: normal,
+            uv: (0.0, 0.0),
+            tangent,
+            bitangent,
+            front_face,
+            primitive_id: 0,
+            time: ray.time,
+            color: None,
+        }
+    }
+
+    // Sets the outward facing shading normal, flipped to the same side as the
+    // geometric normal, and rebuilds the tangent frame around it.
+    pub(crate) fn with_shading_normal(mut self, outward_normal: Normal) -> Hit {
+        self.shading_normal = if self.front_face {
+            outward_normal
+        } else {
+            -outward_normal
+        };
+        self.with_tangents(self.tangent, self.bitangent)
+    }
+
+    // Replaces the shading normal with one that already faces the ray, as
+    // produced by normal and bump maps, keeping the tangent frame handedness.
+    pub(crate) fn with_perturbed_normal(mut self, normal: Normal) -> Hit {
+        self.shading_normal = normal;
+        self.with_tangents(self.tangent, self.bitangent)
+    }
+
+    // Orthogonalizes `tangent` against the shading normal, for example the
+    // surface derivative along u. Degenerate tangents keep the current frame.
+    pub(crate) fn with_tangent(self, tangent: Vector) -> Hit {
+        let bitangent = Vector::from(self.shading_normal).cross(tangent);
+        self.with_tangents(tangent, bitangent)
+    }
+
+    // Like `with_tangent`, but the bitangent is flipped to point along
+    // `bitangent`, for example the surface derivative along v. This keeps
+    // mirrored texture coordinates mirrored in the tangent frame.
+    pub(crate) fn with_tangents(mut self, tangent: Vector, bitangent: Vector) -> Hit {
+        let normal = Vector::from(self.shading_normal);
+        let tangent = tangent - normal * normal.dot(tangent);
+        if tangent.length() < 1e-12 {
+            (self.tangent, self.bitangent) = orthonormal_basis(self.shading_normal);
+        } else {
+            let tangent = Vector::from(tangent.normalize());
+            let cross = normal.cross(tangent);
+            self.tangent = tangent;
+            self.bitangent = if cross.dot(bitangent) < 0.0 {
+                -cross
+            } else {
+                cross
+            };
+        }
+        self
+    }
+
+    pub(crate) fn with_world_to_object(mut self, world_to_object: Matrix<4, 4>) -> Hit {
+        self.object_point = world_to_object * self.point;
+        self.world_to_object = world_to_object;
+        self
+    }
+
+    pub(crate) fn with_uv(mut self, uv: (f64, f64)) -> Hit {
+        self.uv = uv;
+        self
+    }
+
+    pub(crate) fn with_primitive_id(mut self, primitive_id: usize) -> Hit {
+        self.primitive_id = primitive_id;
+        self
+    }
+
+    pub(crate) fn with_color(mut self, color: Vector) -> Hit {
+        self.color = Some(color);
+        self
+    }
+}
+
+// Builds two unit vectors perpendicular to the normal and to each other.
+pub(crate) fn orthonormal_basis(normal: Normal) -> (Vector, Vector) {
+    let n = Vector::from(normal);
+    let helper = if normal.x.abs() > 0.9 {
+        Vector::new(0.0, 1.0, 0.0)
+    } else {
+        Vector::new(1.0, 0.0, 0.0)
+    };
+    let tangent = Vector::from(helper.cross(n).normalize());
+    let bitangent = n.cross(tangent);
+    (tangent, bitangent)
+}
@@ -0,0 +1,154 @@
+use super::aligned_box::AlignedBox;
+use super::hit::{Hit, Interval};
+use super::matrix::Matrix;
+use super::motion::Motion;
+use super::ray::Ray;
+use super::triangle_mesh::TriangleMesh;
+use super::vector::Vector;
+use super::{Intersect, Transform, Transformation};
+
+// Places an object by a transform that changes over time. Rays are moved
+// into the object's space at their own time, so an object that moves while
+// the shutter is open is blurred along its path. The wrapped object keeps
+// its texture space.
+pub(crate) struct Instance {
+    object: Box<dyn Intersect>,
+    motion: Motion,
+    // Transformations applied to the instance after the motion.
+    placement: Matrix<4, 4>,
+}
+
+impl Instance {
+    pub(crate) fn new(object: Box<dyn Intersect>, motion: Motion) -> Instance {
+        Instance {
+            object,
+            motion,
+            placement: Matrix::identity(),
+        }
+    }
+
+    #[cfg_attr(not(test), allow(dead_code))]
+    pub(crate) fn motion(&self) -> &Motion {
+        &self.motion
+    }
+
+    pub(crate) fn object_to_world(&self, time: f64) -> Matrix<4, 4> {
+        self.placement * self.motion.matrix_at(time)
+    }
+}
+
+impl Intersect for Instance {
+    fn intersect(&self, ray: &Ray, interval: Interval) -> Option<Hit> {
+        let object_to_world = self.object_to_world(ray.time);
+        let world_to_object = object_to_world.inverse()?;
+
+        // The object space direction is normalized, which scales distances
+        // along the ray by `scale`.
+        let direction = world_to_object * Vector::from(ray.direction);
+        let scale = direction.length();
+        if scale == 0.0 {
+            return None;
+        }
+        let local = Ray::new(
+            world_to_object * ray.origin,
+            (direction / scale).normalize(),
+        )
+        .with_time(ray.time);
+        let local_interval = Interval::new(interval.min * scale, interval.max * scale);
+        let hit = self.object.intersect(&local, local_interval)?;
+
+        // Normals go through the inverse transpose, which keeps them facing
+        // the ray.
+        let normal_matrix = world_to_object.transpose();
+        let tangent = object_to_world * hit.tangent;
+        let bitangent = object_to_world * hit.bitangent;
+        let world_hit = Hit {
+            distance: hit.distance / scale,
+            point: object_to_world * hit.point,
+            geometric_normal: normal_matrix * hit.geometric_normal,
+            shading_normal: normal_matrix * hit.shading_normal,
+            world_to_object: hit.world_to_object * world_to_object,
+            time: ray.time,
+            ..hit
+        };
+        Some(world_hit.with_tangents(tangent, bitangent))
+    }
+
+    // Rotations can swing the object outside the boxes at the keyframes, so
+    // the motion is also sampled in between.
+    fn bounds(&self) -> AlignedBox {
+        const STEPS: usize = 8;
+        let object_bounds = self.object.bounds();
+        if !object_bounds.is_finite() {
+            return object_bounds;
+        }
+        let keyframes = self.motion.keyframes();
+        let mut times = vec![keyframes.first().map_or(0.0, |key| key.time)];
+        for pair in keyframes.windows(2) {
+            let (start, end) = (pair[0].time, pair[1].time);
+            times
+                .extend((1..=STEPS).map(|step| start + (end - start) * step as f64 / STEPS as f64));
+        }
+        let mut bounds = AlignedBox::empty();
+        for time in times {
+            let object_to_world = self.object_to_world(time);
+            for corner in object_bounds.corners() {
+                bounds.grow(object_to_world * corner);
+            }
+        }
+        bounds
+    }
+
+    fn mesh_at(&self, time: f64) -> Option<TriangleMesh> {
+        let mut mesh = self.object.mesh_at(time)?;
+        mesh.transform(Transformation::Matrix(self.object_to_world(time)));
+        Some(mesh)
+    }
+}
+
+impl Transform for Instance {
+    fn transform(&mut self, transformation: Transformation) {
+        self.placement = transformation.transformation_to_matrix() * self.placement;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geometry::normal::Normal;
+    use crate::geometry::point::Point;
+    use crate::geometry::quaternion::Quaternion;
+    use crate::geometry::sphere::Sphere;
+    use crate::geometry::trs::Trs;
+
+    fn moving_sphere() -> Instance {
+        let at = |x: f64| {
+            Trs::new(
+                Vector::new(x, 0.0, 0.0),
+                Quaternion::identity(),
+                Vector::new(1.0, 1.0, 1.0),
+            )
+        };
+        let sphere = Sphere::new(Point::new(0.0, 0.0, 0.0), 1.0);
+        Instance::new(Box::new(sphere), Motion::linear(at(0.0), at(4.0)))
+    }
+
+    #[test]
+    fn rays_see_the_object_at_their_time() {
+        let instance = moving_sphere();
+        assert_eq!(instance.motion().keyframes().len(), 2);
+        let ray = Ray::new(Point::new(4.0, 0.0, -5.0), Normal::new(0.0, 0.0, 1.0));
+        assert!(instance.intersect(&ray, Interval::positive()).is_none());
+        let hit = instance
+            .intersect(&ray.with_time(1.0), Interval::positive())
+            .unwrap();
+        assert!((hit.distance - 4.0).abs() < 1e-9);
+        assert_eq!(hit.time, 1.0);
+    }
+
+    #[test]
+    fn bounds_cover_the_whole_path() {
+        let bounds = moving_sphere().bounds();
+        assert!(bounds.min().x <= -1.0 && bounds.max().x >= 5.0);
+    }
+}
@@ -2,6 +2,7 @@ use std::ops::{Index, IndexMut, Mul};
 
 use super::normal::Normal;
 use super::point::Point;
+use super::quaternion::Quaternion;
 use super::vector::Vector;
 
 #[derive(Debug, Clone, Copy, PartialEq)]
@@ -10,15 +11,65 @@ pub(crate) struct Matrix<const ROW: usize, const COLUMN: usize> {
 }
 
 impl<const ROW: usize, const COLUMN: usize> Matrix<ROW, COLUMN> {
-    pub(crate) fn new() -> Self {
-        Matrix {
-            data: [[0.0; COLUMN]; ROW],
+    pub(crate) fn with_data(data: [[f64; COLUMN]; ROW]) -> Self {
+        Matrix { data }
+    }
+
+    pub(crate) fn transpose(&self) -> Matrix<COLUMN, ROW> {
+        let mut data = [[0.0; ROW]; COLUMN];
+        for (i, row) in self.data.iter().enumerate() {
+            for (j, value) in row.iter().enumerate() {
+                data[j][i] = *value;
+            }
         }
+        Matrix { data }
     }
+}
 
-    pub(crate) fn with_data(data: [[f64; COLUMN]; ROW]) -> Self {
+impl<const SIZE: usize> Matrix<SIZE, SIZE> {
+    pub(crate) fn identity() -> Self {
+        let mut data = [[0.0; SIZE]; SIZE];
+        for (i, row) in data.iter_mut().enumerate() {
+            row[i] = 1.0;
+        }
         Matrix { data }
     }
+
+    // Gauss-Jordan elimination with partial pivoting. Returns `None` for
+    // singular matrices.
+    pub(crate) fn inverse(&self) -> Option<Self> {
+        let mut left = self.data;
+        let mut right = Self::identity().data;
+        for column in 0..SIZE {
+            let pivot = (column..SIZE)
+                .max_by(|&a, &b| left[a][column].abs().total_cmp(&left[b][column].abs()))?;
+            if left[pivot][column].abs() < 1e-12 {
+                return None;
+            }
+            left.swap(column, pivot);
+            right.swap(column, pivot);
+
+            let scale = 1.0 / left[column][column];
+            for j in 0..SIZE {
+                left[column][j] *= scale;
+                right[column][j] *= scale;
+            }
+            for row in 0..SIZE {
+                if row == column {
+                    continue;
+                }
+                let factor = left[row][column];
+                if factor == 0.0 {
+                    continue;
+                }
+                for j in 0..SIZE {
+                    left[row][j] -= factor * left[column][j];
+                    right[row][j] -= factor * right[column][j];
+                }
+            }
+        }
+        Some(Matrix { data: right })
+    }
 }
 
 impl Matrix<4, 4> {
@@ -29,7 +80,7 @@ impl Matrix<4, 4> {
             [1.0, 0.0, 0.0, 0.0],
             [0.0, c, -s, 0.0],
             [0.0, s, c, 0.0],
-            [0.0, 0.0, 0.0, 0.1],
+            [0.0, 0.0, 0.0, 1.0],
         ])
     }
 
@@ -40,7 +91,7 @@ impl Matrix<4, 4> {
             [c, 0.0, s, 0.0],
             [0.0, 1.0, 0.0, 0.0],
             [-s, 0.0, c, 0.0],
-            [0.0, 0.0, 0.0, 0.1],
+            [0.0, 0.0, 0.0, 1.0],
         ])
     }
 
@@ -72,6 +123,31 @@ impl Matrix<4, 4> {
             [0.0, 0.0, 0.0, 1.0],
         ])
     }
+
+    // Splits an affine matrix built as T * R * S into its translation, rotation
+    // and scale. Shear is not representable and is folded into the rotation.
+    #[cfg_attr(not(test), allow(dead_code))]
+    pub(crate) fn decompose(&self) -> (Vector, Quaternion, Vector) {
+        let translation = Vector::new(self[0][3], self[1][3], self[2][3]);
+        let column = |j: usize| Vector::new(self[0][j], self[1][j], self[2][j]);
+        let mut scale = Vector::new(column(0).length(), column(1).length(), column(2).length());
+
+        // A negative determinant means the matrix mirrors, put it on one axis.
+        let determinant = column(0).dot(column(1).cross(column(2)));
+        if determinant < 0.0 {
+            scale.x = -scale.x;
+        }
+
+        let mut rotation = Matrix::<4, 4>::identity();
+        for (j, factor) in [scale.x, scale.y, scale.z].into_iter().enumerate() {
+            if factor != 0.0 {
+                for i in 0..3 {
+                    rotation[i][j] = self[i][j] / factor;
+                }
+            }
+        }
+        (translation, Quaternion::from_matrix(&rotation), scale)
+    }
 }
 
 impl<const ROW: usize, const COLUMN: usize> Index<usize> for Matrix<ROW, COLUMN> {
@@ -130,14 +206,52 @@ impl<const COLUMN: usize> Mul<Normal> for Matrix<4, COLUMN> {
     }
 }
 
+// Points are affected by translation (w = 1), directions are not (w = 0).
 pub(crate) fn from_point(point: Point) -> Matrix<4, 1> {
     Matrix {
-        data: [[point.x], [point.y], [point.z], [0.0]],
+        data: [[point.x], [point.y], [point.z], [1.0]],
     }
 }
 
 pub(crate) fn from_vector(vector: Vector) -> Matrix<4, 1> {
     Matrix {
-        data: [[vector.x], [vector.y], [vector.z], [1.0]],
+        data: [[vector.x], [vector.y], [vector.z], [0.0]],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(a: Vector, b: Vector) {
+        assert!((a - b).length() < 1e-9, "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn decompose_recovers_translation_rotation_and_scale() {
+        let translation = Vector::new(1.0, -2.0, 3.0);
+        let rotation = Quaternion::from_axis_angle(Vector::new(1.0, 1.0, 0.0), 0.7);
+        let scale = Vector::new(2.0, 0.5, 3.0);
+        let matrix = Matrix::<4, 4>::translation(translation)
+            * rotation.to_matrix()
+            * Matrix::<4, 4>::scale(scale);
+        let (t, r, s) = matrix.decompose();
+        assert_close(t, translation);
+        assert!(r.dot(rotation).abs() > 1.0 - 1e-9);
+        assert_close(s, scale);
+    }
+
+    #[test]
+    fn decompose_puts_a_mirror_on_x() {
+        let matrix =
+            Matrix::<4, 4>::rotation_y(0.3) * Matrix::<4, 4>::scale(Vector::new(1.0, 1.0, -2.0));
+        let (t, r, s) = matrix.decompose();
+        assert!(s.x < 0.0);
+        let rebuilt = Matrix::<4, 4>::translation(t) * r.to_matrix() * Matrix::<4, 4>::scale(s);
+        for i in 0..4 {
+            for j in 0..4 {
+                assert!((rebuilt[i][j] - matrix[i][j]).abs() < 1e-9);
+            }
+        }
     }
 }
@@ -0,0 +1,131 @@
+use std::collections::HashMap;
+
+use super::normal::Normal;
+use super::triangle_mesh::TriangleMesh;
+use super::vector::Vector;
+
+// Problems the loaders fixed while building a mesh, kept with the mesh so the
+// check still reports what was wrong in the file.
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct Repairs {
+    // Vertex normals that were scaled to unit length.
+    pub(crate) normalized_normals: usize,
+    // Degenerate triangles that were left out of the mesh.
+    pub(crate) dropped_triangles: usize,
+}
+
+impl Repairs {
+    // Scales a normal from a model file to unit length. Zero and NaN normals
+    // cannot be fixed, they stay NaN and the check finds them in the mesh.
+    pub(crate) fn normalize(&mut self, normal: Vector) -> Normal {
+        let length = normal.length();
+        if length > 0.0 && length.is_finite() && !is_unit(length) {
+            self.normalized_normals += 1;
+        }
+        normal.normalize()
+    }
+}
+
+// Counts of the problems found in a triangle mesh. Edges are matched by
+// vertex position, so seams where the loaders split vertices for different
+// normals or texture coordinates still join their triangles.
+pub(crate) struct MeshCheck {
+    pub(crate) vertices: usize,
+    pub(crate) triangles: usize,
+    // Triangles with no area, whose face normal is NaN, including the ones
+    // the loader left out.
+    pub(crate) degenerate: usize,
+    // Edges shared by more than two triangles.
+    pub(crate) non_manifold_edges: usize,
+    // Edges of a single triangle, where the surface is open.
+    pub(crate) boundary_edges: usize,
+    // Edges whose two triangles run along them in the same direction, so one
+    // of the two faces the other way.
+    pub(crate) inconsistent_winding: usize,
+    pub(crate) has_normals: bool,
+    // Vertex normals that are not of unit length, zero and NaN included.
+    pub(crate) unnormalized_normals: usize,
+}
+
+impl MeshCheck {
+    pub(crate) fn new(mesh: &TriangleMesh) -> MeshCheck {
+        let positions = mesh.positions();
+        let mut welded = HashMap::new();
+        let ids: Vec<usize> = positions
+            .iter()
+            .map(|p| {
+                // Adding zero turns -0 into 0 so both weld together.
+                let key = [p.x, p.y, p.z].map(|value| (value + 0.0).to_bits());
+                let next = welded.len();
+                *welded.entry(key).or_insert(next)
+            })
+            .collect();
+
+        let mut check = MeshCheck {
+            vertices: positions.len(),
+            triangles: mesh.indices().len(),
+            degenerate: mesh.repairs().dropped_triangles,
+            non_manifold_edges: 0,
+            boundary_edges: 0,
+            inconsistent_winding: 0,
+            has_normals: !mesh.normals().is_empty(),
+            unnormalized_normals: 0,
+        };
+        // Number of triangles along every edge and how many of them run from
+        // the lower to the higher vertex.
+        let mut edges: HashMap<(usize, usize), (usize, usize)> = HashMap::new();
+        for triangle in mesh.indices() {
+            let [a, b, c] = triangle.map(|i| positions[i as usize]);
+            let (ab, ac) = (b - a, c - a);
+            let longest = [ab, ac, c - b]
+                .iter()
+                .map(|edge| edge.dot(*edge))
+                .fold(0.0, f64::max);
+            let area = ab.cross(ac).length();
+            if !area.is_finite() || area <= f64::EPSILON * longest {
+                check.degenerate += 1;
+            }
+
+            let corners = triangle.map(|i| ids[i as usize]);
+            for k in 0..3 {
+                let (from, to) = (corners[k], corners[(k + 1) % 3]);
+                if from == to {
+                    continue;
+                }
+                let edge = edges.entry((from.min(to), from.max(to))).or_default();
+                edge.0 += 1;
+                edge.1 += (from < to) as usize;
+            }
+        }
+        for (count, forward) in edges.into_values() {
+            match count {
+                1 => check.boundary_edges += 1,
+                2 if forward != 1 => check.inconsistent_winding += 1,
+                2 => {}
+                _ => check.non_manifold_edges += 1,
+            }
+        }
+
+        check.unnormalized_normals = mesh.repairs().normalized_normals
+            + mesh
+                .normals()
+                .iter()
+                .filter(|&&normal| !is_unit(Vector::from(normal).length()))
+                .count();
+        check
+    }
+
+    // Problems that make the mesh render wrongly. Open boundaries and missing
+    // normals are allowed, such meshes are simply flat shaded or thin.
+    pub(crate) fn errors(&self) -> usize {
+        self.degenerate
+            + self.non_manifold_edges
+            + self.inconsistent_winding
+            + self.unnormalized_normals
+    }
+}
+
+// NaN lengths are not of unit length either.
+fn is_unit(length: f64) -> bool {
+    (length - 1.0).abs() <= 1e-3
+}
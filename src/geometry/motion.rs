@@ -0,0 +1,232 @@
+use super::matrix::Matrix;
+use super::quaternion::Quaternion;
+use super::trs::Trs;
+use super::vector::Vector;
+
+// How a keyframe blends into the next one.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Interpolation {
+    Linear,
+    // Smooth curve through the neighbouring keyframes. It eases in and out
+    // at the first and last keyframe.
+    Bezier,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Keyframe {
+    pub(crate) time: f64,
+    pub(crate) trs: Trs,
+    pub(crate) interpolation: Interpolation,
+}
+
+impl Keyframe {
+    pub(crate) fn new(time: f64, trs: Trs) -> Keyframe {
+        Keyframe {
+            time,
+            trs,
+            interpolation: Interpolation::Linear,
+        }
+    }
+
+    pub(crate) fn with_interpolation(mut self, interpolation: Interpolation) -> Keyframe {
+        self.interpolation = interpolation;
+        self
+    }
+}
+
+// Transform that changes over time, given as keyframes interpolated between
+// their neighbours. Before the first and after the last keyframe it holds
+// still.
+#[derive(Debug, Clone)]
+pub(crate) struct Motion {
+    // Sorted by time.
+    keyframes: Vec<Keyframe>,
+}
+
+impl Motion {
+    pub(crate) fn new(mut keyframes: Vec<Keyframe>) -> Motion {
+        keyframes.sort_by(|a, b| a.time.total_cmp(&b.time));
+        Motion { keyframes }
+    }
+
+    // Moves from `start` at time 0 to `end` at time 1.
+    pub(crate) fn linear(start: Trs, end: Trs) -> Motion {
+        Motion::new(vec![Keyframe::new(0.0, start), Keyframe::new(1.0, end)])
+    }
+
+    #[cfg_attr(not(test), allow(dead_code))]
+    pub(crate) fn still(trs: Trs) -> Motion {
+        Motion::new(vec![Keyframe::new(0.0, trs)])
+    }
+
+    pub(crate) fn keyframes(&self) -> &[Keyframe] {
+        &self.keyframes
+    }
+
+    #[cfg_attr(not(test), allow(dead_code))]
+    pub(crate) fn is_still(&self) -> bool {
+        self.keyframes.len() < 2
+    }
+
+    pub(crate) fn at(&self, time: f64) -> Trs {
+        let Some(first) = self.keyframes.first() else {
+            return Trs::identity();
+        };
+        let next = self.keyframes.partition_point(|key| key.time <= time);
+        if next == 0 || time <= first.time {
+            return first.trs;
+        }
+        if next == self.keyframes.len() {
+            return self.keyframes[next - 1].trs;
+        }
+        let (from, to) = (&self.keyframes[next - 1], &self.keyframes[next]);
+        let t = (time - from.time) / (to.time - from.time);
+        match from.interpolation {
+            Interpolation::Linear => from.trs.interpolate(&to.trs, t),
+            Interpolation::Bezier => self.bezier(next - 1, t),
+        }
+    }
+
+    pub(crate) fn matrix_at(&self, time: f64) -> Matrix<4, 4> {
+        self.at(time).to_matrix()
+    }
+
+    // Cubic Bezier from keyframe `index` to the next one at `t` in [0, 1].
+    // The inner control points follow the direction between the neighbouring
+    // keyframes, like a Catmull-Rom spline, and are flat at either end of
+    // the motion.
+    fn bezier(&self, index: usize, t: f64) -> Trs {
+        let keys = &self.keyframes;
+        let (from, to) = (&keys[index], &keys[index + 1]);
+        let span = to.time - from.time;
+        // Control point offsets at a keyframe, as a share of the velocity
+        // through it.
+        let handle = |key: usize| -> Option<(usize, usize, f64)> {
+            let (before, after) = (key.checked_sub(1)?, key + 1);
+            (after < keys.len()).then(|| {
+                (
+                    before,
+                    after,
+                    span / (3.0 * (keys[after].time - keys[before].time)),
+                )
+            })
+        };
+        let start = handle(index);
+        let end = handle(index + 1);
+
+        let curve = |value: &dyn Fn(&Trs) -> Vector| -> Vector {
+            let (p0, p3) = (value(&from.trs), value(&to.trs));
+            let p1 = start.map_or(p0, |(a, b, s)| {
+                p0 + (value(&keys[b].trs) - value(&keys[a].trs)) * s
+            });
+            let p2 = end.map_or(p3, |(a, b, s)| {
+                p3 - (value(&keys[b].trs) - value(&keys[a].trs)) * s
+            });
+            cubic_bezier(p0, p1, p2, p3, t)
+        };
+        let translation = curve(&|trs| trs.translation);
+        let scale = curve(&|trs| trs.scale);
+
+        // The rotation uses the same construction on the 4D quaternion, with
+        // every keyframe on the hemisphere of the segment start, and slerps
+        // between the control points.
+        let aligned = |key: usize| {
+            let q = keys[key].trs.rotation;
+            if q.dot(from.trs.rotation) < 0.0 {
+                -q
+            } else {
+                q
+            }
+        };
+        let offset = |q: Quaternion, a: Quaternion, b: Quaternion, s: f64| {
+            Quaternion::new(
+                q.w + (b.w - a.w) * s,
+                q.x + (b.x - a.x) * s,
+                q.y + (b.y - a.y) * s,
+                q.z + (b.z - a.z) * s,
+            )
+            .normalize()
+        };
+        let (q0, q3) = (aligned(index), aligned(index + 1));
+        let q1 = start.map_or(q0, |(a, b, s)| offset(q0, aligned(a), aligned(b), s));
+        let q2 = end.map_or(q3, |(a, b, s)| offset(q3, aligned(a), aligned(b), -s));
+        let rotation = spherical_bezier(q0, q1, q2, q3, t);
+
+        Trs::new(translation, rotation, scale)
+    }
+}
+
+fn cubic_bezier(p0: Vector, p1: Vector, p2: Vector, p3: Vector, t: f64) -> Vector {
+    let a = p0.lerp(p1, t);
+    let b = p1.lerp(p2, t);
+    let c = p2.lerp(p3, t);
+    a.lerp(b, t).lerp(b.lerp(c, t), t)
+}
+
+// De Casteljau's construction with slerp in place of lerp.
+fn spherical_bezier(
+    q0: Quaternion,
+    q1: Quaternion,
+    q2: Quaternion,
+    q3: Quaternion,
+    t: f64,
+) -> Quaternion {
+    let a = q0.slerp(q1, t);
+    let b = q1.slerp(q2, t);
+    let c = q2.slerp(q3, t);
+    a.slerp(b, t).slerp(b.slerp(c, t), t)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at_x(x: f64) -> Trs {
+        Trs::new(
+            Vector::new(x, 0.0, 0.0),
+            Quaternion::identity(),
+            Vector::new(1.0, 1.0, 1.0),
+        )
+    }
+
+    #[test]
+    fn still_motion_holds_its_transform() {
+        let motion = Motion::still(at_x(3.0));
+        assert!(motion.is_still());
+        assert_eq!(motion.at(-10.0).translation.x, 3.0);
+        assert_eq!(motion.at(10.0).translation.x, 3.0);
+        assert!(!Motion::linear(at_x(0.0), at_x(1.0)).is_still());
+    }
+
+    #[test]
+    fn linear_motion_interpolates_and_holds_at_the_ends() {
+        let motion = Motion::linear(at_x(0.0), at_x(2.0));
+        assert_eq!(motion.at(0.25).translation.x, 0.5);
+        assert_eq!(motion.at(-1.0).translation.x, 0.0);
+        assert_eq!(motion.at(5.0).translation.x, 2.0);
+    }
+
+    #[test]
+    fn bezier_passes_through_keyframes_and_eases_at_the_ends() {
+        let keyframes = [(0.0, 0.0), (1.0, 1.0), (2.0, 2.0)].map(|(time, x)| {
+            Keyframe::new(time, at_x(x)).with_interpolation(Interpolation::Bezier)
+        });
+        let motion = Motion::new(keyframes.to_vec());
+        assert!((motion.at(1.0).translation.x - 1.0).abs() < 1e-12);
+        // The start is flat, so the first tenth covers less than a tenth.
+        assert!(motion.at(0.1).translation.x < 0.1);
+        // Through the middle keyframe the speed is kept.
+        let slope = (motion.at(1.0001).translation.x - motion.at(0.9999).translation.x) / 0.0002;
+        assert!((slope - 1.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn keyframes_are_sorted_by_time() {
+        let motion = Motion::new(vec![
+            Keyframe::new(2.0, at_x(2.0)),
+            Keyframe::new(0.0, at_x(0.0)),
+        ]);
+        assert_eq!(motion.keyframes()[0].time, 0.0);
+        assert_eq!(motion.at(1.0).translation.x, 1.0);
+    }
+}
@@ -3,21 +3,29 @@ use crate::geometry::point::Point;
 use crate::geometry::ray::Ray;
 use crate::geometry::vector::Vector;
 
-use super::{Intersect, Intersection, NormalAtPoint};
+use super::aligned_box::AlignedBox;
+use super::hit::{orthonormal_basis, Hit, Interval};
+use super::matrix::Matrix;
+use super::{Intersect, Transform, Transformation};
 
 pub(crate) struct Plane {
     pub(crate) normal: Normal,
     pub(crate) center: Point,
+    world_to_object: Matrix<4, 4>,
 }
 
 impl Plane {
     pub(crate) fn new(normal: Normal, center: Point) -> Plane {
-        Plane { normal, center }
+        Plane {
+            normal,
+            center,
+            world_to_object: Matrix::identity(),
+        }
     }
 }
 
 impl Intersect for Plane {
-    fn intersect(&self, ray: &Ray) -> Intersection {
+    fn intersect(&self, ray: &Ray, interval: Interval) -> Option<Hit> {
         // (p - c)*n = 0 plane equation
         // p = (o + t*d) ray equation
         // k = (o - c)
@@ -25,27 +33,43 @@ impl Intersect for Plane {
         // t*d*n = -k*n;
         // t = -k*n / d*n;
         // if d * n == 0 then the ray is parallel to the plane, so no intersection
-        // if t < 0 then the ray is pointing away from the plane, so no intersection
+        // if t is outside of the interval the plane is behind the ray or too far
 
         let normal: Vector = self.normal.into();
         let dn = Vector::from(ray.direction).dot(normal);
 
         if dn.abs() <= f64::EPSILON {
-            return Intersection::DoesNotIntersect;
+            return None;
         }
 
         let k = Vector::from(self.center) - Vector::from(ray.origin);
         let t = k.dot(normal) / dn;
-        if t > 0. {
-            Intersection::Intersect(t)
-        } else {
-            Intersection::DoesNotIntersect
+        if !interval.contains(t) {
+            return None;
         }
+        // Planar mapping in world units, so textures repeat across the plane.
+        let (tangent, bitangent) = orthonormal_basis(self.normal);
+        let local = ray.at(t) - self.center;
+        let uv = (local.dot(tangent), local.dot(bitangent));
+        let hit = Hit::new(ray, t, self.normal);
+        Some(
+            hit.with_uv(uv)
+                .with_tangent(tangent)
+                .with_world_to_object(self.world_to_object),
+        )
+    }
+
+    fn bounds(&self) -> AlignedBox {
+        AlignedBox::infinite()
     }
 }
 
-impl NormalAtPoint for Plane {
-    fn normal_at_point(&self, point: &Point, _: Intersection) -> Normal {
-        self.normal
+impl Transform for Plane {
+    fn transform(&mut self, transform: Transformation) {
+        let matrix = transform.transformation_to_matrix();
+        let inverse = transform.inverse_matrix();
+        self.center = matrix * self.center;
+        self.normal = inverse.transpose() * self.normal;
+        self.world_to_object = self.world_to_object * inverse;
     }
 }
@@ -17,7 +17,7 @@ impl Point {
         Point { x, y, z }
     }
 
-    pub(crate) fn to_vector(&self) -> Vector {
+    pub(crate) fn to_vector(self) -> Vector {
         Vector::new(self.x, self.y, self.z)
     }
 }
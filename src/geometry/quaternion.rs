@@ -0,0 +1,378 @@
+use std::ops::{Mul, Neg};
+
+use super::matrix::Matrix;
+use super::normal::Normal;
+use super::vector::Vector;
+
+// Order in which Euler rotations are applied. Each rotation is about a world
+// axis, so `Xyz` rotates about X first, then Y, then Z (matrix Rz * Ry * Rx).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum EulerOrder {
+    Xyz,
+    Xzy,
+    Yxz,
+    Yzx,
+    Zxy,
+    Zyx,
+}
+
+// Unit quaternion describing a rotation, stored as w + xi + yj + zk.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct Quaternion {
+    pub(crate) w: f64,
+    pub(crate) x: f64,
+    pub(crate) y: f64,
+    pub(crate) z: f64,
+}
+
+impl Quaternion {
+    pub(crate) fn new(w: f64, x: f64, y: f64, z: f64) -> Quaternion {
+        Quaternion { w, x, y, z }
+    }
+
+    pub(crate) fn identity() -> Quaternion {
+        Quaternion::new(1.0, 0.0, 0.0, 0.0)
+    }
+
+    pub(crate) fn from_axis_angle(axis: Vector, radians: f64) -> Quaternion {
+        let axis = axis.normalize();
+        let (s, c) = (radians / 2.0).sin_cos();
+        Quaternion::new(c, axis.x * s, axis.y * s, axis.z * s)
+    }
+
+    // Returns the rotation axis and the angle in radians. The identity rotation
+    // has no defined axis, so X is returned with a zero angle.
+    #[cfg_attr(not(test), allow(dead_code))]
+    pub(crate) fn to_axis_angle(self) -> (Normal, f64) {
+        let q = if self.w < 0.0 { -self } else { self }.normalize();
+        let s = (1.0 - q.w * q.w).max(0.0).sqrt();
+        if s < 1e-9 {
+            return (Normal::new(1.0, 0.0, 0.0), 0.0);
+        }
+        let angle = 2.0 * q.w.clamp(-1.0, 1.0).acos();
+        (Vector::new(q.x, q.y, q.z).normalize(), angle)
+    }
+
+    pub(crate) fn from_euler(x: f64, y: f64, z: f64, order: EulerOrder) -> Quaternion {
+        let qx = Quaternion::from_axis_angle(Vector::new(1.0, 0.0, 0.0), x);
+        let qy = Quaternion::from_axis_angle(Vector::new(0.0, 1.0, 0.0), y);
+        let qz = Quaternion::from_axis_angle(Vector::new(0.0, 0.0, 1.0), z);
+        // The first rotation applied is the rightmost factor.
+        match order {
+            EulerOrder::Xyz => qz * qy * qx,
+            EulerOrder::Xzy => qy * qz * qx,
+            EulerOrder::Yxz => qz * qx * qy,
+            EulerOrder::Yzx => qx * qz * qy,
+            EulerOrder::Zxy => qy * qx * qz,
+            EulerOrder::Zyx => qx * qy * qz,
+        }
+    }
+
+    // Returns the (x, y, z) angles in radians that reproduce this rotation when
+    // passed to `from_euler` with the same order.
+    #[cfg_attr(not(test), allow(dead_code))]
+    pub(crate) fn to_euler(self, order: EulerOrder) -> (f64, f64, f64) {
+        const GIMBAL_LOCK: f64 = 0.999_999_9;
+        let m = self.to_matrix();
+        let (m11, m12, m13) = (m[0][0], m[0][1], m[0][2]);
+        let (m21, m22, m23) = (m[1][0], m[1][1], m[1][2]);
+        let (m31, m32, m33) = (m[2][0], m[2][1], m[2][2]);
+        match order {
+            EulerOrder::Xyz => {
+                let y = (-m31.clamp(-1.0, 1.0)).asin();
+                if m31.abs() < GIMBAL_LOCK {
+                    (m32.atan2(m33), y, m21.atan2(m11))
+                } else {
+                    (0.0, y, (-m12).atan2(m22))
+                }
+            }
+            EulerOrder::Xzy => {
+                let z = m21.clamp(-1.0, 1.0).asin();
+                if m21.abs() < GIMBAL_LOCK {
+                    ((-m23).atan2(m22), (-m31).atan2(m11), z)
+                } else {
+                    (0.0, m13.atan2(m33), z)
+                }
+            }
+            EulerOrder::Yxz => {
+                let x = m32.clamp(-1.0, 1.0).asin();
+                if m32.abs() < GIMBAL_LOCK {
+                    (x, (-m31).atan2(m33), (-m12).atan2(m22))
+                } else {
+                    (x, 0.0, m21.atan2(m11))
+                }
+            }
+            EulerOrder::Yzx => {
+                let z = (-m12.clamp(-1.0, 1.0)).asin();
+                if m12.abs() < GIMBAL_LOCK {
+                    (m32.atan2(m22), m13.atan2(m11), z)
+                } else {
+                    ((-m23).atan2(m33), 0.0, z)
+                }
+            }
+            EulerOrder::Zxy => {
+                let x = (-m23.clamp(-1.0, 1.0)).asin();
+                if m23.abs() < GIMBAL_LOCK {
+                    (x, m13.atan2(m33), m21.atan2(m22))
+                } else {
+                    (x, (-m31).atan2(m11), 0.0)
+                }
+            }
+            EulerOrder::Zyx => {
+                let y = m13.clamp(-1.0, 1.0).asin();
+                if m13.abs() < GIMBAL_LOCK {
+                    ((-m23).atan2(m33), y, (-m12).atan2(m11))
+                } else {
+                    (m32.atan2(m22), y, 0.0)
+                }
+            }
+        }
+    }
+
+    // Extracts the rotation from the upper 3x3 part of the matrix, which must
+    // be a pure rotation (no scale or shear).
+    #[cfg_attr(not(test), allow(dead_code))]
+    pub(crate) fn from_matrix(matrix: &Matrix<4, 4>) -> Quaternion {
+        let m = matrix;
+        let trace = m[0][0] + m[1][1] + m[2][2];
+        let q = if trace > 0.0 {
+            let s = 0.5 / (trace + 1.0).sqrt();
+            Quaternion::new(
+                0.25 / s,
+                (m[2][1] - m[1][2]) * s,
+                (m[0][2] - m[2][0]) * s,
+                (m[1][0] - m[0][1]) * s,
+            )
+        } else if m[0][0] > m[1][1] && m[0][0] > m[2][2] {
+            let s = 2.0 * (1.0 + m[0][0] - m[1][1] - m[2][2]).sqrt();
+            Quaternion::new(
+                (m[2][1] - m[1][2]) / s,
+                0.25 * s,
+                (m[0][1] + m[1][0]) / s,
+                (m[0][2] + m[2][0]) / s,
+            )
+        } else if m[1][1] > m[2][2] {
+            let s = 2.0 * (1.0 + m[1][1] - m[0][0] - m[2][2]).sqrt();
+            Quaternion::new(
+                (m[0][2] - m[2][0]) / s,
+                (m[0][1] + m[1][0]) / s,
+                0.25 * s,
+                (m[1][2] + m[2][1]) / s,
+            )
+        } else {
+            let s = 2.0 * (1.0 + m[2][2] - m[0][0] - m[1][1]).sqrt();
+            Quaternion::new(
+                (m[1][0] - m[0][1]) / s,
+                (m[0][2] + m[2][0]) / s,
+                (m[1][2] + m[2][1]) / s,
+                0.25 * s,
+            )
+        };
+        q.normalize()
+    }
+
+    pub(crate) fn to_matrix(self) -> Matrix<4, 4> {
+        let Quaternion { w, x, y, z } = self.normalize();
+        Matrix::with_data([
+            [
+                1.0 - 2.0 * (y * y + z * z),
+                2.0 * (x * y - w * z),
+                2.0 * (x * z + w * y),
+                0.0,
+            ],
+            [
+                2.0 * (x * y + w * z),
+                1.0 - 2.0 * (x * x + z * z),
+                2.0 * (y * z - w * x),
+                0.0,
+            ],
+            [
+                2.0 * (x * z - w * y),
+                2.0 * (y * z + w * x),
+                1.0 - 2.0 * (x * x + y * y),
+                0.0,
+            ],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    pub(crate) fn dot(&self, other: Quaternion) -> f64 {
+        self.w * other.w + self.x * other.x + self.y * other.y + self.z * other.z
+    }
+
+    pub(crate) fn length(&self) -> f64 {
+        self.dot(*self).sqrt()
+    }
+
+    pub(crate) fn normalize(&self) -> Quaternion {
+        let length = self.length();
+        if length == 0.0 {
+            return Quaternion::identity();
+        }
+        Quaternion::new(
+            self.w / length,
+            self.x / length,
+            self.y / length,
+            self.z / length,
+        )
+    }
+
+    #[cfg_attr(not(test), allow(dead_code))]
+    pub(crate) fn conjugate(&self) -> Quaternion {
+        Quaternion::new(self.w, -self.x, -self.y, -self.z)
+    }
+
+    #[cfg_attr(not(test), allow(dead_code))]
+    pub(crate) fn rotate(&self, vector: Vector) -> Vector {
+        let q = Quaternion::new(0.0, vector.x, vector.y, vector.z);
+        let r = *self * q * self.conjugate();
+        Vector::new(r.x, r.y, r.z)
+    }
+
+    // Spherical linear interpolation along the shortest arc.
+    pub(crate) fn slerp(&self, other: Quaternion, t: f64) -> Quaternion {
+        let mut other = other;
+        let mut cos_theta = self.dot(other);
+        if cos_theta < 0.0 {
+            other = -other;
+            cos_theta = -cos_theta;
+        }
+
+        // Nearly parallel quaternions make sin(theta) vanish, fall back to lerp.
+        if cos_theta > 0.9995 {
+            return Quaternion::new(
+                self.w + (other.w - self.w) * t,
+                self.x + (other.x - self.x) * t,
+                self.y + (other.y - self.y) * t,
+                self.z + (other.z - self.z) * t,
+            )
+            .normalize();
+        }
+
+        let theta = cos_theta.clamp(-1.0, 1.0).acos();
+        let sin_theta = theta.sin();
+        let a = ((1.0 - t) * theta).sin() / sin_theta;
+        let b = (t * theta).sin() / sin_theta;
+        Quaternion::new(
+            self.w * a + other.w * b,
+            self.x * a + other.x * b,
+            self.y * a + other.y * b,
+            self.z * a + other.z * b,
+        )
+    }
+}
+
+impl Mul for Quaternion {
+    type Output = Quaternion;
+
+    fn mul(self, other: Quaternion) -> Quaternion {
+        Quaternion {
+            w: self.w * other.w - self.x * other.x - self.y * other.y - self.z * other.z,
+            x: self.w * other.x + self.x * other.w + self.y * other.z - self.z * other.y,
+            y: self.w * other.y - self.x * other.z + self.y * other.w + self.z * other.x,
+            z: self.w * other.z + self.x * other.y - self.y * other.x + self.z * other.w,
+        }
+    }
+}
+
+impl Neg for Quaternion {
+    type Output = Quaternion;
+
+    fn neg(self) -> Quaternion {
+        Quaternion::new(-self.w, -self.x, -self.y, -self.z)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_same_rotation(a: Quaternion, b: Quaternion) {
+        // q and -q describe the same rotation.
+        assert!(a.dot(b).abs() > 1.0 - 1e-9, "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn euler_angles_round_trip_in_every_order() {
+        let orders = [
+            EulerOrder::Xyz,
+            EulerOrder::Xzy,
+            EulerOrder::Yxz,
+            EulerOrder::Yzx,
+            EulerOrder::Zxy,
+            EulerOrder::Zyx,
+        ];
+        for order in orders {
+            let (x, y, z) = (0.3, -0.7, 1.1);
+            let (rx, ry, rz) = Quaternion::from_euler(x, y, z, order).to_euler(order);
+            assert!((rx - x).abs() < 1e-9, "{:?}", order);
+            assert!((ry - y).abs() < 1e-9, "{:?}", order);
+            assert!((rz - z).abs() < 1e-9, "{:?}", order);
+        }
+    }
+
+    #[test]
+    fn euler_angles_in_gimbal_lock_keep_the_rotation() {
+        let q = Quaternion::from_euler(0.4, std::f64::consts::FRAC_PI_2, 0.2, EulerOrder::Xyz);
+        let (x, y, z) = q.to_euler(EulerOrder::Xyz);
+        assert_same_rotation(Quaternion::from_euler(x, y, z, EulerOrder::Xyz), q);
+    }
+
+    #[test]
+    fn euler_order_matches_matrix_order() {
+        let q = Quaternion::from_euler(0.5, 0.25, -0.75, EulerOrder::Xyz);
+        let m = Matrix::<4, 4>::rotation_z(-0.75)
+            * Matrix::<4, 4>::rotation_y(0.25)
+            * Matrix::<4, 4>::rotation_x(0.5);
+        let q = q.to_matrix();
+        for i in 0..4 {
+            for j in 0..4 {
+                assert!((q[i][j] - m[i][j]).abs() < 1e-12);
+            }
+        }
+    }
+
+    #[test]
+    fn matrix_round_trip() {
+        // Rotations by close to 180 degrees take the branches without the
+        // trace.
+        let rotations = [
+            Quaternion::from_axis_angle(Vector::new(1.0, 2.0, 3.0), 0.8),
+            Quaternion::from_axis_angle(Vector::new(1.0, 0.1, 0.0), 3.1),
+            Quaternion::from_axis_angle(Vector::new(0.1, 1.0, 0.0), 3.1),
+            Quaternion::from_axis_angle(Vector::new(0.0, 0.1, 1.0), 3.1),
+        ];
+        for q in rotations {
+            assert_same_rotation(Quaternion::from_matrix(&q.to_matrix()), q);
+        }
+    }
+
+    #[test]
+    fn axis_angle_round_trip() {
+        let q = Quaternion::from_axis_angle(Vector::new(0.0, 3.0, 4.0), 1.2);
+        let (axis, angle) = q.to_axis_angle();
+        assert!((angle - 1.2).abs() < 1e-9);
+        assert!((axis.y - 0.6).abs() < 1e-9 && (axis.z - 0.8).abs() < 1e-9);
+        assert_eq!(Quaternion::identity().to_axis_angle().1, 0.0);
+    }
+
+    #[test]
+    fn rotate_matches_the_matrix() {
+        let q = Quaternion::from_euler(0.1, 0.2, 0.3, EulerOrder::Zxy);
+        let v = Vector::new(1.0, -2.0, 0.5);
+        let (a, b) = (q.rotate(v), q.to_matrix() * v);
+        assert!((a - b).length() < 1e-12);
+    }
+
+    #[test]
+    fn slerp_halves_the_angle() {
+        let y = Vector::new(0.0, 1.0, 0.0);
+        let start = Quaternion::identity();
+        let end = Quaternion::from_axis_angle(y, 1.0);
+        assert_same_rotation(start.slerp(end, 0.5), Quaternion::from_axis_angle(y, 0.5));
+        assert_same_rotation(start.slerp(end, 0.0), start);
+        assert_same_rotation(start.slerp(end, 1.0), end);
+        // The negated end is the same rotation, slerp still takes the short arc.
+        assert_same_rotation(start.slerp(-end, 0.5), Quaternion::from_axis_angle(y, 0.5));
+    }
+}
@@ -1,18 +1,30 @@
 use crate::geometry::normal::Normal;
 use crate::geometry::point::Point;
 
+// `time` is the moment inside the exposure the ray was sent at, moving
+// objects are intersected where they are at that moment.
 #[derive(Debug)]
 pub(crate) struct Ray {
     pub(crate) origin: Point,
     pub(crate) direction: Normal,
+    pub(crate) time: f64,
 }
 
 impl Ray {
     pub(crate) fn new(origin: Point, direction: Normal) -> Ray {
-        Ray { origin, direction }
+        Ray {
+            origin,
+            direction,
+            time: 0.0,
+        }
+    }
+
+    pub(crate) fn with_time(mut self, time: f64) -> Ray {
+        self.time = time;
+        self
     }
 
     pub(crate) fn at(&self, t: f64) -> Point {
         self.origin + self.direction * t
     }
-}
\ No newline at end of file
+}
@@ -1,66 +1,137 @@
-use crate::geometry::normal::Normal;
+use std::f64::consts::PI;
+
 use crate::geometry::point::Point;
 use crate::geometry::ray::Ray;
 use crate::geometry::vector::Vector;
 
-use super::{Intersect, Intersection, NormalAtPoint, Transform, Transformation};
+use super::aligned_box::AlignedBox;
+use super::hit::{Hit, Interval};
+use super::matrix::Matrix;
+use super::{Intersect, Transform, Transformation};
 
 #[derive(Debug, Clone, Copy)]
+#[cfg_attr(not(test), allow(dead_code))]
 pub(crate) struct Sphere {
     center: Point,
     radius: f64,
+    world_to_object: Matrix<4, 4>,
 }
 
+#[cfg_attr(not(test), allow(dead_code))]
 impl Sphere {
     pub(crate) fn new(center: Point, radius: f64) -> Sphere {
-        Sphere { center, radius }
+        Sphere {
+            center,
+            radius,
+            world_to_object: Matrix::identity(),
+        }
     }
 }
 
 impl Intersect for Sphere {
-    fn intersect(&self, ray: &Ray) -> Intersection {
+    fn intersect(&self, ray: &Ray, interval: Interval) -> Option<Hit> {
         let k = Vector::from(ray.origin) - Vector::from(self.center);
         let a = ray.direction.dot(ray.direction);
         let b = 2. * k.dot(Vector::from(ray.direction));
         let c = k.dot(k) - self.radius * self.radius;
         let discriminant = b * b - 4. * a * c;
         if discriminant < 0. {
-            return Intersection::DoesNotIntersect;
+            return None;
         }
         let square_descriminant = discriminant.sqrt();
         let t1 = (-b - square_descriminant) / (2. * a);
         let t2 = (-b + square_descriminant) / (2. * a);
 
-        if t1 > 0. && t2 > 0. {
-            Intersection::Intersect(t1.min(t2))
-        } else if t1 > 0. {
-            Intersection::Intersect(t1)
-        } else if t2 > 0. {
-            Intersection::Intersect(t2)
+        let t = if interval.contains(t1) {
+            t1
+        } else if interval.contains(t2) {
+            t2
         } else {
-            Intersection::DoesNotIntersect
-        }
+            return None;
+        };
+        let normal = ((ray.at(t) - self.center) / self.radius).normalize();
+        // Longitude around the Y axis and latitude, with v growing upwards.
+        let u = 0.5 + (-normal.z).atan2(normal.x) / (2.0 * PI);
+        let v = 0.5 + normal.y.clamp(-1.0, 1.0).asin() / PI;
+        let tangent = Vector::new(normal.z, 0.0, -normal.x);
+        let hit = Hit::new(ray, t, normal);
+        Some(
+            hit.with_uv((u, v))
+                .with_tangent(tangent)
+                .with_world_to_object(self.world_to_object),
+        )
     }
-}
 
-impl NormalAtPoint for Sphere {
-    fn normal_at_point(&self, point: &Point, _: Intersection) -> Normal {
-        (Vector::from(*point) - Vector::from(self.center)).normalize()
+    fn bounds(&self) -> AlignedBox {
+        let extent = Vector::new(self.radius, self.radius, self.radius);
+        AlignedBox::new(self.center + -extent, self.center + extent)
     }
 }
 
 impl Transform for Sphere {
     fn transform(&mut self, transformation: Transformation) {
-        match transformation {
-            Transformation::Translation(_) => {
-                self.center = transformation.transformation_to_matrix() * self.center;
-            }
-            Transformation::Scale(scale) => {
-                self.center = transformation.transformation_to_matrix() * self.center;
-                // TODO: if scale is not uniform, this will not work, because it suppose to become a ellipsoid
-                self.radius *= scale.x.max(scale.y).max(scale.z);
-            }
-            Transformation::Rotation(_, _) => {}
-        }
+        let matrix = transformation.transformation_to_matrix();
+        self.center = matrix * self.center;
+        self.world_to_object = self.world_to_object * transformation.inverse_matrix();
+        // TODO: if scale is not uniform, this will not work, because it suppose to become a ellipsoid
+        let (_, _, scale) = matrix.decompose();
+        self.radius *= scale.x.abs().max(scale.y.abs()).max(scale.z.abs());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ray(origin: Point, direction: Vector) -> Ray {
+        Ray::new(origin, direction.normalize())
+    }
+
+    #[test]
+    fn uv_follows_longitude_and_latitude() {
+        let sphere = Sphere::new(Point::new(0.0, 0.0, 0.0), 1.0);
+        let hit = sphere
+            .intersect(
+                &ray(Point::new(0.0, 0.0, -5.0), Vector::new(0.0, 0.0, 1.0)),
+                Interval::positive(),
+            )
+            .unwrap();
+        assert!((hit.distance - 4.0).abs() < 1e-12);
+        assert!((hit.uv.0 - 0.75).abs() < 1e-12 && (hit.uv.1 - 0.5).abs() < 1e-12);
+
+        let top = sphere
+            .intersect(
+                &ray(Point::new(0.0, 5.0, 0.0), Vector::new(0.0, -1.0, 0.0)),
+                Interval::positive(),
+            )
+            .unwrap();
+        assert!((top.uv.1 - 1.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn inside_hits_the_far_side() {
+        let sphere = Sphere::new(Point::new(0.0, 0.0, 0.0), 2.0);
+        let hit = sphere
+            .intersect(
+                &ray(Point::new(0.0, 0.0, 0.0), Vector::new(1.0, 0.0, 0.0)),
+                Interval::positive(),
+            )
+            .unwrap();
+        assert!((hit.distance - 2.0).abs() < 1e-12);
+        assert!(!hit.front_face);
+    }
+
+    #[test]
+    fn scale_grows_the_radius_and_keeps_object_points() {
+        let mut sphere = Sphere::new(Point::new(0.0, 0.0, 0.0), 1.0);
+        sphere.transform(Transformation::Scale(Vector::new(3.0, 3.0, 3.0)));
+        let hit = sphere
+            .intersect(
+                &ray(Point::new(0.0, 0.0, -5.0), Vector::new(0.0, 0.0, 1.0)),
+                Interval::positive(),
+            )
+            .unwrap();
+        assert!((hit.distance - 2.0).abs() < 1e-9);
+        assert!((hit.object_point.z + 1.0).abs() < 1e-9);
     }
 }
@@ -1,8 +1,14 @@
 use super::{
-    normal::Normal, ray::Ray, vector::Vector, Intersect, Intersection, NormalAtPoint, Transform,
-    Transformation,
+    aligned_box::AlignedBox,
+    hit::{Hit, Interval},
+    matrix::Matrix,
+    normal::Normal,
+    ray::Ray,
+    vector::Vector,
+    Intersect, Transform, Transformation,
 };
 use crate::geometry::point::Point;
+#[cfg_attr(not(test), allow(dead_code))]
 pub(crate) struct Triangle {
     a: Vector,
     b: Vector,
@@ -11,8 +17,11 @@ pub(crate) struct Triangle {
     nb: Normal,
     nc: Normal,
     normal_at_point: bool,
+    uvs: Option<[(f64, f64); 3]>,
+    world_to_object: Matrix<4, 4>,
 }
 
+#[cfg_attr(not(test), allow(dead_code))]
 impl Triangle {
     pub(crate) fn new(a: Point, b: Point, c: Point) -> Triangle {
         let a = Vector::from(a);
@@ -30,6 +39,8 @@ impl Triangle {
             nb: n,
             nc: n,
             normal_at_point: false,
+            uvs: None,
+            world_to_object: Matrix::identity(),
         }
     }
     pub(crate) fn with_normals(
@@ -48,65 +59,178 @@ impl Triangle {
             nb,
             nc,
             normal_at_point: true,
+            uvs: None,
+            world_to_object: Matrix::identity(),
         }
     }
+
+    // Texture coordinates of the three vertices, interpolated at the hit.
+    pub(crate) fn with_uvs(mut self, uva: (f64, f64), uvb: (f64, f64), uvc: (f64, f64)) -> Self {
+        self.uvs = Some([uva, uvb, uvc]);
+        self
+    }
 }
 
 impl Intersect for Triangle {
-    fn intersect(&self, ray: &Ray) -> Intersection {
-        let ab = self.b - self.a;
-        let ac = self.c - self.a;
-        let normal = Vector::from(ray.direction).cross(ac);
-
-        let d = ab.dot(normal);
-        if d.abs() < f64::EPSILON {
-            return Intersection::DoesNotIntersect;
+    fn intersect(&self, ray: &Ray, interval: Interval) -> Option<Hit> {
+        let (t, u, v) = intersect_triangle(ray, interval, self.a, self.b, self.c)?;
+        let geometric_normal = (self.b - self.a).cross(self.c - self.a).normalize();
+        let w = 1.0 - u - v;
+        // Without texture coordinates the barycentric weights are used.
+        let uvs = self.uvs.unwrap_or([(0.0, 0.0), (1.0, 0.0), (0.0, 1.0)]);
+        let uv = (
+            uvs[0].0 * w + uvs[1].0 * u + uvs[2].0 * v,
+            uvs[0].1 * w + uvs[1].1 * u + uvs[2].1 * v,
+        );
+        let (tangent, bitangent) = uv_derivatives([self.a, self.b, self.c], uvs)
+            .unwrap_or((self.b - self.a, self.c - self.a));
+        let hit = Hit::new(ray, t, geometric_normal);
+        let hit = hit
+            .with_uv(uv)
+            .with_tangents(tangent, bitangent)
+            .with_world_to_object(self.world_to_object);
+        if self.normal_at_point {
+            let normal = self.na * (1.0 - u - v) + self.nb * u + self.nc * v;
+            Some(hit.with_shading_normal(normal.normalize()))
+        } else {
+            Some(hit)
         }
+    }
 
-        let inv_d = 1.0 / d;
-        let ao = Vector::from(ray.origin) - self.a;
-        let u_coordinate = ao.dot(normal) * inv_d;
-        if !(0.0..=1.0).contains(&u_coordinate) {
-            return Intersection::DoesNotIntersect;
+    fn bounds(&self) -> AlignedBox {
+        let mut bounds = AlignedBox::empty();
+        for vertex in [self.a, self.b, self.c] {
+            bounds.grow(vertex.into());
         }
+        bounds
+    }
+}
 
-        let ao_cross_ab = ao.cross(ab);
-        let v_coordinate = Vector::from(ray.direction).dot(ao_cross_ab) * inv_d;
-        if v_coordinate < 0.0 || u_coordinate + v_coordinate > 1.0 {
-            return Intersection::DoesNotIntersect;
-        }
-        let t = ac.dot(ao_cross_ab) * inv_d;
-        if t > f64::EPSILON {
-            Intersection::TriangleIntesersect(t, u_coordinate, v_coordinate)
-        } else {
-            Intersection::DoesNotIntersect
-        }
+// Directions in which the texture coordinates u and v grow across the
+// triangle, or `None` when the texture coordinates are degenerate.
+pub(crate) fn uv_derivatives(
+    vertices: [Vector; 3],
+    uvs: [(f64, f64); 3],
+) -> Option<(Vector, Vector)> {
+    let edge1 = vertices[1] - vertices[0];
+    let edge2 = vertices[2] - vertices[0];
+    let (du1, dv1) = (uvs[1].0 - uvs[0].0, uvs[1].1 - uvs[0].1);
+    let (du2, dv2) = (uvs[2].0 - uvs[0].0, uvs[2].1 - uvs[0].1);
+    let determinant = du1 * dv2 - du2 * dv1;
+    if determinant.abs() < 1e-12 {
+        return None;
     }
+    Some((
+        (edge1 * dv2 - edge2 * dv1) / determinant,
+        (edge2 * du1 - edge1 * du2) / determinant,
+    ))
 }
 
-impl NormalAtPoint for Triangle {
-    fn normal_at_point(&self, _: &Point, intersection: Intersection) -> Normal {
-        match intersection {
-            Intersection::TriangleIntesersect(_, u, v) => {
-                if self.normal_at_point {
-                    (self.na * u + self.nb * v + self.nc * (1.0 - u - v)).normalize()
-                } else {
-                    self.na
-                }
-            }
-            _ => panic!("Called with wrong intersaction type"),
-        }
+// Moller-Trumbore ray/triangle test shared by `Triangle` and `TriangleMesh`.
+// Returns the distance and the barycentric weights of `b` and `c`.
+pub(crate) fn intersect_triangle(
+    ray: &Ray,
+    interval: Interval,
+    a: Vector,
+    b: Vector,
+    c: Vector,
+) -> Option<(f64, f64, f64)> {
+    let ab = b - a;
+    let ac = c - a;
+    let normal = Vector::from(ray.direction).cross(ac);
+
+    let d = ab.dot(normal);
+    if d.abs() < f64::EPSILON {
+        return None;
+    }
+
+    let inv_d = 1.0 / d;
+    let ao = Vector::from(ray.origin) - a;
+    let u_coordinate = ao.dot(normal) * inv_d;
+    if !(0.0..=1.0).contains(&u_coordinate) {
+        return None;
+    }
+
+    let ao_cross_ab = ao.cross(ab);
+    let v_coordinate = Vector::from(ray.direction).dot(ao_cross_ab) * inv_d;
+    if v_coordinate < 0.0 || u_coordinate + v_coordinate > 1.0 {
+        return None;
+    }
+    let t = ac.dot(ao_cross_ab) * inv_d;
+    if interval.contains(t) {
+        Some((t, u_coordinate, v_coordinate))
+    } else {
+        None
     }
 }
 
 impl Transform for Triangle {
     fn transform(&mut self, transform: Transformation) {
         let matrix = transform.transformation_to_matrix();
-        self.a = matrix * self.a;
-        self.b = matrix * self.b;
-        self.c = matrix * self.c;
+        self.world_to_object = self.world_to_object * transform.inverse_matrix();
+        self.a = (matrix * Point::from(self.a)).into();
+        self.b = (matrix * Point::from(self.b)).into();
+        self.c = (matrix * Point::from(self.c)).into();
         self.na = matrix * self.na;
         self.nb = matrix * self.nb;
         self.nc = matrix * self.nc;
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn triangle() -> Triangle {
+        Triangle::new(
+            Point::new(0.0, 0.0, 0.0),
+            Point::new(1.0, 0.0, 0.0),
+            Point::new(0.0, 1.0, 0.0),
+        )
+    }
+
+    fn ray_at(x: f64, y: f64) -> Ray {
+        Ray::new(Point::new(x, y, 1.0), Normal::new(0.0, 0.0, -1.0))
+    }
+
+    #[test]
+    fn uv_defaults_to_the_barycentric_weights() {
+        let hit = triangle()
+            .intersect(&ray_at(0.25, 0.5), Interval::positive())
+            .unwrap();
+        assert!((hit.uv.0 - 0.25).abs() < 1e-12 && (hit.uv.1 - 0.5).abs() < 1e-12);
+    }
+
+    #[test]
+    fn vertex_uvs_are_interpolated() {
+        let triangle = triangle().with_uvs((0.0, 1.0), (1.0, 1.0), (0.0, 0.0));
+        let hit = triangle
+            .intersect(&ray_at(0.25, 0.5), Interval::positive())
+            .unwrap();
+        assert!((hit.uv.0 - 0.25).abs() < 1e-12 && (hit.uv.1 - 0.5).abs() < 1e-12);
+        // u grows along x and v shrinks along y.
+        assert!(hit.tangent.x > 0.0 && hit.bitangent.y < 0.0);
+    }
+
+    #[test]
+    fn vertex_normals_are_interpolated() {
+        let n = Normal::new(0.0, 0.0, 1.0);
+        let tilted = Vector::new(1.0, 0.0, 1.0).normalize();
+        let triangle = Triangle::with_normals(
+            Point::new(0.0, 0.0, 0.0),
+            n,
+            Point::new(1.0, 0.0, 0.0),
+            tilted,
+            Point::new(0.0, 1.0, 0.0),
+            n,
+        );
+        let hit = triangle
+            .intersect(&ray_at(0.5, 0.0), Interval::positive())
+            .unwrap();
+        assert!(hit.shading_normal.x > 0.0);
+        assert!(hit.geometric_normal.x == 0.0);
+        assert!(triangle
+            .intersect(&ray_at(0.75, 0.5), Interval::positive())
+            .is_none());
+    }
+}
@@ -0,0 +1,368 @@
+use super::aligned_box::AlignedBox;
+use super::bvh::Bvh;
+use super::hit::{orthonormal_basis, Hit, Interval};
+use super::matrix::Matrix;
+use super::mesh_check::Repairs;
+use super::normal::Normal;
+use super::point::Point;
+use super::ray::Ray;
+use super::triangle::{intersect_triangle, uv_derivatives};
+use super::vector::Vector;
+use super::{Intersect, Transform, Transformation};
+
+// Triangle mesh with shared vertex buffers. Every vertex attribute buffer is
+// either empty or has exactly one entry per position, faces index into them.
+// Tangents are derived from the normals and texture coordinates when both are
+// present and store the direction along u with the handedness of the
+// bitangent, which is `sign * normal x tangent`. Vertex colors are RGB in
+// [0, 1].
+#[derive(Clone)]
+pub(crate) struct TriangleMesh {
+    positions: Vec<Point>,
+    normals: Vec<Normal>,
+    uvs: Vec<(f64, f64)>,
+    tangents: Vec<(Vector, f64)>,
+    colors: Vec<Vector>,
+    indices: Vec<[u32; 3]>,
+    repairs: Repairs,
+    bvh: Bvh,
+    world_to_object: Matrix<4, 4>,
+}
+
+impl TriangleMesh {
+    pub(crate) fn new(
+        positions: Vec<Point>,
+        normals: Vec<Normal>,
+        uvs: Vec<(f64, f64)>,
+        indices: Vec<[u32; 3]>,
+    ) -> TriangleMesh {
+        assert!(
+            normals.is_empty() || normals.len() == positions.len(),
+            "Expected {} normals, but have {}",
+            positions.len(),
+            normals.len()
+        );
+        assert!(
+            uvs.is_empty() || uvs.len() == positions.len(),
+            "Expected {} uvs, but have {}",
+            positions.len(),
+            uvs.len()
+        );
+        let tangents = vertex_tangents(&positions, &normals, &uvs, &indices);
+        let mut mesh = TriangleMesh {
+            positions,
+            normals,
+            uvs,
+            tangents,
+            colors: vec![],
+            indices,
+            repairs: Repairs::default(),
+            bvh: Bvh::default(),
+            world_to_object: Matrix::identity(),
+        };
+        mesh.build_bvh();
+        mesh
+    }
+
+    pub(crate) fn with_colors(mut self, colors: Vec<Vector>) -> TriangleMesh {
+        assert!(
+            colors.is_empty() || colors.len() == self.positions.len(),
+            "Expected {} colors, but have {}",
+            self.positions.len(),
+            colors.len()
+        );
+        self.colors = colors;
+        self
+    }
+
+    // Replaces the derived tangents with ones from the model file, in the
+    // same direction and sign layout.
+    pub(crate) fn with_tangents(mut self, tangents: Vec<(Vector, f64)>) -> TriangleMesh {
+        assert!(
+            tangents.is_empty()
+                || (tangents.len() == self.positions.len() && !self.normals.is_empty()),
+            "Expected {} tangents and normals, but have {} tangents",
+            self.positions.len(),
+            tangents.len()
+        );
+        self.tangents = tangents;
+        self
+    }
+
+    pub(crate) fn with_repairs(mut self, repairs: Repairs) -> TriangleMesh {
+        self.repairs = repairs;
+        self
+    }
+
+    pub(crate) fn positions(&self) -> &[Point] {
+        &self.positions
+    }
+
+    pub(crate) fn normals(&self) -> &[Normal] {
+        &self.normals
+    }
+
+    pub(crate) fn uvs(&self) -> &[(f64, f64)] {
+        &self.uvs
+    }
+
+    #[cfg_attr(not(test), allow(dead_code))]
+    pub(crate) fn tangents(&self) -> &[(Vector, f64)] {
+        &self.tangents
+    }
+
+    pub(crate) fn colors(&self) -> &[Vector] {
+        &self.colors
+    }
+
+    pub(crate) fn indices(&self) -> &[[u32; 3]] {
+        &self.indices
+    }
+
+    pub(crate) fn repairs(&self) -> Repairs {
+        self.repairs
+    }
+
+    fn vertices(&self, triangle: usize) -> (Vector, Vector, Vector) {
+        let [a, b, c] = self.indices[triangle];
+        (
+            self.positions[a as usize].into(),
+            self.positions[b as usize].into(),
+            self.positions[c as usize].into(),
+        )
+    }
+
+    fn build_bvh(&mut self) {
+        let bounds: Vec<AlignedBox> = self
+            .indices
+            .iter()
+            .map(|triangle| {
+                let mut bounds = AlignedBox::empty();
+                for &index in triangle {
+                    bounds.grow(self.positions[index as usize]);
+                }
+                bounds
+            })
+            .collect();
+        self.bvh = Bvh::build(&bounds);
+    }
+}
+
+impl Intersect for TriangleMesh {
+    fn intersect(&self, ray: &Ray, interval: Interval) -> Option<Hit> {
+        // Every accepted hit is closer than the previous one, so the last
+        // recorded weights belong to the closest triangle.
+        let mut barycentric = (0.0, 0.0);
+        let (triangle, t) = self.bvh.intersect(ray, interval, |triangle, interval| {
+            let (a, b, c) = self.vertices(triangle);
+            let (t, u, v) = intersect_triangle(ray, interval, a, b, c)?;
+            barycentric = (u, v);
+            Some(t)
+        })?;
+
+        let (u, v) = barycentric;
+        let w = 1.0 - u - v;
+        let [ia, ib, ic] = self.indices[triangle].map(|i| i as usize);
+        let (a, b, c) = self.vertices(triangle);
+        let geometric_normal = (b - a).cross(c - a).normalize();
+        let mut hit = Hit::new(ray, t, geometric_normal).with_primitive_id(triangle);
+        hit = hit.with_world_to_object(self.world_to_object);
+
+        // Without texture coordinates the barycentric weights are used.
+        let uvs = if self.uvs.is_empty() {
+            [(0.0, 0.0), (1.0, 0.0), (0.0, 1.0)]
+        } else {
+            [self.uvs[ia], self.uvs[ib], self.uvs[ic]]
+        };
+        let (tangent, bitangent) = uv_derivatives([a, b, c], uvs).unwrap_or((b - a, c - a));
+        hit = hit
+            .with_uv((
+                uvs[0].0 * w + uvs[1].0 * u + uvs[2].0 * v,
+                uvs[0].1 * w + uvs[1].1 * u + uvs[2].1 * v,
+            ))
+            .with_tangents(tangent, bitangent);
+        if !self.colors.is_empty() {
+            hit = hit.with_color(self.colors[ia] * w + self.colors[ib] * u + self.colors[ic] * v);
+        }
+        if !self.normals.is_empty() {
+            let normal = self.normals[ia] * w + self.normals[ib] * u + self.normals[ic] * v;
+            hit = hit.with_shading_normal(normal.normalize());
+            if !self.tangents.is_empty() {
+                let tangent =
+                    self.tangents[ia].0 * w + self.tangents[ib].0 * u + self.tangents[ic].0 * v;
+                let bitangent = normal.cross(tangent) * self.tangents[ia].1;
+                hit = hit.with_tangents(tangent, bitangent);
+            }
+        }
+        Some(hit)
+    }
+
+    fn bounds(&self) -> AlignedBox {
+        self.bvh.bounds()
+    }
+
+    fn mesh_at(&self, _time: f64) -> Option<TriangleMesh> {
+        Some(self.clone())
+    }
+}
+
+impl Transform for TriangleMesh {
+    fn transform(&mut self, transform: Transformation) {
+        let matrix = transform.transformation_to_matrix();
+        // Normals need the inverse transpose to stay perpendicular under
+        // non-uniform scale.
+        let inverse = transform.inverse_matrix();
+        let normal_matrix = inverse.transpose();
+        self.world_to_object = self.world_to_object * inverse;
+        for position in self.positions.iter_mut() {
+            *position = matrix * *position;
+        }
+        for normal in self.normals.iter_mut() {
+            *normal = normal_matrix * *normal;
+        }
+        // Mirroring transformations flip the handedness of the tangent frame
+        // and turn the winding of every triangle inside out. Swapping two
+        // corners keeps the faces pointing the way the normals do.
+        let mirrored = determinant3(&matrix) < 0.0;
+        for (tangent, sign) in self.tangents.iter_mut() {
+            *tangent = matrix * *tangent;
+            if mirrored {
+                *sign = -*sign;
+            }
+        }
+        if mirrored {
+            for triangle in self.indices.iter_mut() {
+                triangle.swap(1, 2);
+            }
+        }
+        self.build_bvh();
+    }
+}
+
+// Per vertex tangents in the spirit of MikkTSpace: the u and v derivatives of
+// every face are projected into the tangent plane of each of its vertices and
+// accumulated weighted by the corner angle, then orthonormalized against the
+// vertex normal. Returns nothing when normals or texture coordinates are
+// missing.
+fn vertex_tangents(
+    positions: &[Point],
+    normals: &[Normal],
+    uvs: &[(f64, f64)],
+    indices: &[[u32; 3]],
+) -> Vec<(Vector, f64)> {
+    if normals.is_empty() || uvs.is_empty() {
+        return vec![];
+    }
+    let zero = Vector::new(0.0, 0.0, 0.0);
+    let mut tangents = vec![zero; positions.len()];
+    let mut bitangents = vec![zero; positions.len()];
+    for triangle in indices {
+        let [a, b, c] = triangle.map(|i| i as usize);
+        let vertices = [a, b, c].map(|i| Vector::from(positions[i]));
+        let Some((dpdu, dpdv)) = uv_derivatives(vertices, [uvs[a], uvs[b], uvs[c]]) else {
+            continue;
+        };
+        for corner in 0..3 {
+            let index = triangle[corner] as usize;
+            let normal = Vector::from(normals[index]);
+            let to_next = vertices[(corner + 1) % 3] - vertices[corner];
+            let to_previous = vertices[(corner + 2) % 3] - vertices[corner];
+            let angle = angle_between(to_next, to_previous);
+            tangents[index] = tangents[index] + unit(dpdu - normal * normal.dot(dpdu)) * angle;
+            bitangents[index] = bitangents[index] + unit(dpdv - normal * normal.dot(dpdv)) * angle;
+        }
+    }
+
+    normals
+        .iter()
+        .zip(tangents.iter().zip(bitangents.iter()))
+        .map(|(&normal, (&tangent, &bitangent))| {
+            let n = Vector::from(normal);
+            let tangent = tangent - n * n.dot(tangent);
+            let tangent = if tangent.length() < 1e-12 {
+                orthonormal_basis(normal).0
+            } else {
+                unit(tangent)
+            };
+            let sign = if n.cross(tangent).dot(bitangent) < 0.0 {
+                -1.0
+            } else {
+                1.0
+            };
+            (tangent, sign)
+        })
+        .collect()
+}
+
+fn unit(vector: Vector) -> Vector {
+    let length = vector.length();
+    if length < 1e-12 {
+        vector
+    } else {
+        vector / length
+    }
+}
+
+fn angle_between(a: Vector, b: Vector) -> f64 {
+    let lengths = a.length() * b.length();
+    if lengths < 1e-24 {
+        return 0.0;
+    }
+    (a.dot(b) / lengths).clamp(-1.0, 1.0).acos()
+}
+
+// Determinant of the upper left 3x3 block, the linear part of an affine
+// transformation.
+fn determinant3(matrix: &Matrix<4, 4>) -> f64 {
+    matrix[0][0] * (matrix[1][1] * matrix[2][2] - matrix[1][2] * matrix[2][1])
+        - matrix[0][1] * (matrix[1][0] * matrix[2][2] - matrix[1][2] * matrix[2][0])
+        + matrix[0][2] * (matrix[1][0] * matrix[2][1] - matrix[1][1] * matrix[2][0])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Unit square in the XY plane facing +Z, with u along X and v along Y.
+    fn square() -> TriangleMesh {
+        let positions = vec![
+            Point::new(0.0, 0.0, 0.0),
+            Point::new(1.0, 0.0, 0.0),
+            Point::new(1.0, 1.0, 0.0),
+            Point::new(0.0, 1.0, 0.0),
+        ];
+        let normals = vec![Normal::new(0.0, 0.0, 1.0); 4];
+        let uvs = vec![(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0)];
+        TriangleMesh::new(positions, normals, uvs, vec![[0, 1, 2], [0, 2, 3]])
+    }
+
+    #[test]
+    fn tangents_follow_u_with_right_handed_sign() {
+        let mesh = square();
+        assert_eq!(mesh.tangents().len(), 4);
+        for &(tangent, sign) in mesh.tangents() {
+            assert!((tangent - Vector::new(1.0, 0.0, 0.0)).length() < 1e-9);
+            assert_eq!(sign, 1.0);
+        }
+    }
+
+    #[test]
+    fn mirroring_flips_winding_and_tangent_sign() {
+        let mut mesh = square();
+        mesh.transform(Transformation::Scale(Vector::new(-1.0, 1.0, 1.0)));
+        assert_eq!(mesh.indices()[0], [0, 2, 1]);
+        assert!(mesh.tangents().iter().all(|&(_, sign)| sign == -1.0));
+        // The flipped winding faces the same way as the stored normals.
+        let [a, b, c] = mesh.indices()[0].map(|i| Vector::from(mesh.positions()[i as usize]));
+        let face = (b - a).cross(c - a);
+        assert!(face.dot(Vector::from(mesh.normals()[0])) > 0.0);
+    }
+
+    #[test]
+    fn hits_interpolate_uvs() {
+        let ray = Ray::new(Point::new(0.25, 0.75, 1.0), Normal::new(0.0, 0.0, -1.0));
+        let hit = square().intersect(&ray, Interval::positive()).unwrap();
+        assert!((hit.uv.0 - 0.25).abs() < 1e-12 && (hit.uv.1 - 0.75).abs() < 1e-12);
+        assert!((hit.distance - 1.0).abs() < 1e-12);
+    }
+}
@@ -0,0 +1,85 @@
+use super::matrix::Matrix;
+use super::quaternion::Quaternion;
+use super::vector::Vector;
+
+// Affine transform split into translation, rotation and scale, applied in
+// the order scale, rotation, translation. Unlike matrices, it can be
+// interpolated without introducing shear.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Trs {
+    pub(crate) translation: Vector,
+    pub(crate) rotation: Quaternion,
+    pub(crate) scale: Vector,
+}
+
+impl Trs {
+    pub(crate) fn new(translation: Vector, rotation: Quaternion, scale: Vector) -> Trs {
+        Trs {
+            translation,
+            rotation,
+            scale,
+        }
+    }
+
+    pub(crate) fn identity() -> Trs {
+        Trs::new(
+            Vector::new(0.0, 0.0, 0.0),
+            Quaternion::identity(),
+            Vector::new(1.0, 1.0, 1.0),
+        )
+    }
+
+    #[cfg_attr(not(test), allow(dead_code))]
+    pub(crate) fn from_matrix(matrix: &Matrix<4, 4>) -> Trs {
+        let (translation, rotation, scale) = matrix.decompose();
+        Trs::new(translation, rotation, scale)
+    }
+
+    pub(crate) fn to_matrix(self) -> Matrix<4, 4> {
+        Matrix::<4, 4>::translation(self.translation)
+            * self.rotation.to_matrix()
+            * Matrix::<4, 4>::scale(self.scale)
+    }
+
+    // Linear interpolation of translation and scale, slerp of the rotation.
+    pub(crate) fn interpolate(&self, other: &Trs, t: f64) -> Trs {
+        Trs::new(
+            self.translation.lerp(other.translation, t),
+            self.rotation.slerp(other.rotation, t),
+            self.scale.lerp(other.scale, t),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn from_matrix_undoes_to_matrix() {
+        let trs = Trs::new(
+            Vector::new(4.0, 5.0, 6.0),
+            Quaternion::from_axis_angle(Vector::new(0.0, 0.0, 1.0), 1.3),
+            Vector::new(1.5, 1.5, 0.5),
+        );
+        let back = Trs::from_matrix(&trs.to_matrix());
+        assert!((back.translation - trs.translation).length() < 1e-9);
+        assert!(back.rotation.dot(trs.rotation).abs() > 1.0 - 1e-9);
+        assert!((back.scale - trs.scale).length() < 1e-9);
+    }
+
+    #[test]
+    fn interpolate_blends_every_part() {
+        let start = Trs::identity();
+        let end = Trs::new(
+            Vector::new(2.0, 0.0, 0.0),
+            Quaternion::from_axis_angle(Vector::new(0.0, 1.0, 0.0), 1.0),
+            Vector::new(3.0, 3.0, 3.0),
+        );
+        let middle = start.interpolate(&end, 0.5);
+        assert!((middle.translation - Vector::new(1.0, 0.0, 0.0)).length() < 1e-12);
+        assert!((middle.scale - Vector::new(2.0, 2.0, 2.0)).length() < 1e-12);
+        let half = Quaternion::from_axis_angle(Vector::new(0.0, 1.0, 0.0), 0.5);
+        assert!(middle.rotation.dot(half).abs() > 1.0 - 1e-9);
+    }
+}
@@ -6,9 +6,9 @@ use crate::geometry::point::Point;
 
 #[derive(Copy, Clone, Debug)]
 pub(crate) struct Vector {
-    pub(super) x: f64,
-    pub(super) y: f64,
-    pub(super) z: f64,
+    pub(crate) x: f64,
+    pub(crate) y: f64,
+    pub(crate) z: f64,
 }
 
 impl Vector {
@@ -39,6 +39,10 @@ impl Vector {
         }
         Normal::new(self.x / length, self.y / length, self.z / length)
     }
+
+    pub(crate) fn lerp(&self, other: Vector, t: f64) -> Vector {
+        *self + (other - *self) * t
+    }
 }
 
 impl Mul for Vector {
@@ -49,6 +53,18 @@ impl Mul for Vector {
     }
 }
 
+impl Mul<f64> for Vector {
+    type Output = Vector;
+
+    fn mul(self, other: f64) -> Vector {
+        Vector {
+            x: self.x * other,
+            y: self.y * other,
+            z: self.z * other,
+        }
+    }
+}
+
 impl Add for Vector {
     type Output = Vector;
 
@@ -1,15 +1,332 @@
-use std::io::Result;
+use std::collections::HashMap;
+use std::ffi::OsStr;
+use std::io::{Error, ErrorKind, Result};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU8, Ordering};
+
+use crate::geometry::normal::Normal;
+use crate::geometry::point::Point;
+use crate::geometry::triangle_mesh::TriangleMesh;
+use crate::geometry::vector::Vector;
+use crate::geometry::{Axis, Transform, Transformation};
+use crate::renderer::color::{Color, Pixel};
 use crate::renderer::scene::Scene;
 
+pub(crate) mod animation_file;
+pub(crate) mod checkpoint_file;
 pub(crate) mod console;
-pub(crate) mod ppm_image;
+pub(crate) mod deflate;
+pub(crate) mod exr_image;
+pub(crate) mod gltf_file;
+pub(crate) mod hdr_image;
+pub(crate) mod inflate;
+pub(crate) mod json;
 pub(crate) mod obj_file;
+pub(crate) mod pam_image;
+pub(crate) mod pfm_image;
+pub(crate) mod ply_file;
+pub(crate) mod png_image;
+pub(crate) mod ppm_image;
+pub(crate) mod stl_file;
 
+// Receives the rendered frame. Writers without an alpha channel composite
+// the pixels over black.
 pub(crate) trait Output {
-    fn dump(&self, buff: &[f64], width: usize, height: usize) -> Result<()>;
+    fn dump(&self, buff: &[Pixel], width: usize, height: usize) -> Result<()>;
 }
 
+impl Output for Box<dyn Output> {
+    fn dump(&self, buff: &[Pixel], width: usize, height: usize) -> Result<()> {
+        self.as_ref().dump(buff, width, height)
+    }
+}
 
 pub(crate) trait Input {
-   fn load(&self) -> Result<Scene>;
-}
\ No newline at end of file
+    fn load(&self) -> Result<Scene>;
+}
+
+// How much is reported on stderr while working.
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+pub(crate) enum Verbosity {
+    Quiet,
+    Normal,
+    Verbose,
+}
+
+static VERBOSITY: AtomicU8 = AtomicU8::new(Verbosity::Normal as u8);
+
+pub(crate) fn set_verbosity(verbosity: Verbosity) {
+    VERBOSITY.store(verbosity as u8, Ordering::Relaxed);
+}
+
+fn verbosity() -> Verbosity {
+    match VERBOSITY.load(Ordering::Relaxed) {
+        0 => Verbosity::Quiet,
+        1 => Verbosity::Normal,
+        _ => Verbosity::Verbose,
+    }
+}
+
+// Progress of the work, left out when quiet.
+pub(crate) fn status(message: &str) {
+    if verbosity() >= Verbosity::Normal {
+        eprintln!("{}", message);
+    }
+}
+
+// Problems that do not stop the work, left out when quiet.
+pub(crate) fn warning(message: &str) {
+    if verbosity() >= Verbosity::Normal {
+        eprintln!("warning: {}", message);
+    }
+}
+
+// Timings and other details, only shown when verbose.
+pub(crate) fn detail(message: &str) {
+    if verbosity() >= Verbosity::Verbose {
+        eprintln!("{}", message);
+    }
+}
+
+// Writes the triangle meshes of a scene where they are at `time`, one group
+// per object. Objects not made of triangles are left out.
+pub(crate) trait Export {
+    fn save(&self, scene: &Scene, time: f64) -> Result<()>;
+}
+
+// Meshes of the scene with the index of their object.
+fn scene_meshes(scene: &Scene, time: f64) -> Vec<(usize, TriangleMesh)> {
+    scene
+        .objects()
+        .iter()
+        .enumerate()
+        .filter_map(|(index, object)| Some((index, object.mesh_at(time)?)))
+        .collect()
+}
+
+// Axis that points up in a model file.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum UpAxis {
+    Y,
+    Z,
+}
+
+// Conversions applied to a loaded model so it arrives Y up, right-handed and
+// in scene units.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct ImportOptions {
+    pub(crate) up: UpAxis,
+    // Mirrors Z, turning left-handed coordinates into right-handed ones.
+    pub(crate) flip_handedness: bool,
+    // Degrees the model is turned about X, then Y, then Z once it is Y up.
+    pub(crate) rotation: Vector,
+    // Uniform scale from file units to scene units.
+    pub(crate) scale: f64,
+    // Moves the center of the model's bounds to the origin.
+    pub(crate) recenter: bool,
+    // Smooths the normals of formats without them across edges that bend
+    // less than this many degrees. Flat shading when missing.
+    pub(crate) crease_angle: Option<f64>,
+}
+
+impl Default for ImportOptions {
+    fn default() -> ImportOptions {
+        ImportOptions {
+            up: UpAxis::Y,
+            flip_handedness: false,
+            rotation: Vector::new(0.0, 0.0, 0.0),
+            scale: 1.0,
+            recenter: false,
+            crease_angle: None,
+        }
+    }
+}
+
+impl ImportOptions {
+    // Converts every object and light of a freshly loaded scene.
+    pub(crate) fn apply(&self, scene: &mut Scene) {
+        let mut transformations = vec![];
+        if self.up == UpAxis::Z {
+            // Z up becomes Y up, and Y becomes -Z so the result stays
+            // right-handed.
+            transformations.push(Transformation::Rotation(Axis::X, -90.0));
+        }
+        if self.flip_handedness {
+            transformations.push(Transformation::Scale(Vector::new(1.0, 1.0, -1.0)));
+        }
+        let rotations = [
+            (Axis::X, self.rotation.x),
+            (Axis::Y, self.rotation.y),
+            (Axis::Z, self.rotation.z),
+        ];
+        for (axis, degrees) in rotations {
+            if degrees != 0.0 {
+                transformations.push(Transformation::Rotation(axis, degrees));
+            }
+        }
+        if self.scale != 1.0 {
+            let scale = Vector::new(self.scale, self.scale, self.scale);
+            transformations.push(Transformation::Scale(scale));
+        }
+        for transformation in transformations {
+            transform_scene(scene, transformation);
+        }
+        if self.recenter {
+            let bounds = scene.bounds();
+            if !bounds.is_empty() {
+                let center = Transformation::Translation(-bounds.center().to_vector());
+                transform_scene(scene, center);
+            }
+        }
+    }
+}
+
+fn transform_scene(scene: &mut Scene, transformation: Transformation) {
+    for object in scene.objects_mut().iter_mut() {
+        object.transform(transformation);
+    }
+    let matrix = transformation.transformation_to_matrix();
+    for light in scene.lights_mut().iter_mut() {
+        light.position = matrix * light.position;
+    }
+    for viewpoint in scene.viewpoints_mut().iter_mut() {
+        viewpoint.transform(transformation);
+    }
+}
+
+// Smooth vertex normals for meshes without them. Every corner gets the area
+// weighted normal of the triangles around its vertex that bend less than
+// `crease_angle` degrees away from its own triangle, so sharper edges stay
+// hard. Corners of one vertex with different normals are split into
+// separate vertices. Returns the original vertex of every new vertex, the
+// new normals and the renumbered triangles, degenerate triangles are left
+// out.
+pub(crate) fn crease_normals(
+    positions: &[Point],
+    triangles: &[[u32; 3]],
+    crease_angle: f64,
+) -> (Vec<usize>, Vec<Normal>, Vec<[u32; 3]>) {
+    // Twice the area, pointing out of the counter-clockwise side.
+    let faces: Vec<([u32; 3], Vector)> = triangles
+        .iter()
+        .map(|&triangle| {
+            let [a, b, c] = triangle.map(|i| positions[i as usize]);
+            (triangle, (b - a).cross(c - a))
+        })
+        .filter(|(_, normal)| normal.length() > 0.0)
+        .collect();
+    let mut around: Vec<Vec<usize>> = vec![vec![]; positions.len()];
+    for (face, (corners, _)) in faces.iter().enumerate() {
+        for &corner in corners {
+            around[corner as usize].push(face);
+        }
+    }
+    let cos_crease = crease_angle.to_radians().cos();
+    let unit = |normal: Vector| normal / normal.length();
+
+    let mut vertices: HashMap<(u32, [u64; 3]), u32> = HashMap::new();
+    let mut sources = vec![];
+    let mut normals: Vec<Normal> = vec![];
+    let indices = faces
+        .iter()
+        .map(|(corners, normal)| {
+            let facing = unit(*normal);
+            corners.map(|corner| {
+                let smooth = around[corner as usize]
+                    .iter()
+                    .map(|&face| faces[face].1)
+                    .filter(|&other| unit(other).dot(facing) >= cos_crease)
+                    .fold(Vector::new(0.0, 0.0, 0.0), |sum, other| sum + other)
+                    .normalize();
+                let key = (corner, [smooth.x, smooth.y, smooth.z].map(f64::to_bits));
+                *vertices.entry(key).or_insert_with(|| {
+                    sources.push(corner as usize);
+                    normals.push(smooth);
+                    (sources.len() - 1) as u32
+                })
+            })
+        })
+        .collect();
+    (sources, normals, indices)
+}
+
+// Decoded image, pixels are stored row by row starting at the top left.
+pub(crate) struct Image {
+    pub(crate) width: usize,
+    pub(crate) height: usize,
+    pub(crate) pixels: Vec<Color>,
+}
+
+pub(crate) trait ImageInput {
+    fn read(&self) -> Result<Image>;
+}
+
+// Picks the image reader from the file extension.
+pub(crate) fn read_image(path: &Path) -> Result<Image> {
+    let extension = path
+        .extension()
+        .and_then(OsStr::to_str)
+        .map(str::to_ascii_lowercase);
+    match extension.as_deref() {
+        Some("hdr") => hdr_image::HDRImage::new(path.to_path_buf()).read(),
+        Some("pfm") => pfm_image::PFMImage::new(path.to_path_buf()).read(),
+        Some("png") => png_image::PNGImage::new(path.to_path_buf()).read(),
+        Some("ppm") => ppm_image::PPMImage::new(path.to_path_buf()).read(),
+        _ => Err(Error::new(
+            ErrorKind::InvalidInput,
+            format!("Unsupported image format {}", path.display()),
+        )),
+    }
+}
+
+// Picks the image writer from the file extension.
+pub(crate) fn image_output(path: &Path) -> Result<Box<dyn Output>> {
+    let extension = path
+        .extension()
+        .and_then(OsStr::to_str)
+        .map(str::to_ascii_lowercase);
+    image_output_as(path, extension.as_deref().unwrap_or_default())
+}
+
+// Image writer for `format`, a file extension such as "png", whatever the
+// extension of the path is.
+pub(crate) fn image_output_as(path: &Path, format: &str) -> Result<Box<dyn Output>> {
+    let path = path.to_path_buf();
+    match format {
+        "exr" => Ok(Box::new(exr_image::EXRImage::new(path))),
+        "pam" => Ok(Box::new(pam_image::PAMImage::new(path))),
+        "png" => Ok(Box::new(png_image::PNGImage::new(path))),
+        "ppm" => Ok(Box::new(ppm_image::PPMImage::new(path))),
+        _ => Err(Error::new(
+            ErrorKind::InvalidInput,
+            format!("Unsupported output format {}", path.display()),
+        )),
+    }
+}
+
+// Picks the mesh writer from the file extension.
+pub(crate) fn scene_output(path: &Path) -> Result<Box<dyn Export>> {
+    let extension = path
+        .extension()
+        .and_then(OsStr::to_str)
+        .map(str::to_ascii_lowercase);
+    let path = path.to_path_buf();
+    match extension.as_deref() {
+        Some("obj") => Ok(Box::new(obj_file::ObjectFile::new(path))),
+        Some("ply") => Ok(Box::new(ply_file::PlyFile::new(path))),
+        _ => Err(Error::new(
+            ErrorKind::InvalidInput,
+            format!("Unsupported export format {}", path.display()),
+        )),
+    }
+}
+
+// Path of one frame of an image sequence, `out.png` becomes `out_0001.png`.
+pub(crate) fn numbered_path(path: &Path, frame: usize) -> PathBuf {
+    let stem = path.file_stem().and_then(OsStr::to_str).unwrap_or_default();
+    let name = match path.extension().and_then(OsStr::to_str) {
+        Some(extension) => format!("{}_{:04}.{}", stem, frame, extension),
+        None => format!("{}_{:04}", stem, frame),
+    };
+    path.with_file_name(name)
+}
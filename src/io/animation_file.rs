@@ -0,0 +1,134 @@
+use std::io::{BufRead, Error, ErrorKind, Result};
+use std::path::PathBuf;
+
+use crate::geometry::motion::{Interpolation, Keyframe, Motion};
+use crate::geometry::quaternion::{EulerOrder, Quaternion};
+use crate::geometry::trs::Trs;
+use crate::geometry::vector::Vector;
+use crate::renderer::animation::{Animation, Target};
+
+// Plain text keyframe list, one keyframe per line:
+//
+//     # target    frame  interpolation  transform
+//     camera      1      bezier         translate 0 0 0
+//     camera      48                    translate -20 0 0 rotate 0 15 0
+//     object 2    1      linear         rotate 0 0 0 scale 1 1 1
+//     objects     1                     translate 0 0 0
+//     light 0     24                    translate 0 30 0
+//
+// The interpolation blends a keyframe into the next one and is linear when
+// left out. `translate`, `rotate` (Euler angles in degrees, applied X, Y
+// then Z unless an order such as `zyx` follows them) and `scale` may come in
+// any order, missing ones keep their identity value. Lines of the same target
+// make up one track.
+pub(crate) struct AnimationFile {
+    path: PathBuf,
+}
+
+impl AnimationFile {
+    pub(crate) fn new(path: PathBuf) -> AnimationFile {
+        AnimationFile { path }
+    }
+
+    pub(crate) fn load(&self) -> Result<Animation> {
+        let file = std::fs::File::open(&self.path)?;
+        let reader = std::io::BufReader::new(file);
+        let mut tracks: Vec<(Target, Vec<Keyframe>)> = vec![];
+        for (i, l) in reader.lines().enumerate() {
+            let l = l?;
+            let line = l.split('#').next().unwrap_or_default();
+            let words: Vec<&str> = line.split_whitespace().collect();
+            if words.is_empty() {
+                continue;
+            }
+            let (target, keyframe) =
+                parse_keyframe(&words).map_err(|message| invalid(message, i + 1))?;
+            match tracks.iter_mut().find(|(t, _)| *t == target) {
+                Some((_, keyframes)) => keyframes.push(keyframe),
+                None => tracks.push((target, vec![keyframe])),
+            }
+        }
+
+        let mut animation = Animation::new();
+        for (target, keyframes) in tracks {
+            animation.add_track(target, Motion::new(keyframes));
+        }
+        Ok(animation)
+    }
+}
+
+fn parse_keyframe(words: &[&str]) -> std::result::Result<(Target, Keyframe), String> {
+    let mut words = words.iter().copied().peekable();
+    let index = |kind: &str, word: Option<&str>| {
+        word.and_then(|word| word.parse::<usize>().ok())
+            .ok_or_else(|| format!("expected the {} number", kind))
+    };
+    let target = match words.next() {
+        Some("camera") => Target::Camera,
+        Some("objects") => Target::AllObjects,
+        Some("object") => Target::Object(index("object", words.next())?),
+        Some("light") => Target::Light(index("light", words.next())?),
+        Some(other) => return Err(format!("unknown target {}", other)),
+        None => return Err("expected a target".to_string()),
+    };
+    let time = words
+        .next()
+        .and_then(|word| word.parse::<f64>().ok())
+        .ok_or("expected the frame of the keyframe")?;
+    let interpolation = match words.peek() {
+        Some(&"linear") => Some(Interpolation::Linear),
+        Some(&"bezier") => Some(Interpolation::Bezier),
+        _ => None,
+    };
+    if interpolation.is_some() {
+        words.next();
+    }
+
+    let mut trs = Trs::identity();
+    while let Some(word) = words.next() {
+        if !matches!(word, "translate" | "rotate" | "scale") {
+            return Err(format!("unknown transform {}", word));
+        }
+        let mut component = || {
+            words
+                .next()
+                .and_then(|word| word.parse::<f64>().ok())
+                .ok_or(format!("expected three numbers after {}", word))
+        };
+        let vector = Vector::new(component()?, component()?, component()?);
+        match word {
+            "translate" => trs.translation = vector,
+            "rotate" => {
+                let order = match words.peek() {
+                    Some(&"xyz") => Some(EulerOrder::Xyz),
+                    Some(&"xzy") => Some(EulerOrder::Xzy),
+                    Some(&"yxz") => Some(EulerOrder::Yxz),
+                    Some(&"yzx") => Some(EulerOrder::Yzx),
+                    Some(&"zxy") => Some(EulerOrder::Zxy),
+                    Some(&"zyx") => Some(EulerOrder::Zyx),
+                    _ => None,
+                };
+                if order.is_some() {
+                    words.next();
+                }
+                trs.rotation = Quaternion::from_euler(
+                    vector.x.to_radians(),
+                    vector.y.to_radians(),
+                    vector.z.to_radians(),
+                    order.unwrap_or(EulerOrder::Xyz),
+                )
+            }
+            _ => trs.scale = vector,
+        }
+    }
+    let keyframe =
+        Keyframe::new(time, trs).with_interpolation(interpolation.unwrap_or(Interpolation::Linear));
+    Ok((target, keyframe))
+}
+
+fn invalid(message: String, line: usize) -> Error {
+    Error::new(
+        ErrorKind::InvalidData,
+        format!("{} at line {} of the animation file", message, line),
+    )
+}
@@ -0,0 +1,245 @@
+use std::fs::File;
+use std::io::{BufWriter, Error, ErrorKind, Read, Result, Write};
+use std::path::PathBuf;
+
+use crate::renderer::accumulation::Accumulation;
+use crate::renderer::aov::Aov;
+use crate::renderer::color::{Color, Pixel};
+use crate::renderer::frame::Region;
+
+const MAGIC: &[u8; 8] = b"RTCHECK2";
+
+// State of a progressive render, so it can go on after the process stopped.
+// Little endian binary file:
+//
+//     "RTCHECK2"
+//     u32 each: image width and height, region x, y, width and height,
+//               samples per pass, 1 if passes double in size or 0 if not,
+//               number of passes done
+//     number of extra passes  u32, then each name as a u8 length and bytes
+//     per pixel: samples u32, squared luminance f64,
+//                image red, green, blue, alpha f64,
+//                the same four values for every extra pass
+//
+// Sums are kept at full precision so a resumed render ends up with exactly
+// the image of an uninterrupted one.
+pub(crate) struct CheckpointFile {
+    path: PathBuf,
+}
+
+impl CheckpointFile {
+    pub(crate) fn new(path: PathBuf) -> CheckpointFile {
+        CheckpointFile { path }
+    }
+
+    pub(crate) fn load(&self) -> Result<Accumulation> {
+        let mut bytes = vec![];
+        File::open(&self.path)?.read_to_end(&mut bytes)?;
+        let mut reader = Reader {
+            bytes: &bytes,
+            position: 0,
+        };
+        if reader.take(MAGIC.len())? != MAGIC {
+            return Err(invalid("Not a checkpoint file"));
+        }
+        let mut header = [0; 9];
+        for value in &mut header {
+            *value = reader.u32()? as usize;
+        }
+        let [width, height, x, y, region_width, region_height, pass_samples, doubling, passes] =
+            header;
+        let region = Region::new(x, y, region_width, region_height);
+        if !region.fits(width, height) {
+            return Err(invalid("The checkpoint region is outside of the image"));
+        }
+        let mut aovs = vec![];
+        for _ in 0..reader.u32()? {
+            let length = reader.take(1)?[0] as usize;
+            let name = std::str::from_utf8(reader.take(length)?).unwrap_or_default();
+            aovs.push(
+                Aov::from_name(name)
+                    .ok_or_else(|| invalid(&format!("Unknown pass {} in the checkpoint", name)))?,
+            );
+        }
+
+        // Pixels are only allocated once the file is known to hold all of
+        // them.
+        let pixel_size = 4 + 8 + 32 * (1 + aovs.len());
+        let expected = width
+            .checked_mul(height)
+            .and_then(|pixels| pixels.checked_mul(pixel_size));
+        if expected != Some(reader.remaining()) {
+            return Err(invalid(
+                "The checkpoint file does not match the size of its image",
+            ));
+        }
+        let mut accumulation =
+            Accumulation::new(width, height, region, pass_samples, doubling != 0, &aovs);
+        accumulation.passes = passes;
+        for index in 0..width * height {
+            accumulation.samples[index] = reader.u32()?;
+            accumulation.squares[index] = reader.f64()?;
+            accumulation.pixels[index] = reader.pixel()?;
+            for (_, buffer) in &mut accumulation.aovs {
+                buffer[index] = reader.pixel()?;
+            }
+        }
+        Ok(accumulation)
+    }
+
+    // Writes next to the checkpoint first and then replaces it, so a render
+    // stopped while saving still leaves the previous checkpoint.
+    pub(crate) fn save(&self, accumulation: &Accumulation) -> Result<()> {
+        let mut temporary = self.path.clone().into_os_string();
+        temporary.push(".tmp");
+        let temporary = PathBuf::from(temporary);
+
+        let mut file = BufWriter::new(File::create(&temporary)?);
+        file.write_all(MAGIC)?;
+        let region = accumulation.region;
+        let header = [
+            accumulation.width,
+            accumulation.height,
+            region.x,
+            region.y,
+            region.width,
+            region.height,
+            accumulation.pass_samples,
+            accumulation.doubling as usize,
+            accumulation.passes,
+            accumulation.aovs.len(),
+        ];
+        for value in header {
+            file.write_all(&(value as u32).to_le_bytes())?;
+        }
+        for (aov, _) in &accumulation.aovs {
+            file.write_all(&[aov.name().len() as u8])?;
+            file.write_all(aov.name().as_bytes())?;
+        }
+        let pixel = |file: &mut BufWriter<File>, pixel: Pixel| -> Result<()> {
+            let Pixel { color, alpha } = pixel;
+            for value in [color.r, color.g, color.b, alpha] {
+                file.write_all(&value.to_le_bytes())?;
+            }
+            Ok(())
+        };
+        for index in 0..accumulation.samples.len() {
+            file.write_all(&accumulation.samples[index].to_le_bytes())?;
+            file.write_all(&accumulation.squares[index].to_le_bytes())?;
+            pixel(&mut file, accumulation.pixels[index])?;
+            for (_, buffer) in &accumulation.aovs {
+                pixel(&mut file, buffer[index])?;
+            }
+        }
+        file.into_inner()?.sync_all()?;
+        std::fs::rename(&temporary, &self.path)
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl Reader<'_> {
+    fn remaining(&self) -> usize {
+        self.bytes.len() - self.position
+    }
+
+    fn take(&mut self, count: usize) -> Result<&[u8]> {
+        let bytes = self
+            .bytes
+            .get(self.position..self.position + count)
+            .ok_or_else(|| invalid("The checkpoint file is cut short"))?;
+        self.position += count;
+        Ok(bytes)
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn f64(&mut self) -> Result<f64> {
+        Ok(f64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    fn pixel(&mut self) -> Result<Pixel> {
+        let color = Color::new(self.f64()?, self.f64()?, self.f64()?);
+        Ok(Pixel::new(color, self.f64()?))
+    }
+}
+
+fn invalid(message: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn accumulation() -> Accumulation {
+        let region = Region::new(1, 0, 2, 2);
+        let mut accumulation = Accumulation::new(3, 2, region, 4, true, &[Aov::Depth]);
+        accumulation.passes = 3;
+        for index in 0..6 {
+            accumulation.samples[index] = index as u32;
+            accumulation.squares[index] = 0.1 * index as f64;
+            accumulation.pixels[index] = Pixel::new(Color::new(0.3, 0.5, index as f64), 0.75);
+            accumulation.aovs[0].1[index] = Pixel::new(Color::gray(index as f64 / 7.0), 1.0);
+        }
+        accumulation
+    }
+
+    fn temporary(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("{}_{}.checkpoint", name, std::process::id()))
+    }
+
+    #[test]
+    fn saved_checkpoints_load_back_exactly() {
+        let path = temporary("round_trip");
+        let file = CheckpointFile::new(path.clone());
+        let saved = accumulation();
+        file.save(&saved).unwrap();
+        let loaded = file.load();
+        std::fs::remove_file(&path).unwrap();
+
+        let loaded = loaded.unwrap();
+        assert_eq!((loaded.width, loaded.height), (3, 2));
+        assert_eq!(loaded.region, Region::new(1, 0, 2, 2));
+        assert_eq!(
+            (loaded.pass_samples, loaded.doubling, loaded.passes),
+            (4, true, 3)
+        );
+        assert_eq!(loaded.samples, saved.samples);
+        assert_eq!(loaded.squares, saved.squares);
+        assert_eq!(loaded.pixels, saved.pixels);
+        assert_eq!(loaded.aovs, saved.aovs);
+    }
+
+    #[test]
+    fn files_that_do_not_hold_every_pixel_are_rejected() {
+        let path = temporary("truncated");
+        let file = CheckpointFile::new(path.clone());
+        file.save(&accumulation()).unwrap();
+        let mut bytes = std::fs::read(&path).unwrap();
+
+        // One byte short, and a huge image with no pixels at all.
+        bytes.pop();
+        std::fs::write(&path, &bytes).unwrap();
+        let truncated = file.load();
+        bytes.truncate(MAGIC.len() + 40);
+        bytes[8..16].copy_from_slice(&[0xff; 8]);
+        bytes[16..24].copy_from_slice(&[0; 8]);
+        bytes[44..48].copy_from_slice(&[0; 4]);
+        std::fs::write(&path, &bytes).unwrap();
+        let huge = file.load();
+        std::fs::remove_file(&path).unwrap();
+
+        for result in [truncated, huge] {
+            let Err(error) = result else {
+                panic!("a checkpoint without all its pixels loaded");
+            };
+            assert_eq!(error.kind(), ErrorKind::InvalidData);
+        }
+    }
+}
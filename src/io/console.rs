@@ -1,16 +1,22 @@
 use std::io::{BufWriter, Result, Write};
 
 use crate::io::Output;
+use crate::renderer::color::{Color, Pixel};
 
 pub(crate) struct Console {}
 
 impl Output for Console {
-    fn dump(&self, buff: &[f64], width: usize, height: usize) -> Result<()> {
+    fn dump(&self, buff: &[Pixel], width: usize, height: usize) -> Result<()> {
         let mut stream = BufWriter::with_capacity(width * height, std::io::stdout());
         for y in 0..height {
             for x in 0..width {
                 let index = y * width + x;
-                let intensity = buff[index];
+                let pixel = buff[index];
+                let intensity = if pixel.alpha > 0.0 {
+                    pixel.over(Color::black()).luminance()
+                } else {
+                    -1.0
+                };
                 let char = match intensity {
                     l if l > 0.0 && l < 0.2 => b'.',
                     l if l > 0.2 && l < 0.5 => b'*',
@@ -25,4 +31,4 @@ impl Output for Console {
         stream.flush()?;
         Ok(())
     }
-}
\ No newline at end of file
+}
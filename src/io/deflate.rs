@@ -0,0 +1,217 @@
+use super::inflate::{DISTANCE_BASE, DISTANCE_EXTRA, LENGTH_BASE, LENGTH_EXTRA};
+
+// Encoder for zlib streams (RFC 1950), the counterpart of `inflate`. Data is
+// compressed into a single block with the fixed Huffman codes, using greedy
+// LZ77 matching over hash chains.
+
+const WINDOW_SIZE: usize = 32768;
+const MIN_MATCH: usize = 3;
+const MAX_MATCH: usize = 258;
+// How many earlier positions with the same hash are tried per match.
+const MAX_CHAIN: usize = 64;
+const HASH_BITS: u32 = 15;
+
+pub(crate) fn zlib_compress(data: &[u8]) -> Vec<u8> {
+    // Deflate with a 32K window and the default compression level.
+    let mut output = vec![0x78, 0x9c];
+    output.extend(deflate(data));
+    output.extend_from_slice(&adler32(data).to_be_bytes());
+    output
+}
+
+pub(crate) fn deflate(data: &[u8]) -> Vec<u8> {
+    let mut writer = BitWriter::default();
+    // Final block compressed with the fixed codes.
+    writer.bits(1, 1);
+    writer.bits(1, 2);
+
+    let mut head = vec![usize::MAX; 1 << HASH_BITS];
+    let mut previous = vec![usize::MAX; WINDOW_SIZE];
+    let insert = |position: usize, head: &mut [usize], previous: &mut [usize]| {
+        if position + MIN_MATCH <= data.len() {
+            let hash = hash(&data[position..position + MIN_MATCH]);
+            previous[position % WINDOW_SIZE] = head[hash];
+            head[hash] = position;
+        }
+    };
+
+    let mut position = 0;
+    while position < data.len() {
+        let (length, distance) = longest_match(data, position, &head, &previous);
+        if length >= MIN_MATCH {
+            write_length(&mut writer, length);
+            write_distance(&mut writer, distance);
+            for offset in 0..length {
+                insert(position + offset, &mut head, &mut previous);
+            }
+            position += length;
+        } else {
+            write_literal(&mut writer, data[position] as u16);
+            insert(position, &mut head, &mut previous);
+            position += 1;
+        }
+    }
+    write_literal(&mut writer, 256);
+    writer.finish()
+}
+
+fn hash(bytes: &[u8]) -> usize {
+    let value = (bytes[0] as u32) << 16 | (bytes[1] as u32) << 8 | bytes[2] as u32;
+    (value.wrapping_mul(2_654_435_761) >> (32 - HASH_BITS)) as usize
+}
+
+fn longest_match(
+    data: &[u8],
+    position: usize,
+    head: &[usize],
+    previous: &[usize],
+) -> (usize, usize) {
+    if position + MIN_MATCH > data.len() {
+        return (0, 0);
+    }
+    let limit = MAX_MATCH.min(data.len() - position);
+    let mut best = (0, 0);
+    let mut candidate = head[hash(&data[position..position + MIN_MATCH])];
+    for _ in 0..MAX_CHAIN {
+        if candidate == usize::MAX || position - candidate > WINDOW_SIZE {
+            break;
+        }
+        let length = data[candidate..]
+            .iter()
+            .zip(&data[position..position + limit])
+            .take_while(|(a, b)| a == b)
+            .count();
+        if length > best.0 {
+            best = (length, position - candidate);
+            if length == limit {
+                break;
+            }
+        }
+        let next = previous[candidate % WINDOW_SIZE];
+        // Older entries of the ring buffer may have been overwritten.
+        if next == usize::MAX || next >= candidate {
+            break;
+        }
+        candidate = next;
+    }
+    best
+}
+
+// Fixed literal/length codes from RFC 1951 section 3.2.6.
+fn write_literal(writer: &mut BitWriter, symbol: u16) {
+    let (code, length) = match symbol {
+        0..=143 => (0x30 + symbol, 8),
+        144..=255 => (0x190 + symbol - 144, 9),
+        256..=279 => (symbol - 256, 7),
+        _ => (0xc0 + symbol - 280, 8),
+    };
+    writer.huffman(code as u32, length);
+}
+
+fn write_length(writer: &mut BitWriter, length: usize) {
+    let index = LENGTH_BASE
+        .iter()
+        .rposition(|&base| base as usize <= length)
+        .unwrap();
+    write_literal(writer, 257 + index as u16);
+    writer.bits(
+        (length - LENGTH_BASE[index] as usize) as u32,
+        LENGTH_EXTRA[index] as u32,
+    );
+}
+
+fn write_distance(writer: &mut BitWriter, distance: usize) {
+    let index = DISTANCE_BASE
+        .iter()
+        .rposition(|&base| base as usize <= distance)
+        .unwrap();
+    writer.huffman(index as u32, 5);
+    writer.bits(
+        (distance - DISTANCE_BASE[index] as usize) as u32,
+        DISTANCE_EXTRA[index] as u32,
+    );
+}
+
+pub(crate) fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for chunk in data.chunks(5552) {
+        for &byte in chunk {
+            a += byte as u32;
+            b += a;
+        }
+        a %= 65521;
+        b %= 65521;
+    }
+    b << 16 | a
+}
+
+#[derive(Default)]
+struct BitWriter {
+    output: Vec<u8>,
+    bit_buffer: u64,
+    bit_count: u32,
+}
+
+impl BitWriter {
+    // Writes `count` bits, least significant bit first.
+    fn bits(&mut self, value: u32, count: u32) {
+        self.bit_buffer |= (value as u64) << self.bit_count;
+        self.bit_count += count;
+        while self.bit_count >= 8 {
+            self.output.push(self.bit_buffer as u8);
+            self.bit_buffer >>= 8;
+            self.bit_count -= 8;
+        }
+    }
+
+    // Huffman codes are stored most significant bit first.
+    fn huffman(&mut self, code: u32, length: u32) {
+        let reversed = code.reverse_bits() >> (32 - length);
+        self.bits(reversed, length);
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.bit_count > 0 {
+            self.output.push(self.bit_buffer as u8);
+        }
+        self.output
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::io::inflate::zlib_decompress;
+
+    #[test]
+    fn compressed_data_inflates_back() {
+        let text = b"the quick brown fox jumps over the lazy dog, the lazy dog sleeps".to_vec();
+        let run = vec![7; 1000];
+        // Repeats further back than the window, so matches have to be
+        // found again.
+        let mut state: u32 = 1;
+        let noise: Vec<u8> = (0..20000)
+            .map(|_| {
+                state = state.wrapping_mul(1664525).wrapping_add(1013904223);
+                (state >> 24) as u8
+            })
+            .collect();
+        let far = [noise.clone(), noise.clone()].concat();
+        for data in [vec![], text, run, far] {
+            let compressed = zlib_compress(&data);
+            assert_eq!(zlib_decompress(&compressed).unwrap(), data);
+        }
+    }
+
+    #[test]
+    fn repetition_is_compressed() {
+        let data = b"abcabcabc".repeat(500);
+        assert!(zlib_compress(&data).len() < data.len() / 20);
+    }
+
+    #[test]
+    fn adler32_matches_the_reference() {
+        assert_eq!(adler32(b""), 1);
+        assert_eq!(adler32(b"Wikipedia"), 0x11e6_0398);
+    }
+}
@@ -0,0 +1,151 @@
+use std::fs::File;
+use std::io::{BufWriter, Result, Write};
+use std::path::{Path, PathBuf};
+
+use crate::io::Output;
+use crate::renderer::color::Pixel;
+use crate::renderer::frame::Frame;
+
+// OpenEXR image with uncompressed 32 bit float scanlines. Colors stay linear
+// and unclamped, and are premultiplied by alpha as the format expects.
+pub(crate) struct EXRImage {
+    file_path: PathBuf,
+}
+
+impl EXRImage {
+    pub(crate) fn new(file_path: PathBuf) -> EXRImage {
+        EXRImage { file_path }
+    }
+}
+
+impl Output for EXRImage {
+    fn dump(&self, buff: &[Pixel], width: usize, height: usize) -> Result<()> {
+        let pixels = &buff[..width * height];
+        let channel = |value: fn(&Pixel) -> f64| -> Vec<f32> {
+            pixels.iter().map(|pixel| value(pixel) as f32).collect()
+        };
+        write_channels(
+            &self.file_path,
+            width,
+            height,
+            vec![
+                ("R".to_string(), channel(|pixel| pixel.color.r)),
+                ("G".to_string(), channel(|pixel| pixel.color.g)),
+                ("B".to_string(), channel(|pixel| pixel.color.b)),
+                ("A".to_string(), channel(|pixel| pixel.alpha)),
+            ],
+        )
+    }
+}
+
+// Writes the frame as one multi-layer image: the lit image as R, G, B and A
+// and every extra pass as a layer named after it, for example `depth.Z`.
+pub(crate) fn write_layers(path: &Path, frame: &Frame) -> Result<()> {
+    let channel = |pixels: &[Pixel], component: usize| -> Vec<f32> {
+        pixels
+            .iter()
+            .map(|pixel| match component {
+                0 => pixel.color.r,
+                1 => pixel.color.g,
+                2 => pixel.color.b,
+                _ => pixel.alpha,
+            } as f32)
+            .collect()
+    };
+    let mut channels = vec![];
+    for (component, name) in ["R", "G", "B", "A"].iter().enumerate() {
+        channels.push((name.to_string(), channel(&frame.pixels, component)));
+    }
+    for (aov, pixels) in &frame.aovs {
+        for (component, name) in aov.channels().iter().enumerate() {
+            let name = format!("{}.{}", aov.name(), name);
+            channels.push((name, channel(pixels, component)));
+        }
+    }
+    write_channels(path, frame.width, frame.height, channels)
+}
+
+// Writes any number of named float channels, each holding one value per
+// pixel row by row. Names may contain dots to group channels into layers.
+pub(crate) fn write_channels(
+    path: &Path,
+    width: usize,
+    height: usize,
+    mut channels: Vec<(String, Vec<f32>)>,
+) -> Result<()> {
+    // Readers expect the channel list sorted by name.
+    channels.sort_by(|a, b| a.0.cmp(&b.0));
+    let long_names = channels.iter().any(|(name, _)| name.len() > 31);
+
+    let mut header = vec![];
+    header.extend_from_slice(&20000630u32.to_le_bytes());
+    // Version 2, single part scanline file.
+    let flags: u32 = if long_names { 0x400 } else { 0 };
+    header.extend_from_slice(&(2 | flags).to_le_bytes());
+
+    let mut channel_list = vec![];
+    for (name, _) in &channels {
+        channel_list.extend_from_slice(name.as_bytes());
+        channel_list.push(0);
+        // Float samples, not perceptually linear, full resolution.
+        channel_list.extend_from_slice(&2i32.to_le_bytes());
+        channel_list.extend_from_slice(&[0, 0, 0, 0]);
+        channel_list.extend_from_slice(&1i32.to_le_bytes());
+        channel_list.extend_from_slice(&1i32.to_le_bytes());
+    }
+    channel_list.push(0);
+    attribute(&mut header, "channels", "chlist", &channel_list);
+    attribute(&mut header, "compression", "compression", &[0]);
+    let mut window = vec![];
+    for value in [0, 0, width as i32 - 1, height as i32 - 1] {
+        window.extend_from_slice(&value.to_le_bytes());
+    }
+    attribute(&mut header, "dataWindow", "box2i", &window);
+    attribute(&mut header, "displayWindow", "box2i", &window);
+    attribute(&mut header, "lineOrder", "lineOrder", &[0]);
+    attribute(
+        &mut header,
+        "pixelAspectRatio",
+        "float",
+        &1f32.to_le_bytes(),
+    );
+    attribute(&mut header, "screenWindowCenter", "v2f", &[0; 8]);
+    attribute(
+        &mut header,
+        "screenWindowWidth",
+        "float",
+        &1f32.to_le_bytes(),
+    );
+    header.push(0);
+
+    // Every scanline is its own chunk: y, byte count, then each channel.
+    let line_size = width * 4 * channels.len();
+    let chunk_size = 8 + line_size;
+    let table_end = header.len() + 8 * height;
+
+    let mut stream = BufWriter::new(File::create(path)?);
+    stream.write_all(&header)?;
+    for y in 0..height {
+        let offset = (table_end + y * chunk_size) as u64;
+        stream.write_all(&offset.to_le_bytes())?;
+    }
+    for y in 0..height {
+        stream.write_all(&(y as i32).to_le_bytes())?;
+        stream.write_all(&(line_size as i32).to_le_bytes())?;
+        for (_, values) in &channels {
+            for value in &values[y * width..(y + 1) * width] {
+                stream.write_all(&value.to_le_bytes())?;
+            }
+        }
+    }
+    stream.flush()
+}
+
+fn attribute(header: &mut Vec<u8>, name: &str, kind: &str, value: &[u8]) {
+    header.extend_from_slice(name.as_bytes());
+    header.push(0);
+    header.extend_from_slice(kind.as_bytes());
+    header.push(0);
+    header.extend_from_slice(&(value.len() as i32).to_le_bytes());
+    header.extend_from_slice(value);
+}
@@ -0,0 +1,885 @@
+use std::{
+    io::{Error, ErrorKind, Result},
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    geometry::{
+        matrix::Matrix, mesh_check::Repairs, normal::Normal, point::Point, quaternion::Quaternion,
+        triangle_mesh::TriangleMesh, trs::Trs, vector::Vector, Transform, Transformation,
+    },
+    renderer::{
+        camera::Viewpoint,
+        color::Color,
+        light::Light,
+        material::Material,
+        scene::Scene,
+        texture::{ImageTexture, Texture, WrapMode},
+    },
+};
+
+use super::{
+    crease_normals, json::Json, png_image, read_image, status, warning, Image, ImportOptions, Input,
+};
+
+const GLB_MAGIC: &[u8; 4] = b"glTF";
+const GLB_JSON_CHUNK: u32 = 0x4e4f534a;
+const GLB_BIN_CHUNK: u32 = 0x004e4942;
+
+// Directional lights become point lights this far away in the direction the
+// light comes from. Point lights do not fall off, so that is the same.
+const DIRECTIONAL_LIGHT_DISTANCE: f64 = 1e6;
+
+// glTF 2.0 scene, as a `.gltf` JSON file with external or embedded buffers or
+// as a binary `.glb` file. The node hierarchy of the default scene is
+// flattened: meshes are transformed into place, perspective cameras become
+// viewpoints and `KHR_lights_punctual` lights become point lights at their
+// node. Light colors, intensities and spot cones, skins, morph targets and
+// animations are not supported.
+pub(crate) struct GltfFile {
+    path: PathBuf,
+    options: ImportOptions,
+}
+
+impl GltfFile {
+    pub(crate) fn new(path: PathBuf) -> GltfFile {
+        GltfFile {
+            path,
+            options: ImportOptions::default(),
+        }
+    }
+
+    pub(crate) fn with_options(mut self, options: ImportOptions) -> GltfFile {
+        self.options = options;
+        self
+    }
+}
+
+// The JSON part with every buffer loaded.
+struct Document {
+    json: Json,
+    buffers: Vec<Vec<u8>>,
+    directory: PathBuf,
+}
+
+// Scene material of every glTF material, and whether its base color comes
+// from a texture.
+struct Materials {
+    indices: Vec<usize>,
+    textured: Vec<bool>,
+    // Material for primitives with vertex colors, added when first needed.
+    vertex_color: Option<usize>,
+}
+
+impl Input for GltfFile {
+    fn load(&self) -> Result<Scene> {
+        let bytes = std::fs::read(&self.path)?;
+        status("Loading data from gltf file...");
+        let (text, binary) = if bytes.starts_with(GLB_MAGIC) {
+            split_glb(&bytes)?
+        } else {
+            (bytes.as_slice(), None)
+        };
+        let text = std::str::from_utf8(text).map_err(|_| invalid("The glTF JSON is not UTF-8"))?;
+        let json = Json::parse(text)?;
+        let version = json
+            .get("asset")
+            .get("version")
+            .as_str()
+            .unwrap_or_default();
+        if !version.starts_with('2') {
+            return Err(invalid(&format!("Unsupported glTF version {}", version)));
+        }
+
+        let directory = self.path.parent().unwrap_or(Path::new("")).to_path_buf();
+        let mut buffers = vec![];
+        for (i, buffer) in json.get("buffers").items().iter().enumerate() {
+            let data = match buffer.get("uri").as_str() {
+                Some(uri) => load_uri(uri, &directory)?,
+                None if i == 0 => binary
+                    .ok_or_else(|| invalid("The glTF buffer 0 has no data"))?
+                    .to_vec(),
+                None => return Err(invalid(&format!("The glTF buffer {} has no uri", i))),
+            };
+            buffers.push(data);
+        }
+        let document = Document {
+            json,
+            buffers,
+            directory,
+        };
+
+        let mut scene = Scene::new();
+        let mut materials = document.materials(&mut scene)?;
+        let json = &document.json;
+        let roots: Vec<usize> = match json
+            .get("scenes")
+            .at(json.get("scene").as_usize().unwrap_or(0))
+        {
+            Json::Null => {
+                // Without scenes every node that is nobody's child is a root.
+                let nodes = json.get("nodes").items();
+                let children: Vec<usize> = nodes
+                    .iter()
+                    .flat_map(|node| node.get("children").items())
+                    .filter_map(Json::as_usize)
+                    .collect();
+                (0..nodes.len()).filter(|i| !children.contains(i)).collect()
+            }
+            root => root
+                .get("nodes")
+                .items()
+                .iter()
+                .filter_map(Json::as_usize)
+                .collect(),
+        };
+        let mut stack: Vec<(usize, Matrix<4, 4>, usize)> = roots
+            .into_iter()
+            .map(|node| (node, Matrix::identity(), 0))
+            .collect();
+        while let Some((index, parent, depth)) = stack.pop() {
+            let node = json.get("nodes").at(index);
+            if node.is_null() || depth > json.get("nodes").items().len() {
+                return Err(invalid(&format!(
+                    "The glTF node {} does not exist or is its own ancestor",
+                    index
+                )));
+            }
+            let to_world = parent * local_matrix(node)?;
+            if let Some(mesh) = node.get("mesh").as_usize() {
+                document.add_mesh(mesh, to_world, &mut scene, &mut materials, &self.options)?;
+            }
+            if let Some(camera) = node.get("camera").as_usize() {
+                let camera = json.get("cameras").at(camera);
+                match camera.get("perspective").get("yfov").as_f64() {
+                    Some(yfov) => scene.add_viewpoint(Viewpoint::new(to_world, yfov.to_degrees())),
+                    None => warning(&format!(
+                        "Skipping glTF camera of node {}, only perspective cameras are supported",
+                        index
+                    )),
+                }
+            }
+            let light = node
+                .get("extensions")
+                .get("KHR_lights_punctual")
+                .get("light");
+            if let Some(light) = light.as_usize() {
+                let light = json
+                    .get("extensions")
+                    .get("KHR_lights_punctual")
+                    .get("lights")
+                    .at(light);
+                let origin = to_world * Point::new(0.0, 0.0, 0.0);
+                let position = match light.get("type").as_str() {
+                    // The light shines down the local -Z axis.
+                    Some("directional") => {
+                        let towards =
+                            Vector::from((to_world * Vector::new(0.0, 0.0, 1.0)).normalize());
+                        origin + towards * DIRECTIONAL_LIGHT_DISTANCE
+                    }
+                    _ => origin,
+                };
+                scene.add_light(Light::new(position));
+            }
+            for child in node
+                .get("children")
+                .items()
+                .iter()
+                .filter_map(Json::as_usize)
+            {
+                stack.push((child, to_world, depth + 1));
+            }
+        }
+        self.options.apply(&mut scene);
+        Ok(scene)
+    }
+}
+
+// Splits a binary file into its JSON chunk and optional binary chunk.
+fn split_glb(bytes: &[u8]) -> Result<(&[u8], Option<&[u8]>)> {
+    let word = |offset: usize| {
+        bytes
+            .get(offset..offset + 4)
+            .map(|word| u32::from_le_bytes(word.try_into().unwrap()))
+            .ok_or_else(|| invalid("The GLB file ends early"))
+    };
+    if word(4)? != 2 {
+        return Err(invalid("Unsupported GLB version"));
+    }
+    let length = (word(8)? as usize).min(bytes.len());
+    let mut chunks = vec![];
+    let mut position = 12;
+    while position + 8 <= length {
+        let size = word(position)? as usize;
+        let kind = word(position + 4)?;
+        let data = bytes
+            .get(position + 8..position + 8 + size)
+            .ok_or_else(|| invalid("The GLB file ends early"))?;
+        chunks.push((kind, data));
+        position += 8 + size;
+    }
+    match chunks.as_slice() {
+        [(GLB_JSON_CHUNK, json), rest @ ..] => {
+            let binary = rest
+                .iter()
+                .find(|(kind, _)| *kind == GLB_BIN_CHUNK)
+                .map(|(_, data)| *data);
+            Ok((json, binary))
+        }
+        _ => Err(invalid("The GLB file does not start with a JSON chunk")),
+    }
+}
+
+// Contents of a data URI or of a file relative to `directory`.
+fn load_uri(uri: &str, directory: &Path) -> Result<Vec<u8>> {
+    if let Some(data) = uri.strip_prefix("data:") {
+        let (_, encoded) = data
+            .split_once(";base64,")
+            .ok_or_else(|| invalid("Only base64 data URIs are supported"))?;
+        return decode_base64(encoded);
+    }
+    std::fs::read(directory.join(decode_percent(uri)))
+}
+
+fn decode_base64(text: &str) -> Result<Vec<u8>> {
+    let value = |c: u8| match c {
+        b'A'..=b'Z' => Some(c - b'A'),
+        b'a'..=b'z' => Some(c - b'a' + 26),
+        b'0'..=b'9' => Some(c - b'0' + 52),
+        b'+' | b'-' => Some(62),
+        b'/' | b'_' => Some(63),
+        _ => None,
+    };
+    let mut data = vec![];
+    let (mut bits, mut count) = (0u32, 0);
+    for c in text.bytes().take_while(|&c| c != b'=') {
+        let Some(value) = value(c) else {
+            return Err(invalid("Invalid base64 data"));
+        };
+        bits = (bits << 6) | value as u32;
+        count += 6;
+        if count >= 8 {
+            count -= 8;
+            data.push((bits >> count) as u8);
+        }
+    }
+    Ok(data)
+}
+
+// Resolves `%XX` escapes in relative URIs such as `my%20model.bin`.
+fn decode_percent(uri: &str) -> String {
+    let bytes = uri.as_bytes();
+    let mut decoded = vec![];
+    let mut i = 0;
+    while i < bytes.len() {
+        let escape = bytes
+            .get(i + 1..i + 3)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (bytes[i], escape) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+// Node transform, either a column major matrix or translation, rotation and
+// scale.
+fn local_matrix(node: &Json) -> Result<Matrix<4, 4>> {
+    if let Some(values) = node.get("matrix").as_numbers() {
+        let values: [f64; 16] = values
+            .try_into()
+            .map_err(|_| invalid("A glTF node matrix needs 16 numbers"))?;
+        let mut matrix = Matrix::<4, 4>::identity();
+        for (i, value) in values.iter().enumerate() {
+            matrix[i % 4][i / 4] = *value;
+        }
+        return Ok(matrix);
+    }
+    let vector = |key: &str, default: Vector| match node.get(key).as_numbers().as_deref() {
+        Some(&[x, y, z]) => Vector::new(x, y, z),
+        _ => default,
+    };
+    let translation = vector("translation", Vector::new(0.0, 0.0, 0.0));
+    let scale = vector("scale", Vector::new(1.0, 1.0, 1.0));
+    let rotation = match node.get("rotation").as_numbers().as_deref() {
+        Some(&[x, y, z, w]) => Quaternion::new(w, x, y, z).normalize(),
+        _ => Quaternion::identity(),
+    };
+    Ok(Trs::new(translation, rotation, scale).to_matrix())
+}
+
+impl Document {
+    // Adds every material to the scene. Base color factors only apply to
+    // untextured materials, since textures are not tinted. Metallic and
+    // roughness factors are kept on the materials, but the renderer only
+    // shades the diffuse color, so shiny and metallic surfaces come out as
+    // plain diffuse ones. That is reported once per file.
+    fn materials(&self, scene: &mut Scene) -> Result<Materials> {
+        let mut materials = Materials {
+            indices: vec![],
+            textured: vec![],
+            vertex_color: None,
+        };
+        let mut unshaded = 0;
+        for material in self.json.get("materials").items() {
+            let pbr = material.get("pbrMetallicRoughness");
+            let factor = pbr.get("baseColorFactor").as_numbers().unwrap_or_default();
+            let base_color = match factor.as_slice() {
+                &[r, g, b, ..] => Color::new(r, g, b),
+                _ => Color::white(),
+            };
+            let texture = self.texture(pbr.get("baseColorTexture"))?;
+            materials.textured.push(texture.is_some());
+            let diffuse = texture.map_or(Texture::Constant(base_color), Texture::Image);
+            let metallic = pbr.get("metallicFactor").as_f64().unwrap_or(1.0);
+            let roughness = pbr.get("roughnessFactor").as_f64().unwrap_or(1.0);
+            if metallic > 0.0 || roughness < 1.0 {
+                unshaded += 1;
+            }
+            let mut result = Material::new(diffuse).with_metallic_roughness(metallic, roughness);
+            if let Some(normal_map) = self.texture(material.get("normalTexture"))? {
+                result = result.with_normal_map(Texture::Image(normal_map));
+            }
+            materials.indices.push(scene.add_material(result));
+        }
+        if unshaded > 0 {
+            warning(&format!(
+                "Shading {} glTF materials as diffuse, metallic and roughness are not supported",
+                unshaded
+            ));
+        }
+        Ok(materials)
+    }
+
+    // Image of a texture reference. Images in formats the crate cannot read
+    // are skipped with a note.
+    fn texture(&self, reference: &Json) -> Result<Option<ImageTexture>> {
+        let Some(index) = reference.get("index").as_usize() else {
+            return Ok(None);
+        };
+        let texture = self.json.get("textures").at(index);
+        let wrap = match self
+            .json
+            .get("samplers")
+            .at(texture.get("sampler").as_usize().unwrap_or(usize::MAX))
+            .get("wrapS")
+            .as_usize()
+        {
+            Some(33071) => WrapMode::Clamp,
+            Some(33648) => WrapMode::MirroredRepeat,
+            _ => WrapMode::Repeat,
+        };
+        let Some(source) = texture.get("source").as_usize() else {
+            return Ok(None);
+        };
+        let image = self.json.get("images").at(source);
+        match self.image(image)? {
+            Some(image) => Ok(Some(ImageTexture::new(image, wrap))),
+            None => {
+                warning(&format!(
+                    "Skipping glTF image {}, its format is not supported",
+                    source
+                ));
+                Ok(None)
+            }
+        }
+    }
+
+    fn image(&self, image: &Json) -> Result<Option<Image>> {
+        if let Some(uri) = image.get("uri").as_str() {
+            if uri.starts_with("data:image/png") {
+                return png_image::decode(&load_uri(uri, &self.directory)?).map(Some);
+            }
+            if uri.starts_with("data:") {
+                return Ok(None);
+            }
+            let path = self.directory.join(decode_percent(uri));
+            return match read_image(&path) {
+                Ok(image) => Ok(Some(image)),
+                Err(error) if error.kind() == ErrorKind::InvalidInput => Ok(None),
+                Err(error) => Err(error),
+            };
+        }
+        if image.get("mimeType").as_str() != Some("image/png") {
+            return Ok(None);
+        }
+        let view = image
+            .get("bufferView")
+            .as_usize()
+            .ok_or_else(|| invalid("A glTF image has neither uri nor bufferView"))?;
+        png_image::decode(self.buffer_view(view)?.0).map(Some)
+    }
+
+    // Bytes of a buffer view and its byte stride.
+    fn buffer_view(&self, index: usize) -> Result<(&[u8], Option<usize>)> {
+        let view = self.json.get("bufferViews").at(index);
+        let buffer = view
+            .get("buffer")
+            .as_usize()
+            .and_then(|buffer| self.buffers.get(buffer))
+            .ok_or_else(|| invalid(&format!("The glTF buffer view {} has no buffer", index)))?;
+        let offset = view.get("byteOffset").as_usize().unwrap_or(0);
+        let length = view.get("byteLength").as_usize().unwrap_or(0);
+        let data = offset
+            .checked_add(length)
+            .and_then(|end| buffer.get(offset..end))
+            .ok_or_else(|| invalid(&format!("The glTF buffer view {} is out of range", index)))?;
+        Ok((data, view.get("byteStride").as_usize()))
+    }
+
+    // Elements of an accessor as rows of `components` numbers. Normalized
+    // integers are mapped to [0, 1] or [-1, 1].
+    fn accessor(&self, index: usize) -> Result<(Vec<f64>, usize)> {
+        let accessor = self.json.get("accessors").at(index);
+        let fail = |message: &str| invalid(&format!("The glTF accessor {} {}", index, message));
+        let components = match accessor.get("type").as_str() {
+            Some("SCALAR") => 1,
+            Some("VEC2") => 2,
+            Some("VEC3") => 3,
+            Some("VEC4") | Some("MAT2") => 4,
+            Some("MAT3") => 9,
+            Some("MAT4") => 16,
+            _ => return Err(fail("has an unknown type")),
+        };
+        let component_type = accessor.get("componentType").as_usize().unwrap_or(0);
+        let normalized = accessor.get("normalized").as_bool().unwrap_or(false);
+        let count = accessor
+            .get("count")
+            .as_usize()
+            .ok_or_else(|| fail("has no count"))?;
+
+        let length = count
+            .checked_mul(components)
+            .ok_or_else(|| fail("is too large"))?;
+        let mut values = Vec::new();
+        if let Some(view) = accessor.get("bufferView").as_usize() {
+            let (data, stride) = self.buffer_view(view)?;
+            let offset = accessor.get("byteOffset").as_usize().unwrap_or(0);
+            let size = component_size(component_type)
+                .ok_or_else(|| fail("has an unknown component type"))?;
+            let stride = stride.unwrap_or(size * components);
+            // The count is checked against the view before anything is
+            // allocated, the last element has to end inside it.
+            let end = match count.checked_sub(1) {
+                Some(last) => last
+                    .checked_mul(stride)
+                    .and_then(|start| start.checked_add(offset))
+                    .and_then(|start| start.checked_add(size * components)),
+                None => Some(offset),
+            };
+            if !matches!(end, Some(end) if end <= data.len()) {
+                return Err(fail("is out of range"));
+            }
+            values.reserve_exact(length);
+            for element in 0..count {
+                for component in 0..components {
+                    let start = offset + element * stride + component * size;
+                    values.push(read_component(
+                        component_type,
+                        &data[start..start + size],
+                        normalized,
+                    ));
+                }
+            }
+        } else {
+            // Without a view every element starts out as zero, sparse
+            // accessors then replace some of them.
+            values
+                .try_reserve_exact(length)
+                .map_err(|_| fail("is too large"))?;
+            values.resize(length, 0.0);
+        }
+
+        // Sparse accessors replace some elements of the base values.
+        let sparse = accessor.get("sparse");
+        if let Some(replaced) = sparse.get("count").as_usize() {
+            let indices = self.sparse_part(sparse.get("indices"), replaced, component_type)?;
+            let replaced_values = replaced
+                .checked_mul(components)
+                .ok_or_else(|| fail("is too large"))?;
+            let replacements =
+                self.sparse_part(sparse.get("values"), replaced_values, component_type)?;
+            for (k, &element) in indices.iter().enumerate() {
+                let element = element as usize;
+                if element >= count {
+                    return Err(fail("replaces an element that does not exist"));
+                }
+                values[element * components..(element + 1) * components]
+                    .copy_from_slice(&replacements[k * components..(k + 1) * components]);
+            }
+        }
+        Ok((values, components))
+    }
+
+    // `count` indices or values of a sparse accessor, tightly packed in their
+    // view. Indices bring their own component type, values use the one of
+    // the accessor.
+    fn sparse_part(&self, part: &Json, count: usize, component_type: usize) -> Result<Vec<f64>> {
+        let view = part
+            .get("bufferView")
+            .as_usize()
+            .ok_or_else(|| invalid("A glTF sparse accessor has no bufferView"))?;
+        let (data, _) = self.buffer_view(view)?;
+        let offset = part.get("byteOffset").as_usize().unwrap_or(0);
+        let component_type = part
+            .get("componentType")
+            .as_usize()
+            .unwrap_or(component_type);
+        let size =
+            component_size(component_type).ok_or_else(|| invalid("Unknown glTF component type"))?;
+        (0..count)
+            .map(|i| {
+                let start = offset + i * size;
+                data.get(start..start + size)
+                    .map(|bytes| read_component(component_type, bytes, false))
+                    .ok_or_else(|| invalid("A glTF sparse accessor is out of range"))
+            })
+            .collect()
+    }
+
+    fn add_mesh(
+        &self,
+        index: usize,
+        to_world: Matrix<4, 4>,
+        scene: &mut Scene,
+        materials: &mut Materials,
+        options: &ImportOptions,
+    ) -> Result<()> {
+        let mesh = self.json.get("meshes").at(index);
+        for primitive in mesh.get("primitives").items() {
+            let attributes = primitive.get("attributes");
+            let attribute = |name: &str| -> Result<Option<(Vec<f64>, usize)>> {
+                attributes
+                    .get(name)
+                    .as_usize()
+                    .map(|i| self.accessor(i))
+                    .transpose()
+            };
+            let Some((position_values, 3)) = attribute("POSITION")? else {
+                return Err(invalid(&format!(
+                    "A primitive of glTF mesh {} has no VEC3 positions",
+                    index
+                )));
+            };
+            let count = position_values.len() / 3;
+            let mut positions: Vec<Point> = position_values
+                .chunks_exact(3)
+                .map(|p| Point::new(p[0], p[1], p[2]))
+                .collect();
+            let mut repairs = Repairs::default();
+            let mut normals: Vec<Normal> = match attribute("NORMAL")? {
+                Some((values, 3)) => values
+                    .chunks_exact(3)
+                    .map(|n| repairs.normalize(Vector::new(n[0], n[1], n[2])))
+                    .collect(),
+                _ => vec![],
+            };
+            // glTF puts the origin of texture coordinates at the top left,
+            // the textures here have v pointing up. Flipping v also flips
+            // the bitangent, and with it the tangent sign.
+            let mut uvs: Vec<(f64, f64)> = match attribute("TEXCOORD_0")? {
+                Some((values, 2)) => values.chunks_exact(2).map(|t| (t[0], 1.0 - t[1])).collect(),
+                _ => vec![],
+            };
+            let mut tangents: Vec<(Vector, f64)> = match attribute("TANGENT")? {
+                Some((values, 4)) if !normals.is_empty() && !uvs.is_empty() => values
+                    .chunks_exact(4)
+                    .map(|t| (Vector::new(t[0], t[1], t[2]), -t[3]))
+                    .collect(),
+                _ => vec![],
+            };
+            let mut colors: Vec<Vector> = match attribute("COLOR_0")? {
+                Some((values, components @ (3 | 4))) => values
+                    .chunks_exact(components)
+                    .map(|c| Vector::new(c[0], c[1], c[2]))
+                    .collect(),
+                _ => vec![],
+            };
+            // Every attribute needs one element per position.
+            for (name, length) in [
+                ("NORMAL", normals.len()),
+                ("TEXCOORD_0", uvs.len()),
+                ("TANGENT", tangents.len()),
+                ("COLOR_0", colors.len()),
+            ] {
+                if length != 0 && length != count {
+                    return Err(invalid(&format!(
+                        "A primitive of glTF mesh {} has {} {} elements for {} positions",
+                        index, length, name, count
+                    )));
+                }
+            }
+
+            let order: Vec<u32> = match primitive.get("indices").as_usize() {
+                Some(accessor) => self
+                    .accessor(accessor)?
+                    .0
+                    .iter()
+                    .map(|&i| i as u32)
+                    .collect(),
+                None => (0..count as u32).collect(),
+            };
+            if order.iter().any(|&i| i as usize >= count) {
+                return Err(invalid(&format!(
+                    "A primitive of glTF mesh {} uses a vertex that does not exist",
+                    index
+                )));
+            }
+            let mut indices: Vec<[u32; 3]> = match primitive.get("mode").as_usize().unwrap_or(4) {
+                4 => order.chunks_exact(3).map(|t| [t[0], t[1], t[2]]).collect(),
+                // Every other strip triangle is wound the other way round.
+                5 => (2..order.len())
+                    .map(|k| match k % 2 {
+                        0 => [order[k - 2], order[k - 1], order[k]],
+                        _ => [order[k - 1], order[k - 2], order[k]],
+                    })
+                    .collect(),
+                6 => (2..order.len())
+                    .map(|k| [order[0], order[k - 1], order[k]])
+                    .collect(),
+                mode => {
+                    warning(&format!(
+                        "Skipping a primitive of glTF mesh {}, mode {} is not triangles",
+                        index, mode
+                    ));
+                    continue;
+                }
+            };
+            if indices.is_empty() {
+                continue;
+            }
+
+            if let (true, Some(crease_angle)) = (normals.is_empty(), options.crease_angle) {
+                let (sources, smooth, renumbered) =
+                    crease_normals(&positions, &indices, crease_angle);
+                positions = sources.iter().map(|&i| positions[i]).collect();
+                if !uvs.is_empty() {
+                    uvs = sources.iter().map(|&i| uvs[i]).collect();
+                }
+                if !colors.is_empty() {
+                    colors = sources.iter().map(|&i| colors[i]).collect();
+                }
+                tangents = vec![];
+                normals = smooth;
+                indices = renumbered;
+            }
+
+            let material = primitive.get("material").as_usize();
+            let textured = material.is_some_and(|m| materials.textured.get(m) == Some(&true));
+            let material = if !colors.is_empty() && !textured {
+                *materials
+                    .vertex_color
+                    .get_or_insert_with(|| scene.add_material(Material::new(Texture::VertexColor)))
+            } else {
+                material
+                    .and_then(|m| materials.indices.get(m).copied())
+                    .unwrap_or(0)
+            };
+            let mut mesh = TriangleMesh::new(positions, normals, uvs, indices)
+                .with_colors(colors)
+                .with_repairs(repairs);
+            if !tangents.is_empty() {
+                mesh = mesh.with_tangents(tangents);
+            }
+            mesh.transform(Transformation::Matrix(to_world));
+            scene.add_object_with_material(Box::new(mesh), material);
+        }
+        Ok(())
+    }
+}
+
+fn component_size(component_type: usize) -> Option<usize> {
+    match component_type {
+        5120 | 5121 => Some(1),
+        5122 | 5123 => Some(2),
+        5125 | 5126 => Some(4),
+        _ => None,
+    }
+}
+
+fn read_component(component_type: usize, bytes: &[u8], normalized: bool) -> f64 {
+    let (value, scale) = match component_type {
+        5120 => (bytes[0] as i8 as f64, i8::MAX as f64),
+        5121 => (bytes[0] as f64, u8::MAX as f64),
+        5122 => (
+            i16::from_le_bytes([bytes[0], bytes[1]]) as f64,
+            i16::MAX as f64,
+        ),
+        5123 => (
+            u16::from_le_bytes([bytes[0], bytes[1]]) as f64,
+            u16::MAX as f64,
+        ),
+        5125 => (
+            u32::from_le_bytes(bytes.try_into().unwrap()) as f64,
+            u32::MAX as f64,
+        ),
+        _ => (f32::from_le_bytes(bytes.try_into().unwrap()) as f64, 1.0),
+    };
+    if normalized {
+        (value / scale).max(-1.0)
+    } else {
+        value
+    }
+}
+
+fn invalid(message: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, message.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Document with one buffer holding `floats` and one view over all of it.
+    fn document(accessors: &str, floats: &[f32]) -> Document {
+        let buffer: Vec<u8> = floats.iter().flat_map(|x| x.to_le_bytes()).collect();
+        let json = format!(
+            r#"{{"bufferViews": [{{"buffer": 0, "byteLength": {}}}], "accessors": {}}}"#,
+            buffer.len(),
+            accessors
+        );
+        Document {
+            json: Json::parse(&json).unwrap(),
+            buffers: vec![buffer],
+            directory: PathBuf::new(),
+        }
+    }
+
+    #[test]
+    fn accessors_read_their_elements() {
+        let document = document(
+            r#"[{"bufferView": 0, "byteOffset": 4, "componentType": 5126, "count": 2, "type": "VEC2"}]"#,
+            &[9.0, 1.0, 2.0, 3.0, 4.0],
+        );
+        let (values, components) = document.accessor(0).unwrap();
+        assert_eq!(components, 2);
+        assert_eq!(values, [1.0, 2.0, 3.0, 4.0]);
+    }
+
+    #[test]
+    fn accessor_counts_past_the_view_are_rejected_before_allocating() {
+        for count in ["3", "4611686018427387904", "18446744073709551615"] {
+            let document = document(
+                &format!(
+                    r#"[{{"bufferView": 0, "componentType": 5126, "count": {}, "type": "VEC3"}}]"#,
+                    count
+                ),
+                &[0.0; 6],
+            );
+            let Err(error) = document.accessor(0) else {
+                panic!("{} elements read from a view of 2", count);
+            };
+            assert_eq!(error.kind(), ErrorKind::InvalidData);
+        }
+    }
+
+    // Binary glTF file with a JSON chunk and a binary chunk.
+    fn glb(json: &str, binary: &[u8]) -> Vec<u8> {
+        let mut json = json.as_bytes().to_vec();
+        json.resize(json.len().div_ceil(4) * 4, b' ');
+        let mut binary = binary.to_vec();
+        binary.resize(binary.len().div_ceil(4) * 4, 0);
+        let mut bytes = GLB_MAGIC.to_vec();
+        bytes.extend(2u32.to_le_bytes());
+        bytes.extend((12 + 8 + json.len() as u32 + 8 + binary.len() as u32).to_le_bytes());
+        for (kind, chunk) in [(GLB_JSON_CHUNK, json), (GLB_BIN_CHUNK, binary)] {
+            bytes.extend((chunk.len() as u32).to_le_bytes());
+            bytes.extend(kind.to_le_bytes());
+            bytes.extend(chunk);
+        }
+        bytes
+    }
+
+    fn load(name: &str, bytes: &[u8]) -> Result<Scene> {
+        let path = std::env::temp_dir().join(format!("{}_{}.glb", name, std::process::id()));
+        std::fs::write(&path, bytes).unwrap();
+        let scene = GltfFile::new(path.clone()).load();
+        std::fs::remove_file(&path).unwrap();
+        scene
+    }
+
+    // A triangle moved by its parent node, with 16 bit indices after the
+    // positions.
+    const TRIANGLE: &str = r#"{
+        "asset": {"version": "2.0"},
+        "scene": 0,
+        "scenes": [{"nodes": [0]}],
+        "nodes": [{"translation": [0, 0, 5], "children": [1]}, {"mesh": 0}],
+        "meshes": [{"primitives": [{"attributes": {"POSITION": 0}, "indices": 1}]}],
+        "buffers": [{"byteLength": 42}],
+        "bufferViews": [
+            {"buffer": 0, "byteLength": 36},
+            {"buffer": 0, "byteOffset": 36, "byteLength": 6}
+        ],
+        "accessors": [
+            {"bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3"},
+            {"bufferView": 1, "componentType": 5123, "count": 3, "type": "SCALAR"}
+        ]
+    }"#;
+
+    fn triangle_data() -> Vec<u8> {
+        let positions = [0.0f32, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0];
+        let mut data: Vec<u8> = positions.iter().flat_map(|x| x.to_le_bytes()).collect();
+        for index in [0u16, 1, 2] {
+            data.extend(index.to_le_bytes());
+        }
+        data
+    }
+
+    #[test]
+    fn binary_files_load_with_node_transforms() {
+        let scene = load("triangle", &glb(TRIANGLE, &triangle_data())).unwrap();
+        assert_eq!(scene.objects().len(), 1);
+        let mesh = scene.objects()[0].mesh_at(0.0).unwrap();
+        assert_eq!(mesh.indices(), [[0, 1, 2]]);
+        let corner = mesh.positions()[1];
+        assert_eq!((corner.x, corner.y, corner.z), (1.0, 0.0, 5.0));
+    }
+
+    #[test]
+    fn broken_documents_are_rejected() {
+        let version_1 = TRIANGLE.replace("\"2.0\"", "\"1.0\"");
+        let own_ancestor = TRIANGLE.replace("{\"mesh\": 0}", "{\"mesh\": 0, \"children\": [0]}");
+        for json in [version_1, own_ancestor] {
+            let Err(error) = load("broken", &glb(&json, &triangle_data())) else {
+                panic!("{} loaded", json);
+            };
+            assert_eq!(error.kind(), ErrorKind::InvalidData);
+        }
+        // Cut inside the binary chunk.
+        let bytes = glb(TRIANGLE, &triangle_data());
+        assert!(load("short", &bytes[..bytes.len() - 8]).is_err());
+    }
+
+    #[test]
+    fn sparse_accessors_replace_elements_of_zeros() {
+        let document = document(
+            r#"[{"componentType": 5126, "count": 4, "type": "SCALAR", "sparse": {
+                "count": 2,
+                "indices": {"bufferView": 0, "componentType": 5125},
+                "values": {"bufferView": 0, "byteOffset": 8}
+            }}]"#,
+            // Indices 3 and 1 as u32, then their values.
+            &[f32::from_bits(3), f32::from_bits(1), 7.0, 9.0],
+        );
+        let (values, _) = document.accessor(0).unwrap();
+        assert_eq!(values, [0.0, 9.0, 0.0, 7.0]);
+    }
+
+    #[test]
+    fn base64_decodes_both_alphabets() {
+        assert_eq!(decode_base64("aGVsbG8=").unwrap(), b"hello");
+        assert_eq!(decode_base64("-_8").unwrap(), [0xfb, 0xff]);
+        assert!(decode_base64("aGV*").is_err());
+        let uri = load_uri("data:application/octet-stream;base64,AAEC", Path::new("")).unwrap();
+        assert_eq!(uri, [0, 1, 2]);
+    }
+}
@@ -0,0 +1,160 @@
+use std::io::{Error, ErrorKind, Result};
+use std::path::PathBuf;
+
+use crate::io::{Image, ImageInput};
+use crate::renderer::color::Color;
+
+// Radiance RGBE image (.hdr), flat or run length encoded.
+pub(crate) struct HDRImage {
+    file_path: PathBuf,
+}
+
+impl HDRImage {
+    pub(crate) fn new(file_path: PathBuf) -> HDRImage {
+        HDRImage { file_path }
+    }
+}
+
+impl ImageInput for HDRImage {
+    fn read(&self) -> Result<Image> {
+        let data = std::fs::read(&self.file_path)?;
+        let mut position = 0;
+        let mut next_line = || -> Result<String> {
+            let start = position;
+            while position < data.len() && data[position] != b'\n' {
+                position += 1;
+            }
+            if position >= data.len() {
+                return Err(invalid("unexpected end of HDR header"));
+            }
+            position += 1;
+            Ok(String::from_utf8_lossy(&data[start..position - 1]).into_owned())
+        };
+
+        let magic = next_line()?;
+        if !magic.starts_with("#?") {
+            return Err(invalid("missing HDR signature"));
+        }
+        // Header variables end with an empty line.
+        loop {
+            let line = next_line()?;
+            if line.is_empty() {
+                break;
+            }
+            if let Some(format) = line.strip_prefix("FORMAT=") {
+                if format != "32-bit_rle_rgbe" {
+                    return Err(invalid("unsupported HDR pixel format"));
+                }
+            }
+        }
+        // Only the standard orientation, rows top to bottom, is supported.
+        let resolution = next_line()?;
+        let fields: Vec<&str> = resolution.split_whitespace().collect();
+        let (height, width) = match fields.as_slice() {
+            ["-Y", height, "+X", width] => (
+                height
+                    .parse::<usize>()
+                    .map_err(|_| invalid("invalid HDR height"))?,
+                width
+                    .parse::<usize>()
+                    .map_err(|_| invalid("invalid HDR width"))?,
+            ),
+            _ => return Err(invalid("unsupported HDR orientation")),
+        };
+
+        let mut reader = ScanlineReader {
+            data: &data,
+            position,
+        };
+        let mut pixels = Vec::with_capacity(width * height);
+        let mut scanline = vec![[0u8; 4]; width];
+        for _ in 0..height {
+            reader.read_scanline(&mut scanline)?;
+            pixels.extend(scanline.iter().map(|&rgbe| rgbe_to_color(rgbe)));
+        }
+        Ok(Image {
+            width,
+            height,
+            pixels,
+        })
+    }
+}
+
+struct ScanlineReader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> ScanlineReader<'a> {
+    fn byte(&mut self) -> Result<u8> {
+        let byte = *self
+            .data
+            .get(self.position)
+            .ok_or_else(|| invalid("unexpected end of HDR pixel data"))?;
+        self.position += 1;
+        Ok(byte)
+    }
+
+    fn read_scanline(&mut self, scanline: &mut [[u8; 4]]) -> Result<()> {
+        let width = scanline.len();
+        let is_rle = (8..0x8000).contains(&width)
+            && self.data.get(self.position..self.position + 2) == Some(&[2, 2]);
+        if !is_rle {
+            for pixel in scanline.iter_mut() {
+                for channel in pixel.iter_mut() {
+                    *channel = self.byte()?;
+                }
+            }
+            return Ok(());
+        }
+
+        let header = [self.byte()?, self.byte()?, self.byte()?, self.byte()?];
+        if (header[2] as usize) << 8 | header[3] as usize != width {
+            return Err(invalid("HDR scanline width mismatch"));
+        }
+        // Every channel is stored separately as runs and literal spans.
+        for channel in 0..4 {
+            let mut x = 0;
+            while x < width {
+                let count = self.byte()? as usize;
+                if count > 128 {
+                    let count = count - 128;
+                    if x + count > width {
+                        return Err(invalid("HDR run overflows the scanline"));
+                    }
+                    let value = self.byte()?;
+                    for pixel in &mut scanline[x..x + count] {
+                        pixel[channel] = value;
+                    }
+                    x += count;
+                } else {
+                    if count == 0 || x + count > width {
+                        return Err(invalid("invalid HDR literal span"));
+                    }
+                    for pixel in &mut scanline[x..x + count] {
+                        pixel[channel] = self.byte()?;
+                    }
+                    x += count;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+// Mantissas share the exponent in the fourth byte.
+fn rgbe_to_color([r, g, b, e]: [u8; 4]) -> Color {
+    if e == 0 {
+        return Color::black();
+    }
+    let scale = 2f64.powi(e as i32 - 136);
+    Color::new(
+        (r as f64 + 0.5) * scale,
+        (g as f64 + 0.5) * scale,
+        (b as f64 + 0.5) * scale,
+    )
+}
+
+fn invalid(message: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, message)
+}
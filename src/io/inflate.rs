@@ -0,0 +1,313 @@
+use std::io::{Error, ErrorKind, Result};
+
+// Decoder for zlib streams (RFC 1950) wrapping DEFLATE data (RFC 1951), as
+// used by PNG image data.
+
+pub(super) const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+pub(super) const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+pub(super) const DISTANCE_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+pub(super) const DISTANCE_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+// Order in which code length code lengths are stored in dynamic blocks.
+const CODE_LENGTH_ORDER: [usize; 19] = [
+    16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15,
+];
+
+pub(crate) fn zlib_decompress(data: &[u8]) -> Result<Vec<u8>> {
+    if data.len() < 2 {
+        return Err(invalid("zlib stream is too short"));
+    }
+    let (cmf, flg) = (data[0], data[1]);
+    if cmf & 0x0f != 8 || (u16::from(cmf) << 8 | u16::from(flg)) % 31 != 0 {
+        return Err(invalid("invalid zlib header"));
+    }
+    if flg & 0x20 != 0 {
+        return Err(invalid("zlib preset dictionaries are not supported"));
+    }
+    inflate(&data[2..])
+}
+
+pub(crate) fn inflate(data: &[u8]) -> Result<Vec<u8>> {
+    let mut reader = BitReader::new(data);
+    let mut output = Vec::with_capacity(data.len() * 4);
+    loop {
+        let last = reader.bits(1)? == 1;
+        match reader.bits(2)? {
+            0 => {
+                reader.align_to_byte();
+                let length = reader.bits(16)? as usize;
+                let complement = reader.bits(16)? as usize;
+                if length != !complement & 0xffff {
+                    return Err(invalid("corrupt stored block length"));
+                }
+                output.extend_from_slice(reader.bytes(length)?);
+            }
+            1 => {
+                let (literals, distances) = fixed_tables();
+                inflate_block(&mut reader, &mut output, &literals, &distances)?;
+            }
+            2 => {
+                let (literals, distances) = dynamic_tables(&mut reader)?;
+                inflate_block(&mut reader, &mut output, &literals, &distances)?;
+            }
+            _ => return Err(invalid("invalid deflate block type")),
+        }
+        if last {
+            return Ok(output);
+        }
+    }
+}
+
+fn inflate_block(
+    reader: &mut BitReader,
+    output: &mut Vec<u8>,
+    literals: &Huffman,
+    distances: &Huffman,
+) -> Result<()> {
+    loop {
+        let symbol = literals.decode(reader)?;
+        match symbol {
+            0..=255 => output.push(symbol as u8),
+            256 => return Ok(()),
+            257..=285 => {
+                let index = (symbol - 257) as usize;
+                let length =
+                    LENGTH_BASE[index] as usize + reader.bits(LENGTH_EXTRA[index] as u32)? as usize;
+                let index = distances.decode(reader)? as usize;
+                if index >= DISTANCE_BASE.len() {
+                    return Err(invalid("invalid deflate distance code"));
+                }
+                let distance = DISTANCE_BASE[index] as usize
+                    + reader.bits(DISTANCE_EXTRA[index] as u32)? as usize;
+                if distance > output.len() {
+                    return Err(invalid("deflate distance points before the output"));
+                }
+                // Copies may overlap their own output, so go byte by byte.
+                let start = output.len() - distance;
+                for i in 0..length {
+                    output.push(output[start + i]);
+                }
+            }
+            _ => return Err(invalid("invalid deflate literal code")),
+        }
+    }
+}
+
+fn fixed_tables() -> (Huffman, Huffman) {
+    let mut lengths = [0u8; 288];
+    lengths[..144].fill(8);
+    lengths[144..256].fill(9);
+    lengths[256..280].fill(7);
+    lengths[280..].fill(8);
+    (Huffman::new(&lengths), Huffman::new(&[5; 30]))
+}
+
+fn dynamic_tables(reader: &mut BitReader) -> Result<(Huffman, Huffman)> {
+    let literal_count = reader.bits(5)? as usize + 257;
+    let distance_count = reader.bits(5)? as usize + 1;
+    let code_length_count = reader.bits(4)? as usize + 4;
+
+    let mut code_lengths = [0u8; 19];
+    for &index in &CODE_LENGTH_ORDER[..code_length_count] {
+        code_lengths[index] = reader.bits(3)? as u8;
+    }
+    let code_length_table = Huffman::new(&code_lengths);
+
+    let mut lengths = vec![0u8; literal_count + distance_count];
+    let mut i = 0;
+    while i < lengths.len() {
+        let (value, repeat) = match code_length_table.decode(reader)? {
+            symbol @ 0..=15 => (symbol as u8, 1),
+            16 => {
+                if i == 0 {
+                    return Err(invalid("deflate repeat code without previous length"));
+                }
+                (lengths[i - 1], 3 + reader.bits(2)? as usize)
+            }
+            17 => (0, 3 + reader.bits(3)? as usize),
+            18 => (0, 11 + reader.bits(7)? as usize),
+            _ => return Err(invalid("invalid deflate code length symbol")),
+        };
+        if i + repeat > lengths.len() {
+            return Err(invalid("deflate code lengths overflow"));
+        }
+        lengths[i..i + repeat].fill(value);
+        i += repeat;
+    }
+
+    Ok((
+        Huffman::new(&lengths[..literal_count]),
+        Huffman::new(&lengths[literal_count..]),
+    ))
+}
+
+// Canonical Huffman code decoded one bit at a time.
+struct Huffman {
+    // Number of codes of each bit length.
+    counts: [u16; 16],
+    // Symbols sorted by code.
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> Huffman {
+        let mut counts = [0u16; 16];
+        for &length in lengths {
+            counts[length as usize] += 1;
+        }
+        counts[0] = 0;
+
+        let mut offsets = [0u16; 16];
+        for length in 1..16 {
+            offsets[length] = offsets[length - 1] + counts[length - 1];
+        }
+        let mut symbols = vec![0u16; lengths.len()];
+        for (symbol, &length) in lengths.iter().enumerate() {
+            if length != 0 {
+                symbols[offsets[length as usize] as usize] = symbol as u16;
+                offsets[length as usize] += 1;
+            }
+        }
+        Huffman { counts, symbols }
+    }
+
+    fn decode(&self, reader: &mut BitReader) -> Result<u16> {
+        let mut code: i32 = 0;
+        let mut first: i32 = 0;
+        let mut index: i32 = 0;
+        for length in 1..16 {
+            code |= reader.bits(1)? as i32;
+            let count = self.counts[length] as i32;
+            if code - first < count {
+                return Ok(self.symbols[(index + code - first) as usize]);
+            }
+            index += count;
+            first += count;
+            first <<= 1;
+            code <<= 1;
+        }
+        Err(invalid("invalid deflate huffman code"))
+    }
+}
+
+struct BitReader<'a> {
+    data: &'a [u8],
+    position: usize,
+    bit_buffer: u32,
+    bit_count: u32,
+}
+
+impl<'a> BitReader<'a> {
+    fn new(data: &'a [u8]) -> BitReader<'a> {
+        BitReader {
+            data,
+            position: 0,
+            bit_buffer: 0,
+            bit_count: 0,
+        }
+    }
+
+    // Reads `count` bits, least significant bit first.
+    fn bits(&mut self, count: u32) -> Result<u32> {
+        while self.bit_count < count {
+            let byte = *self
+                .data
+                .get(self.position)
+                .ok_or_else(|| invalid("unexpected end of deflate stream"))?;
+            self.position += 1;
+            self.bit_buffer |= (byte as u32) << self.bit_count;
+            self.bit_count += 8;
+        }
+        let value = self.bit_buffer & ((1u64 << count) - 1) as u32;
+        self.bit_buffer >>= count;
+        self.bit_count -= count;
+        Ok(value)
+    }
+
+    fn align_to_byte(&mut self) {
+        self.bit_buffer = 0;
+        self.bit_count = 0;
+    }
+
+    fn bytes(&mut self, count: usize) -> Result<&'a [u8]> {
+        let end = self.position + count;
+        if end > self.data.len() {
+            return Err(invalid("unexpected end of deflate stream"));
+        }
+        let bytes = &self.data[self.position..end];
+        self.position = end;
+        Ok(bytes)
+    }
+}
+
+fn invalid(message: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 300 bytes drawn from a skewed alphabet, so zlib picks dynamic codes.
+    fn skewed_text() -> Vec<u8> {
+        let alphabet = b"aaaaaaaabbbbccd e";
+        let mut state: u64 = 1;
+        (0..300)
+            .map(|_| {
+                state = (state * 1103515245 + 12345) % (1 << 31);
+                alphabet[(state >> 16) as usize % alphabet.len()]
+            })
+            .collect()
+    }
+
+    // `skewed_text` as compressed by zlib at level 9.
+    const DYNAMIC_STREAM: [u8; 133] = [
+        0x78, 0xda, 0x2d, 0x90, 0x81, 0x15, 0x80, 0x40, 0x08, 0x42, 0x57, 0x61, 0x35, 0x40, 0xf7,
+        0x5f, 0x21, 0xf0, 0xaa, 0xde, 0xcb, 0xe4, 0x73, 0x68, 0x32, 0xa8, 0xbb, 0x4d, 0x52, 0x3b,
+        0x14, 0x38, 0x92, 0xd0, 0x4a, 0x69, 0x4e, 0xc4, 0x85, 0x48, 0x4f, 0xb9, 0xe1, 0x5d, 0x55,
+        0x5c, 0xcb, 0x5c, 0xb1, 0xd1, 0x55, 0xd2, 0xf0, 0xc9, 0x7d, 0xa4, 0x27, 0x47, 0x31, 0xe1,
+        0x73, 0x1a, 0x9b, 0x24, 0xff, 0x87, 0x34, 0x36, 0x79, 0x21, 0xe5, 0x0d, 0x5c, 0x5f, 0xdb,
+        0x61, 0x26, 0x49, 0x7a, 0x9f, 0xbe, 0xb1, 0xaa, 0x21, 0x51, 0x69, 0x0e, 0xac, 0xe9, 0x71,
+        0x05, 0xe2, 0xdb, 0xd8, 0x07, 0x9d, 0xe9, 0xad, 0xf1, 0xe2, 0x53, 0x5e, 0xa7, 0x8c, 0xba,
+        0x53, 0x47, 0x03, 0xf1, 0x42, 0x66, 0x0f, 0xdd, 0x23, 0xf3, 0x0a, 0xc1, 0x5b, 0xa9, 0xbb,
+        0x9c, 0xf9, 0xfd, 0x91, 0x66, 0xec, 0x9b, 0xf6, 0x03, 0xd0, 0xc3, 0x6e, 0xe9,
+    ];
+
+    #[test]
+    fn stored_blocks_are_copied() {
+        let stream = [
+            0x78, 0x01, 0x01, 0x14, 0x00, 0xeb, 0xff, b'h', b'e', b'l', b'l', b'o', b',', b' ',
+            b'h', b'e', b'l', b'l', b'o', b',', b' ', b'h', b'e', b'l', b'l', b'o', b'!', 0x4b,
+            0x1e, 0x06, 0xf6,
+        ];
+        assert_eq!(zlib_decompress(&stream).unwrap(), b"hello, hello, hello!");
+    }
+
+    #[test]
+    fn dynamic_blocks_decode() {
+        assert_eq!(zlib_decompress(&DYNAMIC_STREAM).unwrap(), skewed_text());
+    }
+
+    #[test]
+    fn corrupt_streams_are_errors() {
+        // Wrong compression method, then a stream cut off mid-block.
+        let mut header = DYNAMIC_STREAM;
+        header[0] = 0x79;
+        assert!(zlib_decompress(&header).is_err());
+        assert!(zlib_decompress(&DYNAMIC_STREAM[..40]).is_err());
+        // A stored block whose length does not match its complement.
+        assert!(inflate(&[0x01, 0x05, 0x00, 0x00, 0x00]).is_err());
+        // A fixed block that copies from before the start of the output.
+        assert!(inflate(&[0x03, 0x02, 0x00]).is_err());
+    }
+}
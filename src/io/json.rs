@@ -0,0 +1,345 @@
+use std::io::{Error, ErrorKind, Result};
+
+// Parsed JSON document. Objects keep their members in file order.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Json {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+// Shared by missing members and values of the wrong type, so lookups can be
+// chained without checking every step.
+static NULL: Json = Json::Null;
+
+impl Json {
+    pub(crate) fn parse(text: &str) -> Result<Json> {
+        let mut parser = Parser {
+            bytes: text.as_bytes(),
+            position: 0,
+        };
+        let value = parser.value()?;
+        parser.skip_whitespace();
+        if parser.position != parser.bytes.len() {
+            return Err(parser.error("unexpected text after the JSON value"));
+        }
+        Ok(value)
+    }
+
+    // Member `key` of an object, null when missing.
+    pub(crate) fn get(&self, key: &str) -> &Json {
+        match self {
+            Json::Object(members) => members
+                .iter()
+                .find(|(name, _)| name == key)
+                .map_or(&NULL, |(_, value)| value),
+            _ => &NULL,
+        }
+    }
+
+    // Item `index` of an array, null when missing.
+    pub(crate) fn at(&self, index: usize) -> &Json {
+        match self {
+            Json::Array(items) => items.get(index).unwrap_or(&NULL),
+            _ => &NULL,
+        }
+    }
+
+    pub(crate) fn is_null(&self) -> bool {
+        *self == Json::Null
+    }
+
+    pub(crate) fn as_f64(&self) -> Option<f64> {
+        match self {
+            Json::Number(number) => Some(*number),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn as_usize(&self) -> Option<usize> {
+        self.as_f64()
+            .filter(|number| *number >= 0.0 && number.fract() == 0.0)
+            .map(|number| number as usize)
+    }
+
+    pub(crate) fn as_bool(&self) -> Option<bool> {
+        match self {
+            Json::Bool(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn as_str(&self) -> Option<&str> {
+        match self {
+            Json::String(text) => Some(text),
+            _ => None,
+        }
+    }
+
+    // Items of an array, nothing for other values.
+    pub(crate) fn items(&self) -> &[Json] {
+        match self {
+            Json::Array(items) => items,
+            _ => &[],
+        }
+    }
+
+    // Numbers of an array of numbers.
+    pub(crate) fn as_numbers(&self) -> Option<Vec<f64>> {
+        match self {
+            Json::Array(items) => items.iter().map(Json::as_f64).collect(),
+            _ => None,
+        }
+    }
+}
+
+struct Parser<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl Parser<'_> {
+    fn value(&mut self) -> Result<Json> {
+        self.skip_whitespace();
+        match self.bytes.get(self.position) {
+            Some(b'{') => self.object(),
+            Some(b'[') => self.array(),
+            Some(b'"') => Ok(Json::String(self.string()?)),
+            Some(b't') => self.literal("true", Json::Bool(true)),
+            Some(b'f') => self.literal("false", Json::Bool(false)),
+            Some(b'n') => self.literal("null", Json::Null),
+            Some(b'-' | b'0'..=b'9') => self.number(),
+            Some(_) => Err(self.error("unexpected character")),
+            None => Err(self.error("unexpected end of the JSON text")),
+        }
+    }
+
+    fn object(&mut self) -> Result<Json> {
+        self.position += 1;
+        let mut members = vec![];
+        self.skip_whitespace();
+        if self.eat(b'}') {
+            return Ok(Json::Object(members));
+        }
+        loop {
+            self.skip_whitespace();
+            if self.bytes.get(self.position) != Some(&b'"') {
+                return Err(self.error("expected a member name"));
+            }
+            let name = self.string()?;
+            self.skip_whitespace();
+            if !self.eat(b':') {
+                return Err(self.error("expected ':'"));
+            }
+            members.push((name, self.value()?));
+            self.skip_whitespace();
+            if self.eat(b'}') {
+                return Ok(Json::Object(members));
+            }
+            if !self.eat(b',') {
+                return Err(self.error("expected ',' or '}'"));
+            }
+        }
+    }
+
+    fn array(&mut self) -> Result<Json> {
+        self.position += 1;
+        let mut items = vec![];
+        self.skip_whitespace();
+        if self.eat(b']') {
+            return Ok(Json::Array(items));
+        }
+        loop {
+            items.push(self.value()?);
+            self.skip_whitespace();
+            if self.eat(b']') {
+                return Ok(Json::Array(items));
+            }
+            if !self.eat(b',') {
+                return Err(self.error("expected ',' or ']'"));
+            }
+        }
+    }
+
+    fn string(&mut self) -> Result<String> {
+        self.position += 1;
+        let mut text = String::new();
+        loop {
+            let start = self.position;
+            while !matches!(self.bytes.get(self.position), Some(b'"' | b'\\') | None) {
+                self.position += 1;
+            }
+            // The input is a `str` and quotes and backslashes are ASCII, so
+            // the run ends on a character boundary.
+            text.push_str(std::str::from_utf8(&self.bytes[start..self.position]).unwrap());
+            match self.bytes.get(self.position) {
+                Some(b'"') => {
+                    self.position += 1;
+                    return Ok(text);
+                }
+                Some(b'\\') => {
+                    let escaped = *self
+                        .bytes
+                        .get(self.position + 1)
+                        .ok_or_else(|| self.error("unterminated string"))?;
+                    self.position += 2;
+                    match escaped {
+                        b'"' => text.push('"'),
+                        b'\\' => text.push('\\'),
+                        b'/' => text.push('/'),
+                        b'b' => text.push('\u{8}'),
+                        b'f' => text.push('\u{c}'),
+                        b'n' => text.push('\n'),
+                        b'r' => text.push('\r'),
+                        b't' => text.push('\t'),
+                        b'u' => text.push(self.unicode_escape()?),
+                        _ => return Err(self.error("unknown escape sequence")),
+                    }
+                }
+                _ => return Err(self.error("unterminated string")),
+            }
+        }
+    }
+
+    // The four hex digits after `\u`, combining surrogate pairs.
+    fn unicode_escape(&mut self) -> Result<char> {
+        let high = self.hex4()?;
+        let code = if (0xd800..0xdc00).contains(&high) {
+            if self.bytes.get(self.position..self.position + 2) != Some(b"\\u") {
+                return Err(self.error("unpaired surrogate"));
+            }
+            self.position += 2;
+            let low = self.hex4()?;
+            0x10000 + ((high - 0xd800) << 10) + (low.wrapping_sub(0xdc00) & 0x3ff)
+        } else {
+            high
+        };
+        char::from_u32(code).ok_or_else(|| self.error("invalid unicode escape"))
+    }
+
+    fn hex4(&mut self) -> Result<u32> {
+        let digits = self
+            .bytes
+            .get(self.position..self.position + 4)
+            .and_then(|digits| std::str::from_utf8(digits).ok())
+            .and_then(|digits| u32::from_str_radix(digits, 16).ok())
+            .ok_or_else(|| self.error("invalid unicode escape"))?;
+        self.position += 4;
+        Ok(digits)
+    }
+
+    fn number(&mut self) -> Result<Json> {
+        let start = self.position;
+        while matches!(
+            self.bytes.get(self.position),
+            Some(b'-' | b'+' | b'.' | b'e' | b'E' | b'0'..=b'9')
+        ) {
+            self.position += 1;
+        }
+        std::str::from_utf8(&self.bytes[start..self.position])
+            .ok()
+            .and_then(|number| number.parse().ok())
+            .map(Json::Number)
+            .ok_or_else(|| self.error("invalid number"))
+    }
+
+    fn literal(&mut self, word: &str, value: Json) -> Result<Json> {
+        if self.bytes[self.position..].starts_with(word.as_bytes()) {
+            self.position += word.len();
+            Ok(value)
+        } else {
+            Err(self.error("unexpected character"))
+        }
+    }
+
+    fn eat(&mut self, byte: u8) -> bool {
+        let found = self.bytes.get(self.position) == Some(&byte);
+        if found {
+            self.position += 1;
+        }
+        found
+    }
+
+    fn skip_whitespace(&mut self) {
+        while matches!(
+            self.bytes.get(self.position),
+            Some(b' ' | b'\t' | b'\n' | b'\r')
+        ) {
+            self.position += 1;
+        }
+    }
+
+    fn error(&self, message: &str) -> Error {
+        Error::new(
+            ErrorKind::InvalidData,
+            format!("{} at byte {} of the JSON text", message, self.position),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nested_values_parse_in_file_order() {
+        let json = Json::parse(
+            r#" {"name": "box", "size": [1, 2.5, -3e2], "open": false,
+                "parts": {"lid": null, "count": 4}} "#,
+        )
+        .unwrap();
+        assert_eq!(json.get("name").as_str(), Some("box"));
+        assert_eq!(json.get("size").as_numbers(), Some(vec![1.0, 2.5, -300.0]));
+        assert_eq!(json.get("open").as_bool(), Some(false));
+        assert!(json.get("parts").get("lid").is_null());
+        assert_eq!(json.get("parts").get("count").as_usize(), Some(4));
+        let Json::Object(members) = &json else {
+            panic!("not an object");
+        };
+        let names: Vec<&str> = members.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names, ["name", "size", "open", "parts"]);
+    }
+
+    #[test]
+    fn missing_members_and_wrong_types_read_as_null() {
+        let json = Json::parse(r#"{"list": [1, "two"], "half": 0.5, "below": -1}"#).unwrap();
+        assert!(json.get("missing").get("deeper").at(3).is_null());
+        assert!(json.get("list").at(2).is_null());
+        assert_eq!(json.get("list").as_numbers(), None);
+        assert_eq!(json.get("list").items().len(), 2);
+        assert_eq!(json.get("half").as_usize(), None);
+        assert_eq!(json.get("below").as_usize(), None);
+        assert_eq!(json.get("half").as_str(), None);
+    }
+
+    #[test]
+    fn escapes_decode() {
+        let json = Json::parse(r#""tab\t quote\" slash\/ \u00e9 \ud83d\ude00""#).unwrap();
+        assert_eq!(json.as_str(), Some("tab\t quote\" slash/ \u{e9} \u{1f600}"));
+    }
+
+    #[test]
+    fn malformed_text_is_rejected() {
+        for text in [
+            "",
+            "{",
+            r#"{"a" 1}"#,
+            "[1, 2",
+            "[1 2]",
+            r#""open"#,
+            r#""\q""#,
+            r#""\ud83d""#,
+            "tru",
+            "1.2.3",
+            "{} {}",
+        ] {
+            let Err(error) = Json::parse(text) else {
+                panic!("{:?} parsed", text);
+            };
+            assert_eq!(error.kind(), ErrorKind::InvalidData, "{:?}", text);
+        }
+    }
+}
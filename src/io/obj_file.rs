@@ -1,25 +1,51 @@
 use std::{
-    io::{BufRead, Result},
-    path::PathBuf,
+    collections::HashMap,
+    fs::File,
+    io::{BufRead, BufWriter, Error, ErrorKind, Result, Write},
+    path::{Path, PathBuf},
 };
 
 use crate::{
-    geometry::{normal::Normal, point::Point, triangle::Triangle, vector::Vector},
-    renderer::scene::Scene,
+    geometry::{mesh_check::Repairs, point::Point, triangle_mesh::TriangleMesh, vector::Vector},
+    renderer::{
+        color::Color,
+        material::{BumpMap, Material},
+        procedural::{Checker, Marble, Noise, NoiseKind, Wood},
+        scene::Scene,
+        texture::{ImageTexture, Texture, WrapMode},
+    },
 };
 
-use super::Input;
+use super::{scene_meshes, status, Export, ImportOptions, Input};
 
 pub(crate) struct ObjectFile {
     path: PathBuf,
+    options: ImportOptions,
 }
 
 impl ObjectFile {
     pub(crate) fn new(path: PathBuf) -> ObjectFile {
-        ObjectFile { path }
+        ObjectFile {
+            path,
+            options: ImportOptions::default(),
+        }
+    }
+
+    pub(crate) fn with_options(mut self, options: ImportOptions) -> ObjectFile {
+        self.options = options;
+        self
     }
 }
 
+// Position, texture coordinate and normal indices of one face corner.
+type Corner = (usize, Option<usize>, Option<usize>);
+
+// Faces sharing one material, stored as indices into the corner list.
+struct Group {
+    material: Option<String>,
+    triangles: Vec<[u32; 3]>,
+}
+
 impl Input for ObjectFile {
     fn load(&self) -> Result<Scene> {
         let file = std::fs::File::open(&self.path)?;
@@ -27,90 +53,455 @@ impl Input for ObjectFile {
         let mut scene = Scene::new();
         let mut points = vec![];
         let mut normals = vec![];
-        let mut input_vector = vec![];
+        let mut uvs = vec![];
+        let mut materials: HashMap<String, usize> = HashMap::new();
+
+        // OBJ indexes every attribute separately, the mesh shares one index
+        // for all of them, so each distinct corner becomes one mesh vertex.
+        let mut vertices: HashMap<Corner, u32> = HashMap::new();
+        let mut corners: Vec<Corner> = vec![];
+        let mut groups = vec![Group {
+            material: None,
+            triangles: vec![],
+        }];
+        let mut face = vec![];
 
-        println!("Loading data from obj file...");
+        status("Loading data from obj file...");
         for (i, l) in reader.lines().enumerate() {
             let l = l?;
             let mut iterator = l.split_whitespace();
 
             match iterator.next() {
                 Some("v") => {
-                    let x = iterator.next().unwrap().parse::<f64>().unwrap();
-                    let y = iterator.next().unwrap().parse::<f64>().unwrap();
-                    let z = iterator.next().unwrap().parse::<f64>().unwrap();
+                    let [x, y, z] = parse_numbers(iterator, 3)
+                        .and_then(|numbers| numbers.try_into().ok())
+                        .ok_or_else(|| invalid("A vertex needs 3 coordinates", i + 1))?;
                     points.push(Point::new(x, y, z));
                 }
                 Some("vn") => {
-                    let x = iterator.next().unwrap().parse::<f64>().unwrap();
-                    let y = iterator.next().unwrap().parse::<f64>().unwrap();
-                    let z = iterator.next().unwrap().parse::<f64>().unwrap();
-                    normals.push(Vector::new(x, y, z).normalize());
+                    let [x, y, z] = parse_numbers(iterator, 3)
+                        .and_then(|numbers| numbers.try_into().ok())
+                        .ok_or_else(|| invalid("A normal needs 3 coordinates", i + 1))?;
+                    normals.push(Vector::new(x, y, z));
                 }
-                Some("f") => {
-                    if points.len() < 3 {
-                        panic!("Not enough points to make a triangle");
+                Some("vt") => {
+                    let uv = parse_numbers(iterator, 2)
+                        .filter(|numbers| !numbers.is_empty())
+                        .ok_or_else(|| invalid("Incorrect texture coordinate", i + 1))?;
+                    uvs.push((uv[0], uv.get(1).copied().unwrap_or(0.0)));
+                }
+                Some("mtllib") => {
+                    for library in iterator {
+                        let path = self.path.with_file_name(library);
+                        load_materials(&path, &mut scene, &mut materials)?;
                     }
-                    input_vector.clear();
-                    for point in iterator {
-                        if !point.contains('/') {
-                            let point_index = point.parse::<usize>().unwrap();
-                            input_vector.push((points[point_index - 1], None));
-                        } else {
-                            let (p1, n1) = process_point(point);
-                            let data = if let Some(n2) = n1 {
-                                (points[p1 - 1], Some(normals[n2 - 1]))
-                            } else {
-                                (points[p1 - 1], None)
-                            };
-                            input_vector.push(data);
+                }
+                Some("usemtl") => {
+                    let name = iterator.next().map(str::to_string);
+                    match groups.iter().position(|group| group.material == name) {
+                        Some(index) => {
+                            let group = groups.remove(index);
+                            groups.push(group);
                         }
+                        None => groups.push(Group {
+                            material: name,
+                            triangles: vec![],
+                        }),
+                    }
+                }
+                Some("f") => {
+                    face.clear();
+                    for corner in iterator {
+                        let corner = process_corner(corner, points.len(), uvs.len(), normals.len())
+                            .ok_or_else(|| {
+                                invalid(&format!("Incorrect face corner {}", corner), i + 1)
+                            })?;
+                        let index = *vertices.entry(corner).or_insert_with(|| {
+                            corners.push(corner);
+                            (corners.len() - 1) as u32
+                        });
+                        face.push(index);
                     }
 
-                    match input_vector.len() {
-                        3 => {
-                            let triangle = get_triangle(&input_vector[..3]);
-                            scene.add_object(Box::new(triangle));
-                        }
-                        4 => {
-                            let triangle = get_triangle(&input_vector[..3]);
-                            scene.add_object(Box::new(triangle));
-                            input_vector.remove(1);
-                            let triangle = get_triangle(&input_vector[..3]);
-                            scene.add_object(Box::new(triangle));
-                        }
-                        _ => {
-                            panic!("Currently only triangles and squares are supported, but received {} points at line {}", input_vector.len(), i + 1);
-                        }
+                    if face.len() < 3 {
+                        return Err(invalid("A face needs at least 3 points", i + 1));
+                    }
+                    // Convex polygons are split into a triangle fan.
+                    let triangles = &mut groups.last_mut().unwrap().triangles;
+                    for k in 1..face.len() - 1 {
+                        triangles.push([face[0], face[k], face[k + 1]]);
                     }
                 }
                 _ => {}
             }
         }
 
+        if corners.iter().any(|&(p, t, n)| {
+            p >= points.len()
+                || t.is_some_and(|t| t >= uvs.len())
+                || n.is_some_and(|n| n >= normals.len())
+        }) {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                "An OBJ face refers to a vertex that does not exist",
+            ));
+        }
+        for group in groups {
+            if group.triangles.is_empty() {
+                continue;
+            }
+            let mesh = build_mesh(&group.triangles, &corners, &points, &normals, &uvs);
+            let material = group
+                .material
+                .and_then(|name| materials.get(&name).copied())
+                .unwrap_or(0);
+            scene.add_object_with_material(Box::new(mesh), material);
+        }
+        self.options.apply(&mut scene);
+
         return Ok(scene);
 
-        fn process_point(line: &str) -> (usize, Option<usize>) {
-            let mut iter = line.split('/');
-            (
-                iter.next().unwrap().parse::<usize>().unwrap(),
-                iter.nth(1).map(|i| i.parse::<usize>().unwrap()),
+        // Parses `v`, `v/vt`, `v//vn` or `v/vt/vn` into zero based indices.
+        // Negative indices are relative to the end of the attribute list.
+        // Indices outside the lists wrap around and fail the range check.
+        fn process_corner(
+            corner: &str,
+            points: usize,
+            uvs: usize,
+            normals: usize,
+        ) -> Option<Corner> {
+            let mut iter = corner.split('/');
+            let resolve = |index: &str, count: usize| -> Option<Option<usize>> {
+                if index.is_empty() {
+                    return Some(None);
+                }
+                let index = index.parse::<i64>().ok()?;
+                if index < 0 {
+                    Some(Some((count as i64 + index) as usize))
+                } else {
+                    Some(Some((index - 1) as usize))
+                }
+            };
+            let point = resolve(iter.next()?, points)??;
+            let uv = iter.next().map_or(Some(None), |t| resolve(t, uvs))?;
+            let normal = iter.next().map_or(Some(None), |n| resolve(n, normals))?;
+            if iter.next().is_some() {
+                return None;
+            }
+            Some((point, uv, normal))
+        }
+
+        // Parses the remaining words of a line, at most `count` numbers.
+        fn parse_numbers<'a>(
+            words: impl Iterator<Item = &'a str>,
+            count: usize,
+        ) -> Option<Vec<f64>> {
+            let numbers: Vec<f64> = words.map(|word| word.parse().ok()).collect::<Option<_>>()?;
+            // A trailing `w` weight is allowed and ignored.
+            (numbers.len() <= count + 1).then(|| numbers.into_iter().take(count).collect())
+        }
+
+        fn invalid(message: &str, line: usize) -> Error {
+            Error::new(
+                ErrorKind::InvalidData,
+                format!("{} at line {} of the OBJ file", message, line),
             )
         }
+    }
+}
 
-        fn get_triangle(data: &[(Point, Option<Normal>)]) -> Triangle {
-            if data[0].1.is_some() {
-                Triangle::with_normals(
-                    data[0].0,
-                    data[0].1.unwrap(),
-                    data[1].0,
-                    data[1].1.unwrap(),
-                    data[2].0,
-                    data[2].1.unwrap(),
-                )
-            } else {
-                Triangle::new(data[0].0, data[1].0, data[2].0)
+// Builds a mesh from the corners used by `triangles`, renumbering them so
+// the mesh only stores its own vertices. Normals are scaled to unit length
+// here, so only the ones the mesh uses are counted as repaired.
+fn build_mesh(
+    triangles: &[[u32; 3]],
+    corners: &[Corner],
+    points: &[Point],
+    normals: &[Vector],
+    uvs: &[(f64, f64)],
+) -> TriangleMesh {
+    let mut remap: HashMap<u32, u32> = HashMap::new();
+    let mut used: Vec<Corner> = vec![];
+    let indices = triangles
+        .iter()
+        .map(|triangle| {
+            triangle.map(|corner| {
+                *remap.entry(corner).or_insert_with(|| {
+                    used.push(corners[corner as usize]);
+                    (used.len() - 1) as u32
+                })
+            })
+        })
+        .collect();
+
+    let positions = used.iter().map(|&(p, _, _)| points[p]).collect();
+    // Attributes are only kept when every vertex has them.
+    let mut repairs = Repairs::default();
+    let mesh_normals = if used.iter().all(|&(_, _, n)| n.is_some()) {
+        used.iter()
+            .map(|&(_, _, n)| repairs.normalize(normals[n.unwrap()]))
+            .collect()
+    } else {
+        vec![]
+    };
+    let mesh_uvs = if used.iter().all(|&(_, t, _)| t.is_some()) {
+        used.iter().map(|&(_, t, _)| uvs[t.unwrap()]).collect()
+    } else {
+        vec![]
+    };
+    TriangleMesh::new(positions, mesh_normals, mesh_uvs, indices).with_repairs(repairs)
+}
+
+// Reads the diffuse color (`Kd`), texture (`map_Kd`), bump map (`bump` or
+// `map_Bump` with an optional `-bm` strength) and tangent space normal map
+// (`norm`) of every material in an MTL library and registers them in the
+// scene. As an extension `Kd_<pattern>` and `bump_<pattern>` give the
+// diffuse color and the bump map by a procedural texture, see
+// `procedural_texture`.
+fn load_materials(
+    path: &Path,
+    scene: &mut Scene,
+    materials: &mut HashMap<String, usize>,
+) -> Result<()> {
+    let file = std::fs::File::open(path)?;
+    let reader = std::io::BufReader::new(file);
+    let mut current: Option<(String, Material)> = None;
+    let mut finish = |current: Option<(String, Material)>, scene: &mut Scene| {
+        if let Some((name, material)) = current {
+            materials.insert(name, scene.add_material(material));
+        }
+    };
+
+    for (number, l) in reader.lines().enumerate() {
+        let l = l?;
+        let mut iterator = l.split_whitespace();
+        let incorrect = || {
+            Error::new(
+                ErrorKind::InvalidData,
+                format!(
+                    "Incorrect procedural texture at line {} of {}",
+                    number + 1,
+                    path.display()
+                ),
+            )
+        };
+        match (iterator.next(), current.as_mut()) {
+            (Some("newmtl"), _) => {
+                let name = iterator.next().unwrap_or_default().to_string();
+                finish(current.take(), scene);
+                current = Some((name, Material::default()));
+            }
+            (Some("Kd"), Some((_, material))) => {
+                let mut channel = || iterator.next().map_or(0.0, |c| c.parse().unwrap_or(0.0));
+                let color = Color::new(channel(), channel(), channel());
+                if let Texture::Constant(_) = material.diffuse {
+                    material.diffuse = Texture::Constant(color);
+                }
             }
+            (Some("map_Kd"), Some((_, material))) => {
+                // Options such as `-s` come first, the file name is last.
+                if let Some(file) = iterator.last() {
+                    let texture = ImageTexture::load(&path.with_file_name(file), WrapMode::Repeat)?;
+                    material.diffuse = Texture::Image(texture);
+                }
+            }
+            (Some("bump" | "map_Bump"), Some((_, material))) => {
+                let arguments: Vec<&str> = iterator.collect();
+                let strength = arguments
+                    .iter()
+                    .position(|&argument| argument == "-bm")
+                    .and_then(|i| arguments.get(i + 1))
+                    .map_or(1.0, |s| s.parse().unwrap_or(1.0));
+                if let Some(file) = arguments.last() {
+                    let texture = ImageTexture::load(&path.with_file_name(file), WrapMode::Repeat)?;
+                    material.bump_map = Some(BumpMap::new(Texture::Image(texture), strength));
+                }
+            }
+            (Some(key), Some((_, material))) if key.starts_with("Kd_") => {
+                let arguments: Vec<&str> = iterator.collect();
+                material.diffuse =
+                    procedural_texture(&key["Kd_".len()..], &arguments).ok_or_else(incorrect)?;
+            }
+            (Some(key), Some((_, material))) if key.starts_with("bump_") => {
+                let mut arguments: Vec<&str> = iterator.collect();
+                let mut strength = 1.0;
+                if let Some(i) = arguments.iter().position(|&argument| argument == "-bm") {
+                    let value = arguments.get(i + 1).and_then(|s| s.parse().ok());
+                    strength = value.ok_or_else(incorrect)?;
+                    arguments.drain(i..i + 2);
+                }
+                let height =
+                    procedural_texture(&key["bump_".len()..], &arguments).ok_or_else(incorrect)?;
+                material.bump_map = Some(BumpMap::new(height, strength));
+            }
+            (Some("norm"), Some((_, material))) => {
+                if let Some(file) = iterator.last() {
+                    let texture = ImageTexture::load(&path.with_file_name(file), WrapMode::Repeat)?;
+                    material.normal_map = Some(Texture::Image(texture));
+                }
+            }
+            _ => {}
+        }
+    }
+    finish(current, scene);
+    Ok(())
+}
+
+// Texture of a `checker`, `noise`, `marble` or `wood` pattern from the
+// arguments of an MTL extension line: a scale, in cells, noise features or
+// rings per unit, optionally followed by two colors, white and black by
+// default. Noise takes `perlin`, `fractal` or `turbulence` first, e.g.
+//
+//     Kd_checker 2 0.9 0.9 0.9 0.1 0.1 0.1
+//     Kd_noise turbulence 4
+//     bump_marble 3 -bm 0.2
+fn procedural_texture(pattern: &str, arguments: &[&str]) -> Option<Texture> {
+    let (kind, arguments) = match pattern {
+        "noise" => {
+            let kind = match *arguments.first()? {
+                "perlin" => NoiseKind::Perlin,
+                "fractal" => NoiseKind::Fractal,
+                "turbulence" => NoiseKind::Turbulence,
+                _ => return None,
+            };
+            (Some(kind), &arguments[1..])
+        }
+        _ => (None, arguments),
+    };
+    let numbers: Vec<f64> = arguments
+        .iter()
+        .map(|argument| argument.parse().ok())
+        .collect::<Option<_>>()?;
+    let (scale, first, second) = match numbers[..] {
+        [scale] => (scale, Color::white(), Color::black()),
+        [scale, r1, g1, b1, r2, g2, b2] => (scale, Color::new(r1, g1, b1), Color::new(r2, g2, b2)),
+        _ => return None,
+    };
+    if !(scale > 0.0 && scale.is_finite()) {
+        return None;
+    }
+    Some(match (pattern, kind) {
+        ("checker", _) => Texture::Checker(Checker::new(first, second, scale)),
+        ("noise", Some(kind)) => Texture::Noise(Noise::new(kind, scale, first, second)),
+        ("marble", _) => Texture::Marble(Marble::new(scale, first, second)),
+        ("wood", _) => Texture::Wood(Wood::new(scale, first, second)),
+        _ => return None,
+    })
+}
+
+// Every mesh becomes a group with its own material. Diffuse colors go to a
+// material library next to the file, other textures are written as white.
+impl Export for ObjectFile {
+    fn save(&self, scene: &Scene, time: f64) -> Result<()> {
+        status(&format!("Saving meshes to {} file...", self.path.display()));
+        let library = self.path.with_extension("mtl");
+        let mut file = BufWriter::new(File::create(&self.path)?);
+        if let Some(name) = library.file_name() {
+            writeln!(file, "mtllib {}", name.to_string_lossy())?;
+        }
+
+        let mut used = vec![];
+        // Number of vertices, texture coordinates and normals written so
+        // far, indices are global and start at one.
+        let (mut points, mut uvs, mut normals) = (1, 1, 1);
+        for (object, mesh) in scene_meshes(scene, time) {
+            let material = scene.material_index(object);
+            if !used.contains(&material) {
+                used.push(material);
+            }
+            writeln!(file, "g object_{}", object)?;
+            writeln!(file, "usemtl material_{}", material)?;
+            for point in mesh.positions() {
+                writeln!(file, "v {} {} {}", point.x, point.y, point.z)?;
+            }
+            for (u, v) in mesh.uvs() {
+                writeln!(file, "vt {} {}", u, v)?;
+            }
+            for normal in mesh.normals() {
+                writeln!(file, "vn {} {} {}", normal.x, normal.y, normal.z)?;
+            }
+
+            let has_uvs = !mesh.uvs().is_empty();
+            let has_normals = !mesh.normals().is_empty();
+            let corner = |index: u32| {
+                let index = index as usize;
+                match (has_uvs, has_normals) {
+                    (false, false) => format!("{}", points + index),
+                    (true, false) => format!("{}/{}", points + index, uvs + index),
+                    (false, true) => format!("{}//{}", points + index, normals + index),
+                    (true, true) => {
+                        format!("{}/{}/{}", points + index, uvs + index, normals + index)
+                    }
+                }
+            };
+            for [a, b, c] in mesh.indices() {
+                writeln!(file, "f {} {} {}", corner(*a), corner(*b), corner(*c))?;
+            }
+            points += mesh.positions().len();
+            uvs += mesh.uvs().len();
+            normals += mesh.normals().len();
+        }
+        file.flush()?;
+
+        let mut file = BufWriter::new(File::create(&library)?);
+        for material in used {
+            let color = match scene.materials()[material].diffuse {
+                Texture::Constant(color) => color,
+                _ => Color::white(),
+            };
+            writeln!(file, "newmtl material_{}", material)?;
+            writeln!(file, "Kd {} {} {}", color.r, color.g, color.b)?;
+        }
+        file.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geometry::normal::Normal;
+
+    // Position, normal and texture coordinates of every triangle corner.
+    fn corners(mesh: &TriangleMesh) -> Vec<(Point, Normal, (f64, f64))> {
+        mesh.indices()
+            .iter()
+            .flatten()
+            .map(|&i| {
+                let i = i as usize;
+                (mesh.positions()[i], mesh.normals()[i], mesh.uvs()[i])
+            })
+            .collect()
+    }
+
+    #[test]
+    fn exported_meshes_load_back_unchanged() {
+        let positions = vec![
+            Point::new(0.0, 0.0, 0.0),
+            Point::new(2.0, 0.0, 0.5),
+            Point::new(2.0, 1.0, 0.5),
+            Point::new(0.0, 1.0, 0.0),
+        ];
+        let normal = Vector::new(-0.5, 0.0, 2.0).normalize();
+        let uvs = vec![(0.0, 0.0), (1.0, 0.0), (1.0, 0.5), (0.0, 0.5)];
+        let mesh = TriangleMesh::new(positions, vec![normal; 4], uvs, vec![[0, 1, 2], [0, 2, 3]]);
+        let mut scene = Scene::new();
+        scene.add_object_with_material(Box::new(mesh.clone()), 0);
+
+        let path = std::env::temp_dir().join(format!("round_trip_{}.obj", std::process::id()));
+        ObjectFile::new(path.clone()).save(&scene, 0.0).unwrap();
+        let loaded = ObjectFile::new(path.clone()).load();
+        std::fs::remove_file(&path).unwrap();
+        std::fs::remove_file(path.with_extension("mtl")).unwrap();
+
+        let loaded = loaded.unwrap();
+        assert_eq!(loaded.objects().len(), 1);
+        let loaded = loaded.objects()[0].mesh_at(0.0).unwrap();
+        let (expected, actual) = (corners(&mesh), corners(&loaded));
+        assert_eq!(expected.len(), actual.len());
+        for ((p, n, uv), (q, m, st)) in expected.into_iter().zip(actual) {
+            assert!((p - q).length() < 1e-12);
+            assert!((Vector::from(n) - Vector::from(m)).length() < 1e-12);
+            assert_eq!(uv, st);
         }
     }
 }
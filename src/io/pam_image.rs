@@ -0,0 +1,33 @@
+use std::fs::File;
+use std::io::{BufWriter, Result, Write};
+use std::path::PathBuf;
+
+use crate::io::Output;
+use crate::renderer::color::Pixel;
+
+// Netpbm arbitrary map (P7) with 8 bit RGBA samples and straight alpha.
+pub(crate) struct PAMImage {
+    file_path: PathBuf,
+}
+
+impl PAMImage {
+    pub(crate) fn new(file_path: PathBuf) -> PAMImage {
+        PAMImage { file_path }
+    }
+}
+
+impl Output for PAMImage {
+    fn dump(&self, buff: &[Pixel], width: usize, height: usize) -> Result<()> {
+        let mut stream = BufWriter::new(File::create(&self.file_path)?);
+        write!(
+            stream,
+            "P7\nWIDTH {}\nHEIGHT {}\nDEPTH 4\nMAXVAL 255\nTUPLTYPE RGB_ALPHA\nENDHDR\n",
+            width, height
+        )?;
+        for pixel in &buff[..width * height] {
+            stream.write_all(&pixel.unpremultiplied().to_bytes())?;
+            stream.write_all(&[(pixel.alpha.clamp(0.0, 1.0) * 255.0).round() as u8])?;
+        }
+        stream.flush()
+    }
+}
@@ -0,0 +1,91 @@
+use std::io::{Error, ErrorKind, Result};
+use std::path::PathBuf;
+
+use crate::io::{Image, ImageInput};
+use crate::renderer::color::Color;
+
+// Portable float map, color (PF) or grayscale (Pf).
+pub(crate) struct PFMImage {
+    file_path: PathBuf,
+}
+
+impl PFMImage {
+    pub(crate) fn new(file_path: PathBuf) -> PFMImage {
+        PFMImage { file_path }
+    }
+}
+
+impl ImageInput for PFMImage {
+    fn read(&self) -> Result<Image> {
+        let data = std::fs::read(&self.file_path)?;
+        let invalid = |message: &str| Error::new(ErrorKind::InvalidData, message.to_string());
+
+        // The header is three whitespace separated lines: magic, size, scale.
+        let mut position = 0;
+        let mut next_token = || -> Result<String> {
+            while position < data.len() && data[position].is_ascii_whitespace() {
+                position += 1;
+            }
+            let start = position;
+            while position < data.len() && !data[position].is_ascii_whitespace() {
+                position += 1;
+            }
+            if start == position {
+                return Err(invalid("unexpected end of PFM file"));
+            }
+            Ok(String::from_utf8_lossy(&data[start..position]).into_owned())
+        };
+
+        let channels = match next_token()?.as_str() {
+            "PF" => 3,
+            "Pf" => 1,
+            _ => return Err(invalid("unsupported PFM format")),
+        };
+        let width = next_token()?
+            .parse::<usize>()
+            .map_err(|_| invalid("invalid PFM width"))?;
+        let height = next_token()?
+            .parse::<usize>()
+            .map_err(|_| invalid("invalid PFM height"))?;
+        // A negative scale marks little endian data.
+        let scale = next_token()?
+            .parse::<f64>()
+            .map_err(|_| invalid("invalid PFM scale"))?;
+        let little_endian = scale < 0.0;
+
+        // Exactly one whitespace byte separates the header from the data.
+        let body = data.get(position + 1..).unwrap_or_default();
+        let samples: Vec<f64> = body
+            .chunks_exact(4)
+            .map(|b| {
+                let bytes = [b[0], b[1], b[2], b[3]];
+                if little_endian {
+                    f32::from_le_bytes(bytes) as f64
+                } else {
+                    f32::from_be_bytes(bytes) as f64
+                }
+            })
+            .collect();
+        if samples.len() < width * height * channels {
+            return Err(invalid("PFM pixel data is too short"));
+        }
+
+        // Rows are stored bottom to top.
+        let mut pixels = Vec::with_capacity(width * height);
+        for row in (0..height).rev() {
+            for x in 0..width {
+                let sample = &samples[(row * width + x) * channels..];
+                pixels.push(if channels == 3 {
+                    Color::new(sample[0], sample[1], sample[2])
+                } else {
+                    Color::gray(sample[0])
+                });
+            }
+        }
+        Ok(Image {
+            width,
+            height,
+            pixels,
+        })
+    }
+}
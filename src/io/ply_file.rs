@@ -0,0 +1,597 @@
+use std::{
+    fs::File,
+    io::{BufWriter, Error, ErrorKind, Result, Write},
+    path::PathBuf,
+};
+
+use crate::{
+    geometry::{
+        mesh_check::Repairs, normal::Normal, point::Point, triangle_mesh::TriangleMesh,
+        vector::Vector,
+    },
+    renderer::{material::Material, scene::Scene, texture::Texture},
+};
+
+use super::{crease_normals, scene_meshes, status, Export, ImportOptions, Input};
+
+// Polygon file in any of the three encodings. The header may declare any
+// elements and properties, the `vertex` element provides positions and
+// optionally normals (`nx ny nz`), colors (`red green blue`) and texture
+// coordinates (`u v`, `s t` or `texture_u texture_v`), the `face` element a
+// list of vertex indices per polygon. Everything else is skipped.
+pub(crate) struct PlyFile {
+    path: PathBuf,
+    options: ImportOptions,
+}
+
+impl PlyFile {
+    pub(crate) fn new(path: PathBuf) -> PlyFile {
+        PlyFile {
+            path,
+            options: ImportOptions::default(),
+        }
+    }
+
+    pub(crate) fn with_options(mut self, options: ImportOptions) -> PlyFile {
+        self.options = options;
+        self
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Encoding {
+    Ascii,
+    LittleEndian,
+    BigEndian,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Scalar {
+    Int8,
+    Uint8,
+    Int16,
+    Uint16,
+    Int32,
+    Uint32,
+    Float32,
+    Float64,
+}
+
+impl Scalar {
+    fn from_name(name: &str) -> Option<Scalar> {
+        match name {
+            "char" | "int8" => Some(Scalar::Int8),
+            "uchar" | "uint8" => Some(Scalar::Uint8),
+            "short" | "int16" => Some(Scalar::Int16),
+            "ushort" | "uint16" => Some(Scalar::Uint16),
+            "int" | "int32" => Some(Scalar::Int32),
+            "uint" | "uint32" => Some(Scalar::Uint32),
+            "float" | "float32" => Some(Scalar::Float32),
+            "double" | "float64" => Some(Scalar::Float64),
+            _ => None,
+        }
+    }
+
+    fn size(&self) -> usize {
+        match self {
+            Scalar::Int8 | Scalar::Uint8 => 1,
+            Scalar::Int16 | Scalar::Uint16 => 2,
+            Scalar::Int32 | Scalar::Uint32 | Scalar::Float32 => 4,
+            Scalar::Float64 => 8,
+        }
+    }
+
+    // Largest value of integer types, which stands for full intensity in
+    // colors.
+    fn full_scale(&self) -> f64 {
+        match self {
+            Scalar::Int8 => i8::MAX as f64,
+            Scalar::Uint8 => u8::MAX as f64,
+            Scalar::Int16 => i16::MAX as f64,
+            Scalar::Uint16 => u16::MAX as f64,
+            Scalar::Int32 => i32::MAX as f64,
+            Scalar::Uint32 => u32::MAX as f64,
+            Scalar::Float32 | Scalar::Float64 => 1.0,
+        }
+    }
+}
+
+struct Property {
+    name: String,
+    scalar: Scalar,
+    // Type of the item count of list properties.
+    count: Option<Scalar>,
+}
+
+struct Element {
+    name: String,
+    count: usize,
+    properties: Vec<Property>,
+}
+
+// Values of one element, property by property. Scalar properties hold one
+// value per instance, list properties their items one after the other.
+struct Columns {
+    values: Vec<Vec<f64>>,
+    // Start of every instance's items in `values`, for list properties.
+    offsets: Vec<Vec<usize>>,
+}
+
+impl Input for PlyFile {
+    fn load(&self) -> Result<Scene> {
+        let bytes = std::fs::read(&self.path)?;
+        status("Loading data from ply file...");
+        let (encoding, elements, body) = read_header(&bytes)?;
+        let mut reader = Reader {
+            bytes: &bytes[body..],
+            position: 0,
+            encoding,
+        };
+
+        let mut vertices = None;
+        let mut faces = None;
+        for element in &elements {
+            let columns = reader.read_element(element)?;
+            match element.name.as_str() {
+                "vertex" => vertices = Some((element, columns)),
+                "face" => faces = Some((element, columns)),
+                _ => {}
+            }
+        }
+        let (Some((vertex, vertex_columns)), Some((face, face_columns))) = (vertices, faces) else {
+            return Err(invalid("The PLY file has no vertex or face element"));
+        };
+
+        let column = |names: &[&str]| {
+            names.iter().find_map(|name| {
+                let index = vertex.properties.iter().position(|p| p.name == *name)?;
+                (vertex.properties[index].count.is_none()).then(|| {
+                    (
+                        &vertex_columns.values[index],
+                        vertex.properties[index].scalar,
+                    )
+                })
+            })
+        };
+        let components = |names: [&[&str]; 3]| -> Option<[(&Vec<f64>, Scalar); 3]> {
+            Some([column(names[0])?, column(names[1])?, column(names[2])?])
+        };
+        let Some([x, y, z]) = components([&["x"], &["y"], &["z"]]) else {
+            return Err(invalid("The PLY vertices have no x, y and z"));
+        };
+        let mut positions: Vec<Point> = (0..vertex.count)
+            .map(|i| Point::new(x.0[i], y.0[i], z.0[i]))
+            .collect();
+        let mut repairs = Repairs::default();
+        let mut normals: Vec<Normal> = components([&["nx"], &["ny"], &["nz"]])
+            .map(|[x, y, z]| {
+                (0..vertex.count)
+                    .map(|i| repairs.normalize(Vector::new(x.0[i], y.0[i], z.0[i])))
+                    .collect()
+            })
+            .unwrap_or_default();
+        let mut colors: Vec<Vector> = components([
+            &["red", "r", "diffuse_red"],
+            &["green", "g", "diffuse_green"],
+            &["blue", "b", "diffuse_blue"],
+        ])
+        .map(|channels| {
+            let [r, g, b] = channels.map(|(values, scalar)| (values, scalar.full_scale()));
+            (0..vertex.count)
+                .map(|i| Vector::new(r.0[i] / r.1, g.0[i] / g.1, b.0[i] / b.1))
+                .collect()
+        })
+        .unwrap_or_default();
+        let mut uvs: Vec<(f64, f64)> = ["u", "s", "texture_u"]
+            .iter()
+            .zip(["v", "t", "texture_v"])
+            .find_map(|(u, v)| Some((column(&[u])?, column(&[v])?)))
+            .map(|(u, v)| (0..vertex.count).map(|i| (u.0[i], v.0[i])).collect())
+            .unwrap_or_default();
+
+        let Some(list) = face
+            .properties
+            .iter()
+            .position(|p| matches!(p.name.as_str(), "vertex_indices" | "vertex_index"))
+            .filter(|&index| face.properties[index].count.is_some())
+        else {
+            return Err(invalid("The PLY faces have no vertex_indices list"));
+        };
+        // Polygons are split into a triangle fan.
+        let mut indices = vec![];
+        let items = &face_columns.values[list];
+        let offsets = &face_columns.offsets[list];
+        for (f, &start) in offsets.iter().enumerate() {
+            let end = offsets.get(f + 1).copied().unwrap_or(items.len());
+            let polygon = &items[start..end];
+            if polygon
+                .iter()
+                .any(|&i| i < 0.0 || i as usize >= vertex.count)
+            {
+                return Err(invalid(&format!(
+                    "Face {} uses a vertex that does not exist",
+                    f
+                )));
+            }
+            for k in 1..polygon.len().saturating_sub(1) {
+                indices.push([polygon[0], polygon[k], polygon[k + 1]].map(|i| i as u32));
+            }
+        }
+        if indices.is_empty() {
+            return Err(invalid("The PLY file has no faces"));
+        }
+
+        if let (true, Some(crease_angle)) = (normals.is_empty(), self.options.crease_angle) {
+            let (sources, smooth, renumbered) = crease_normals(&positions, &indices, crease_angle);
+            positions = sources.iter().map(|&i| positions[i]).collect();
+            if !colors.is_empty() {
+                colors = sources.iter().map(|&i| colors[i]).collect();
+            }
+            if !uvs.is_empty() {
+                uvs = sources.iter().map(|&i| uvs[i]).collect();
+            }
+            normals = smooth;
+            indices = renumbered;
+        }
+
+        let mut scene = Scene::new();
+        let material = if colors.is_empty() {
+            0
+        } else {
+            scene.add_material(Material::new(Texture::VertexColor))
+        };
+        let mesh = TriangleMesh::new(positions, normals, uvs, indices)
+            .with_colors(colors)
+            .with_repairs(repairs);
+        scene.add_object_with_material(Box::new(mesh), material);
+        self.options.apply(&mut scene);
+        Ok(scene)
+    }
+}
+
+// Binary little endian file with float vertex properties. Normals, texture
+// coordinates and colors are written only when every mesh has them, since all
+// vertices share one layout. Faces keep the index of their object in an
+// `object` property.
+impl Export for PlyFile {
+    fn save(&self, scene: &Scene, time: f64) -> Result<()> {
+        status(&format!("Saving meshes to {} file...", self.path.display()));
+        let meshes = scene_meshes(scene, time);
+        let every = |has: fn(&TriangleMesh) -> bool| {
+            !meshes.is_empty() && meshes.iter().all(|(_, mesh)| has(mesh))
+        };
+        let has_normals = every(|mesh| !mesh.normals().is_empty());
+        let has_uvs = every(|mesh| !mesh.uvs().is_empty());
+        let has_colors = every(|mesh| !mesh.colors().is_empty());
+        let vertices: usize = meshes.iter().map(|(_, mesh)| mesh.positions().len()).sum();
+        let faces: usize = meshes.iter().map(|(_, mesh)| mesh.indices().len()).sum();
+
+        let mut file = BufWriter::new(File::create(&self.path)?);
+        writeln!(file, "ply")?;
+        writeln!(file, "format binary_little_endian 1.0")?;
+        writeln!(file, "element vertex {}", vertices)?;
+        let mut properties = vec!["x", "y", "z"];
+        if has_normals {
+            properties.extend(["nx", "ny", "nz"]);
+        }
+        if has_uvs {
+            properties.extend(["u", "v"]);
+        }
+        for property in properties {
+            writeln!(file, "property float {}", property)?;
+        }
+        if has_colors {
+            for property in ["red", "green", "blue"] {
+                writeln!(file, "property uchar {}", property)?;
+            }
+        }
+        writeln!(file, "element face {}", faces)?;
+        writeln!(file, "property list uchar int vertex_indices")?;
+        writeln!(file, "property int object")?;
+        writeln!(file, "end_header")?;
+
+        for (_, mesh) in &meshes {
+            for (index, point) in mesh.positions().iter().enumerate() {
+                let mut values = vec![point.x, point.y, point.z];
+                if has_normals {
+                    let normal = mesh.normals()[index];
+                    values.extend([normal.x, normal.y, normal.z]);
+                }
+                if has_uvs {
+                    let (u, v) = mesh.uvs()[index];
+                    values.extend([u, v]);
+                }
+                for value in values {
+                    file.write_all(&(value as f32).to_le_bytes())?;
+                }
+                if has_colors {
+                    let color = mesh.colors()[index];
+                    let channel = |value: f64| (value.clamp(0.0, 1.0) * 255.0).round() as u8;
+                    file.write_all(&[channel(color.x), channel(color.y), channel(color.z)])?;
+                }
+            }
+        }
+        let mut offset = 0;
+        for (object, mesh) in &meshes {
+            for triangle in mesh.indices() {
+                file.write_all(&[3])?;
+                for index in triangle {
+                    file.write_all(&((offset + *index as usize) as i32).to_le_bytes())?;
+                }
+                file.write_all(&(*object as i32).to_le_bytes())?;
+            }
+            offset += mesh.positions().len();
+        }
+        file.flush()
+    }
+}
+
+// Parses the header up to `end_header` and returns where the body starts.
+fn read_header(bytes: &[u8]) -> Result<(Encoding, Vec<Element>, usize)> {
+    if !bytes.starts_with(b"ply") {
+        return Err(invalid("Not a PLY file"));
+    }
+    let mut encoding = None;
+    let mut elements: Vec<Element> = vec![];
+    let mut position = 0;
+    loop {
+        let Some(length) = bytes[position..].iter().position(|&b| b == b'\n') else {
+            return Err(invalid("The PLY header has no end_header"));
+        };
+        let line = String::from_utf8_lossy(&bytes[position..position + length]);
+        position += length + 1;
+        let words: Vec<&str> = line.split_whitespace().collect();
+        match words.as_slice() {
+            ["end_header"] => break,
+            ["format", format, _] => {
+                encoding = Some(match *format {
+                    "ascii" => Encoding::Ascii,
+                    "binary_little_endian" => Encoding::LittleEndian,
+                    "binary_big_endian" => Encoding::BigEndian,
+                    _ => return Err(invalid(&format!("Unknown PLY format {}", format))),
+                })
+            }
+            ["element", name, count] => elements.push(Element {
+                name: name.to_string(),
+                count: count
+                    .parse()
+                    .map_err(|_| invalid(&format!("Incorrect count of {}", name)))?,
+                properties: vec![],
+            }),
+            ["property", "list", count, scalar, name] => {
+                let property = Property {
+                    name: name.to_string(),
+                    scalar: scalar_type(scalar)?,
+                    count: Some(scalar_type(count)?),
+                };
+                add_property(&mut elements, property)?;
+            }
+            ["property", scalar, name] => {
+                let property = Property {
+                    name: name.to_string(),
+                    scalar: scalar_type(scalar)?,
+                    count: None,
+                };
+                add_property(&mut elements, property)?;
+            }
+            _ => {}
+        }
+    }
+    let encoding = encoding.ok_or_else(|| invalid("The PLY header has no format"))?;
+    Ok((encoding, elements, position))
+}
+
+fn scalar_type(name: &str) -> Result<Scalar> {
+    Scalar::from_name(name).ok_or_else(|| invalid(&format!("Unknown PLY property type {}", name)))
+}
+
+fn add_property(elements: &mut [Element], property: Property) -> Result<()> {
+    let element = elements
+        .last_mut()
+        .ok_or_else(|| invalid("A PLY property comes before any element"))?;
+    element.properties.push(property);
+    Ok(())
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+    encoding: Encoding,
+}
+
+impl Reader<'_> {
+    fn read_element(&mut self, element: &Element) -> Result<Columns> {
+        let properties = element.properties.len();
+        let mut columns = Columns {
+            values: vec![vec![]; properties],
+            offsets: vec![vec![]; properties],
+        };
+        for _ in 0..element.count {
+            for (p, property) in element.properties.iter().enumerate() {
+                match property.count {
+                    None => {
+                        let value = self.read(property.scalar)?;
+                        columns.values[p].push(value);
+                    }
+                    Some(count) => {
+                        let count = self.read(count)?;
+                        columns.offsets[p].push(columns.values[p].len());
+                        for _ in 0..count as usize {
+                            let value = self.read(property.scalar)?;
+                            columns.values[p].push(value);
+                        }
+                    }
+                }
+            }
+        }
+        Ok(columns)
+    }
+
+    fn read(&mut self, scalar: Scalar) -> Result<f64> {
+        if self.encoding == Encoding::Ascii {
+            let rest = &self.bytes[self.position..];
+            let start = rest
+                .iter()
+                .position(|b| !b.is_ascii_whitespace())
+                .ok_or_else(|| invalid("The PLY file ends early"))?;
+            let length = rest[start..]
+                .iter()
+                .position(|b| b.is_ascii_whitespace())
+                .unwrap_or(rest.len() - start);
+            self.position += start + length;
+            let word = std::str::from_utf8(&rest[start..start + length]).unwrap_or_default();
+            return word
+                .parse()
+                .map_err(|_| invalid(&format!("Incorrect PLY value {}", word)));
+        }
+
+        let size = scalar.size();
+        let Some(data) = self.bytes.get(self.position..self.position + size) else {
+            return Err(invalid("The PLY file ends early"));
+        };
+        self.position += size;
+        let mut buffer = [0; 8];
+        buffer[..size].copy_from_slice(data);
+        if self.encoding == Encoding::BigEndian {
+            buffer[..size].reverse();
+        }
+        let value = match scalar {
+            Scalar::Int8 => buffer[0] as i8 as f64,
+            Scalar::Uint8 => buffer[0] as f64,
+            Scalar::Int16 => i16::from_le_bytes([buffer[0], buffer[1]]) as f64,
+            Scalar::Uint16 => u16::from_le_bytes([buffer[0], buffer[1]]) as f64,
+            Scalar::Int32 => i32::from_le_bytes(buffer[..4].try_into().unwrap()) as f64,
+            Scalar::Uint32 => u32::from_le_bytes(buffer[..4].try_into().unwrap()) as f64,
+            Scalar::Float32 => f32::from_le_bytes(buffer[..4].try_into().unwrap()) as f64,
+            Scalar::Float64 => f64::from_le_bytes(buffer),
+        };
+        Ok(value)
+    }
+}
+
+fn invalid(message: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, message.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn load(name: &str, bytes: &[u8]) -> Result<Scene> {
+        let path = std::env::temp_dir().join(format!("{}_{}.ply", name, std::process::id()));
+        std::fs::write(&path, bytes).unwrap();
+        let scene = PlyFile::new(path.clone()).load();
+        std::fs::remove_file(&path).unwrap();
+        scene
+    }
+
+    #[test]
+    fn header_lists_elements_and_where_the_body_starts() {
+        let text = b"ply\nformat binary_big_endian 1.0\ncomment made by hand\n\
+                     element vertex 8\nproperty float x\nproperty uchar red\n\
+                     element face 6\nproperty list uchar int vertex_indices\nend_header\nBODY";
+        let (encoding, elements, body) = read_header(text).unwrap();
+        assert_eq!(encoding, Encoding::BigEndian);
+        assert_eq!(&text[body..], b"BODY");
+        let names: Vec<(&str, usize)> = elements
+            .iter()
+            .map(|element| (element.name.as_str(), element.count))
+            .collect();
+        assert_eq!(names, [("vertex", 8), ("face", 6)]);
+        assert_eq!(elements[0].properties[1].scalar, Scalar::Uint8);
+        assert_eq!(elements[1].properties[0].count, Some(Scalar::Uint8));
+
+        for bad in [
+            &b"obj\n"[..],
+            b"ply\nformat ascii 1.0\nelement vertex 1\n",
+            b"ply\nelement vertex 1\nend_header\n",
+            b"ply\nformat ascii 1.0\nproperty float x\nend_header\n",
+            b"ply\nformat ascii 1.0\nelement vertex 1\nproperty half x\nend_header\n",
+        ] {
+            assert!(
+                read_header(bad).is_err(),
+                "{}",
+                String::from_utf8_lossy(bad)
+            );
+        }
+    }
+
+    #[test]
+    fn ascii_polygons_become_fans_with_vertex_colors() {
+        let text = b"ply\nformat ascii 1.0\n\
+                     element vertex 4\n\
+                     property float x\nproperty float y\nproperty float z\n\
+                     property uchar red\nproperty uchar green\nproperty uchar blue\n\
+                     element edge 1\nproperty int vertex1\nproperty int vertex2\n\
+                     element face 1\nproperty list uchar int vertex_indices\nend_header\n\
+                     0 0 0 255 0 0\n1 0 0 0 255 0\n1 1 0 0 0 255\n0 1 0 255 255 255\n\
+                     0 1\n\
+                     4 0 1 2 3\n";
+        let scene = load("ascii", text).unwrap();
+        let mesh = scene.objects()[0].mesh_at(0.0).unwrap();
+        assert_eq!(mesh.indices(), [[0, 1, 2], [0, 2, 3]]);
+        assert!((mesh.colors()[1] - Vector::new(0.0, 1.0, 0.0)).length() < 1e-12);
+        assert!(matches!(scene.material(0).diffuse, Texture::VertexColor));
+    }
+
+    #[test]
+    fn big_endian_values_are_read() {
+        let mut bytes = b"ply\nformat binary_big_endian 1.0\n\
+                          element vertex 3\n\
+                          property double x\nproperty double y\nproperty double z\n\
+                          element face 1\nproperty list uchar int vertex_indices\nend_header\n"
+            .to_vec();
+        for value in [0.0, 0.0, 0.0, 2.0, 0.0, 0.0, 0.0, 0.5, 0.0f64] {
+            bytes.extend(value.to_be_bytes());
+        }
+        bytes.push(3);
+        for index in [0, 1, 2i32] {
+            bytes.extend(index.to_be_bytes());
+        }
+        let scene = load("big_endian", &bytes).unwrap();
+        let mesh = scene.objects()[0].mesh_at(0.0).unwrap();
+        assert_eq!(mesh.positions()[1].x, 2.0);
+        assert_eq!(mesh.positions()[2].y, 0.5);
+
+        // Cut short, and pointing at a vertex that does not exist.
+        assert!(load("short", &bytes[..bytes.len() - 1]).is_err());
+        let last = bytes.len() - 1;
+        bytes[last] = 3;
+        assert!(load("out_of_range", &bytes).is_err());
+    }
+
+    #[test]
+    fn exported_meshes_load_back_unchanged() {
+        let positions = vec![
+            Point::new(0.0, 0.0, 0.0),
+            Point::new(2.0, 0.0, 0.5),
+            Point::new(2.0, 1.0, 0.5),
+            Point::new(0.0, 1.0, 0.0),
+        ];
+        let normal = Vector::new(-0.5, 0.0, 2.0).normalize();
+        let uvs = vec![(0.0, 0.0), (1.0, 0.0), (1.0, 0.5), (0.0, 0.5)];
+        let colors = vec![Vector::new(1.0, 0.5, 0.25); 4];
+        let mesh = TriangleMesh::new(positions, vec![normal; 4], uvs, vec![[0, 1, 2], [0, 2, 3]])
+            .with_colors(colors);
+        let mut scene = Scene::new();
+        scene.add_object_with_material(Box::new(mesh.clone()), 0);
+
+        let path = std::env::temp_dir().join(format!("round_trip_{}.ply", std::process::id()));
+        PlyFile::new(path.clone()).save(&scene, 0.0).unwrap();
+        let loaded = PlyFile::new(path.clone()).load();
+        std::fs::remove_file(&path).unwrap();
+
+        let loaded = loaded.unwrap();
+        assert_eq!(loaded.objects().len(), 1);
+        let loaded = loaded.objects()[0].mesh_at(0.0).unwrap();
+        assert_eq!(loaded.indices(), mesh.indices());
+        for i in 0..4 {
+            assert!((loaded.positions()[i] - mesh.positions()[i]).length() < 1e-6);
+            let normal = Vector::from(loaded.normals()[i]) - Vector::from(mesh.normals()[i]);
+            assert!(normal.length() < 1e-6);
+            assert!((loaded.uvs()[i].0 - mesh.uvs()[i].0).abs() < 1e-6);
+            assert!((loaded.uvs()[i].1 - mesh.uvs()[i].1).abs() < 1e-6);
+            assert!((loaded.colors()[i] - mesh.colors()[i]).length() < 1e-2);
+        }
+    }
+}
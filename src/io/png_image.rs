@@ -0,0 +1,289 @@
+use std::fs;
+use std::io::{Error, ErrorKind, Result};
+use std::path::PathBuf;
+
+use crate::io::deflate::zlib_compress;
+use crate::io::inflate::zlib_decompress;
+use crate::io::{Image, ImageInput, Output};
+use crate::renderer::color::{Color, Pixel};
+
+const SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n'];
+
+// Adam7 passes as (x start, y start, x step, y step).
+const ADAM7: [(usize, usize, usize, usize); 7] = [
+    (0, 0, 8, 8),
+    (4, 0, 8, 8),
+    (0, 4, 4, 8),
+    (2, 0, 4, 4),
+    (0, 2, 2, 4),
+    (1, 0, 2, 2),
+    (0, 1, 1, 2),
+];
+
+pub(crate) struct PNGImage {
+    file_path: PathBuf,
+}
+
+impl PNGImage {
+    pub(crate) fn new(file_path: PathBuf) -> PNGImage {
+        PNGImage { file_path }
+    }
+}
+
+struct Header {
+    width: usize,
+    height: usize,
+    bit_depth: u8,
+    color_type: u8,
+    interlaced: bool,
+}
+
+impl Header {
+    fn channels(&self) -> usize {
+        match self.color_type {
+            0 | 3 => 1,
+            4 => 2,
+            2 => 3,
+            _ => 4,
+        }
+    }
+
+    // Bytes per complete pixel, rounded up to one for sub-byte depths.
+    fn filter_offset(&self) -> usize {
+        (self.channels() * self.bit_depth as usize).div_ceil(8)
+    }
+
+    fn stride(&self, width: usize) -> usize {
+        (width * self.channels() * self.bit_depth as usize).div_ceil(8)
+    }
+}
+
+impl ImageInput for PNGImage {
+    fn read(&self) -> Result<Image> {
+        decode(&fs::read(&self.file_path)?)
+    }
+}
+
+// Decodes a PNG held in memory, such as one embedded in a model file.
+pub(crate) fn decode(data: &[u8]) -> Result<Image> {
+    if data.len() < 8 || data[..8] != SIGNATURE {
+        return Err(invalid("not a PNG file"));
+    }
+
+    let mut header = None;
+    let mut palette: Vec<Color> = vec![];
+    let mut compressed = vec![];
+    let mut position = 8;
+    while position + 8 <= data.len() {
+        let length = u32::from_be_bytes(data[position..position + 4].try_into().unwrap());
+        let kind = &data[position + 4..position + 8];
+        let start = position + 8;
+        let end = start + length as usize;
+        if end + 4 > data.len() {
+            return Err(invalid("truncated PNG chunk"));
+        }
+        let chunk = &data[start..end];
+        match kind {
+            b"IHDR" => header = Some(parse_header(chunk)?),
+            b"PLTE" => {
+                palette = chunk
+                    .chunks_exact(3)
+                    .map(|rgb| Color::from_bytes(rgb[0], rgb[1], rgb[2]))
+                    .collect();
+            }
+            b"IDAT" => compressed.extend_from_slice(chunk),
+            b"IEND" => break,
+            _ => {}
+        }
+        // Skip the CRC.
+        position = end + 4;
+    }
+
+    let header = header.ok_or_else(|| invalid("PNG is missing the IHDR chunk"))?;
+    let raw = zlib_decompress(&compressed)?;
+    let mut pixels = vec![Color::black(); header.width * header.height];
+
+    let passes: Vec<(usize, usize, usize, usize)> = if header.interlaced {
+        ADAM7.to_vec()
+    } else {
+        vec![(0, 0, 1, 1)]
+    };
+    let mut offset = 0;
+    for (x0, y0, dx, dy) in passes {
+        let pass_width = (header.width + dx - 1 - x0) / dx;
+        let pass_height = (header.height + dy - 1 - y0) / dy;
+        if pass_width == 0 || pass_height == 0 {
+            continue;
+        }
+        let size = (header.stride(pass_width) + 1) * pass_height;
+        if offset + size > raw.len() {
+            return Err(invalid("PNG image data is too short"));
+        }
+        let rows = unfilter(&header, &raw[offset..offset + size], pass_width)?;
+        offset += size;
+
+        let stride = header.stride(pass_width);
+        for y in 0..pass_height {
+            let row = &rows[y * stride..(y + 1) * stride];
+            for x in 0..pass_width {
+                let color = pixel(&header, &palette, row, x)?;
+                pixels[(y0 + y * dy) * header.width + x0 + x * dx] = color;
+            }
+        }
+    }
+
+    Ok(Image {
+        width: header.width,
+        height: header.height,
+        pixels,
+    })
+}
+
+impl Output for PNGImage {
+    // Writes 8 bit RGBA with straight alpha, every row unfiltered.
+    fn dump(&self, buff: &[Pixel], width: usize, height: usize) -> Result<()> {
+        let mut raw = Vec::with_capacity((width * 4 + 1) * height);
+        for row in buff.chunks_exact(width).take(height) {
+            raw.push(0);
+            for pixel in row {
+                raw.extend_from_slice(&pixel.unpremultiplied().to_bytes());
+                raw.push((pixel.alpha.clamp(0.0, 1.0) * 255.0).round() as u8);
+            }
+        }
+
+        let mut header = Vec::with_capacity(13);
+        header.extend_from_slice(&(width as u32).to_be_bytes());
+        header.extend_from_slice(&(height as u32).to_be_bytes());
+        // 8 bits per sample, RGBA, deflate, adaptive filtering, no interlace.
+        header.extend_from_slice(&[8, 6, 0, 0, 0]);
+
+        let mut data = SIGNATURE.to_vec();
+        write_chunk(&mut data, b"IHDR", &header);
+        write_chunk(&mut data, b"IDAT", &zlib_compress(&raw));
+        write_chunk(&mut data, b"IEND", &[]);
+        fs::write(&self.file_path, data)
+    }
+}
+
+fn write_chunk(data: &mut Vec<u8>, kind: &[u8; 4], chunk: &[u8]) {
+    data.extend_from_slice(&(chunk.len() as u32).to_be_bytes());
+    let start = data.len();
+    data.extend_from_slice(kind);
+    data.extend_from_slice(chunk);
+    let crc = crc32(&data[start..]);
+    data.extend_from_slice(&crc.to_be_bytes());
+}
+
+// CRC-32 as used by PNG chunks, computed bit by bit.
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = 0xffff_ffffu32;
+    for &byte in bytes {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                0xedb8_8320 ^ (crc >> 1)
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+fn parse_header(chunk: &[u8]) -> Result<Header> {
+    if chunk.len() < 13 {
+        return Err(invalid("PNG IHDR chunk is too short"));
+    }
+    let header = Header {
+        width: u32::from_be_bytes(chunk[0..4].try_into().unwrap()) as usize,
+        height: u32::from_be_bytes(chunk[4..8].try_into().unwrap()) as usize,
+        bit_depth: chunk[8],
+        color_type: chunk[9],
+        interlaced: chunk[12] == 1,
+    };
+    let valid_depth = match header.color_type {
+        0 => matches!(header.bit_depth, 1 | 2 | 4 | 8 | 16),
+        3 => matches!(header.bit_depth, 1 | 2 | 4 | 8),
+        2 | 4 | 6 => matches!(header.bit_depth, 8 | 16),
+        _ => false,
+    };
+    if !valid_depth {
+        return Err(invalid("unsupported PNG color type or bit depth"));
+    }
+    Ok(header)
+}
+
+// Reverses the per-row filters, returning rows without the filter byte.
+fn unfilter(header: &Header, data: &[u8], width: usize) -> Result<Vec<u8>> {
+    let stride = header.stride(width);
+    let bpp = header.filter_offset();
+    let mut rows = vec![0u8; stride * (data.len() / (stride + 1))];
+    for (y, line) in data.chunks_exact(stride + 1).enumerate() {
+        let (filter, line) = (line[0], &line[1..]);
+        let (previous, current) = rows.split_at_mut(y * stride);
+        let previous = if y == 0 {
+            None
+        } else {
+            Some(&previous[(y - 1) * stride..])
+        };
+        let current = &mut current[..stride];
+        for x in 0..stride {
+            let left = if x >= bpp { current[x - bpp] } else { 0 };
+            let up = previous.map_or(0, |row| row[x]);
+            let up_left = match previous {
+                Some(row) if x >= bpp => row[x - bpp],
+                _ => 0,
+            };
+            let predictor = match filter {
+                0 => 0,
+                1 => left,
+                2 => up,
+                3 => ((left as u16 + up as u16) / 2) as u8,
+                4 => paeth(left, up, up_left),
+                _ => return Err(invalid("invalid PNG filter type")),
+            };
+            current[x] = line[x].wrapping_add(predictor);
+        }
+    }
+    Ok(rows)
+}
+
+fn paeth(a: u8, b: u8, c: u8) -> u8 {
+    let p = a as i16 + b as i16 - c as i16;
+    let pa = (p - a as i16).abs();
+    let pb = (p - b as i16).abs();
+    let pc = (p - c as i16).abs();
+    if pa <= pb && pa <= pc {
+        a
+    } else if pb <= pc {
+        b
+    } else {
+        c
+    }
+}
+
+fn pixel(header: &Header, palette: &[Color], row: &[u8], x: usize) -> Result<Color> {
+    let depth = header.bit_depth as usize;
+    let channels = header.channels();
+    let max = ((1u32 << depth) - 1) as f64;
+    let sample = |channel: usize| -> u32 {
+        let bit = (x * channels + channel) * depth;
+        match depth {
+            16 => u16::from_be_bytes([row[bit / 8], row[bit / 8 + 1]]) as u32,
+            8 => row[bit / 8] as u32,
+            _ => ((row[bit / 8] >> (8 - depth - bit % 8)) & ((1 << depth) - 1) as u8) as u32,
+        }
+    };
+    let value = |channel: usize| sample(channel) as f64 / max;
+    Ok(match header.color_type {
+        0 | 4 => Color::gray(value(0)),
+        2 | 6 => Color::new(value(0), value(1), value(2)),
+        _ => *palette
+            .get(sample(0) as usize)
+            .ok_or_else(|| invalid("PNG palette index out of range"))?,
+    })
+}
+
+fn invalid(message: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, message)
+}
@@ -2,9 +2,11 @@ use std::fs::File;
 use std::io::BufWriter;
 use std::io::Result;
 use std::io::Write;
+use std::io::{Error, ErrorKind};
 use std::path::PathBuf;
 
-use crate::io::Output;
+use crate::io::{Image, ImageInput, Output};
+use crate::renderer::color::{Color, Pixel};
 
 pub(crate) struct PPMImage {
     file_path: PathBuf,
@@ -29,23 +31,106 @@ impl PPMImage {
 }
 
 impl Output for PPMImage {
-    fn dump(&self, buff: &[f64], width: usize, height: usize) -> Result<()> {
+    fn dump(&self, buff: &[Pixel], width: usize, height: usize) -> Result<()> {
         let stream = File::create(&self.file_path)?;
         let mut stream = BufWriter::new(stream);
         self.write_header(width, height, &mut stream)?;
         for y in 0..height {
             for x in 0..width {
                 let index = y * width + x;
-                let intensity = buff[index];
-                if intensity == -1.0 {
-                    stream.write_all(&[45, 100, 0])?;
+                stream.write_all(&buff[index].over(Color::black()).to_bytes())?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl ImageInput for PPMImage {
+    // Reads binary (P6) and ASCII (P3) pixmaps with 8 or 16 bit samples.
+    fn read(&self) -> Result<Image> {
+        let data = std::fs::read(&self.file_path)?;
+        let invalid = |message: &str| Error::new(ErrorKind::InvalidData, message.to_string());
+
+        // The header is four whitespace separated tokens, `#` starts a comment.
+        let mut position = 0;
+        let mut next_token = |data: &[u8]| -> Result<String> {
+            loop {
+                while position < data.len() && data[position].is_ascii_whitespace() {
+                    position += 1;
+                }
+                if position < data.len() && data[position] == b'#' {
+                    while position < data.len() && data[position] != b'\n' {
+                        position += 1;
+                    }
+                } else {
+                    break;
+                }
+            }
+            let start = position;
+            while position < data.len() && !data[position].is_ascii_whitespace() {
+                position += 1;
+            }
+            if start == position {
+                return Err(invalid("unexpected end of PPM file"));
+            }
+            Ok(String::from_utf8_lossy(&data[start..position]).into_owned())
+        };
+        let number = |token: String| {
+            token
+                .parse::<usize>()
+                .map_err(|_| invalid("invalid number in PPM header"))
+        };
+
+        let magic = next_token(&data)?;
+        let width = number(next_token(&data)?)?;
+        let height = number(next_token(&data)?)?;
+        let max = number(next_token(&data)?)?;
+        if max == 0 || max > 65535 {
+            return Err(invalid("invalid PPM maximum value"));
+        }
+
+        let samples: Vec<usize> = match magic.as_str() {
+            "P6" => {
+                // Exactly one whitespace byte separates the header from the data.
+                let body = data.get(position + 1..).unwrap_or_default();
+                if max < 256 {
+                    body.iter().map(|&b| b as usize).collect()
                 } else {
-                    let r = (intensity * 255.0) as u8;
-                    stream.write_all(&[r, r, r])?;
+                    body.chunks_exact(2)
+                        .map(|b| u16::from_be_bytes([b[0], b[1]]) as usize)
+                        .collect()
                 }
             }
+            "P3" => {
+                let mut samples = Vec::with_capacity(width * height * 3);
+                while let Ok(token) = next_token(&data) {
+                    samples.push(number(token)?);
+                }
+                samples
+            }
+            _ => return Err(invalid("unsupported PPM format")),
+        };
+        if samples.len() < width * height * 3 {
+            return Err(invalid("PPM pixel data is too short"));
         }
 
-        Ok(())
+        let max = max as f64;
+        let pixels = samples
+            .chunks_exact(3)
+            .take(width * height)
+            .map(|rgb| {
+                Color::new(
+                    rgb[0] as f64 / max,
+                    rgb[1] as f64 / max,
+                    rgb[2] as f64 / max,
+                )
+            })
+            .collect();
+        Ok(Image {
+            width,
+            height,
+            pixels,
+        })
     }
 }
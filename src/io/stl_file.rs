@@ -0,0 +1,355 @@
+use std::{
+    collections::HashMap,
+    io::{Error, ErrorKind, Result},
+    path::PathBuf,
+};
+
+use crate::{
+    geometry::{mesh_check::Repairs, point::Point, triangle_mesh::TriangleMesh},
+    renderer::{color::Color, material::Material, scene::Scene, texture::Texture},
+};
+
+use super::{crease_normals, status, ImportOptions, Input};
+
+// Size of the binary header and of one binary facet.
+const HEADER_SIZE: usize = 80;
+const FACET_SIZE: usize = 50;
+
+// Stereolithography file, ASCII or binary. Files only hold loose triangles,
+// so vertices at the same position are welded into shared ones. Without a
+// crease angle in the import options the triangles are shaded flat.
+pub(crate) struct StlFile {
+    path: PathBuf,
+    options: ImportOptions,
+}
+
+impl StlFile {
+    pub(crate) fn new(path: PathBuf) -> StlFile {
+        StlFile {
+            path,
+            options: ImportOptions::default(),
+        }
+    }
+
+    pub(crate) fn with_options(mut self, options: ImportOptions) -> StlFile {
+        self.options = options;
+        self
+    }
+}
+
+struct Facet {
+    points: [Point; 3],
+    // Color from the attribute bytes of binary files, channels in [0, 1].
+    color: Option<Color>,
+}
+
+// Facets of one color, which share a material.
+struct Group {
+    color: Option<Color>,
+    material: usize,
+    triangles: Vec<[Point; 3]>,
+}
+
+impl Input for StlFile {
+    fn load(&self) -> Result<Scene> {
+        let bytes = std::fs::read(&self.path)?;
+        status("Loading data from stl file...");
+        // ASCII files start with `solid`, but so do the headers of some
+        // binary files. Only a binary file has exactly the size its facet
+        // count promises.
+        let facets = if binary_size(&bytes) == Some(bytes.len()) {
+            read_binary(&bytes)
+        } else if bytes.starts_with(b"solid") {
+            read_ascii(&bytes)?
+        } else {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                "The STL file is neither ASCII nor a complete binary file",
+            ));
+        };
+
+        // Facets are grouped by color, each group becomes one mesh.
+        let mut scene = Scene::new();
+        let mut groups: Vec<Group> = vec![];
+        for facet in facets {
+            let index = match groups.iter().position(|group| group.color == facet.color) {
+                Some(index) => index,
+                None => {
+                    let material = match facet.color {
+                        Some(color) => scene.add_material(Material::new(Texture::Constant(color))),
+                        None => 0,
+                    };
+                    groups.push(Group {
+                        color: facet.color,
+                        material,
+                        triangles: vec![],
+                    });
+                    groups.len() - 1
+                }
+            };
+            groups[index].triangles.push(facet.points);
+        }
+        for group in groups {
+            if let Some(mesh) = build_mesh(&group.triangles, self.options.crease_angle) {
+                scene.add_object_with_material(Box::new(mesh), group.material);
+            }
+        }
+        self.options.apply(&mut scene);
+        Ok(scene)
+    }
+}
+
+fn binary_size(bytes: &[u8]) -> Option<usize> {
+    let count = bytes.get(HEADER_SIZE..HEADER_SIZE + 4)?;
+    let count = u32::from_le_bytes(count.try_into().unwrap()) as usize;
+    Some(HEADER_SIZE + 4 + count * FACET_SIZE)
+}
+
+// Binary facets hold a normal, three vertices and two attribute bytes. Two
+// conventions put a 15 bit color there: VisCAM and SolidView set the top bit
+// for colored facets and store blue in the low bits, Materialise Magics
+// writes `COLOR=` and a default RGBA color into the header, clears the top
+// bit for facets with their own color and stores red in the low bits.
+fn read_binary(bytes: &[u8]) -> Vec<Facet> {
+    let header = &bytes[..HEADER_SIZE];
+    let default_color = header
+        .windows(6)
+        .position(|window| window == b"COLOR=")
+        .and_then(|i| header.get(i + 6..i + 9))
+        .map(|rgb| {
+            Color::new(
+                rgb[0] as f64 / 255.0,
+                rgb[1] as f64 / 255.0,
+                rgb[2] as f64 / 255.0,
+            )
+        });
+
+    let float = |data: &[u8], i: usize| {
+        f32::from_le_bytes(data[i * 4..i * 4 + 4].try_into().unwrap()) as f64
+    };
+    bytes[HEADER_SIZE + 4..]
+        .chunks_exact(FACET_SIZE)
+        .map(|data| {
+            // The stored normal is skipped, the winding order is more
+            // reliable.
+            let point =
+                |i: usize| Point::new(float(data, i), float(data, i + 1), float(data, i + 2));
+            let attribute = u16::from_le_bytes([data[48], data[49]]);
+            let channel = |shift: u16| ((attribute >> shift) & 0x1f) as f64 / 31.0;
+            let color = match default_color {
+                Some(_) if attribute & 0x8000 == 0 => {
+                    Some(Color::new(channel(0), channel(5), channel(10)))
+                }
+                Some(default) => Some(default),
+                None if attribute & 0x8000 != 0 => {
+                    Some(Color::new(channel(10), channel(5), channel(0)))
+                }
+                None => None,
+            };
+            Facet {
+                points: [point(3), point(6), point(9)],
+                color,
+            }
+        })
+        .collect()
+}
+
+// Reads `facet ... outer loop, vertex x y z, ... endloop endfacet` blocks of
+// any number of solids. Loops with more than three vertices are split into a
+// triangle fan.
+fn read_ascii(bytes: &[u8]) -> Result<Vec<Facet>> {
+    let text = std::str::from_utf8(bytes).map_err(|_| {
+        Error::new(
+            ErrorKind::InvalidData,
+            "The ASCII STL file is not valid UTF-8",
+        )
+    })?;
+    let invalid = |message: &str, line: usize| {
+        Error::new(
+            ErrorKind::InvalidData,
+            format!("{} at line {} of the STL file", message, line),
+        )
+    };
+    let mut facets = vec![];
+    let mut loop_points = vec![];
+    for (i, line) in text.lines().enumerate() {
+        let mut words = line.split_whitespace();
+        match words.next() {
+            Some("vertex") => {
+                let coordinates: Vec<f64> = words
+                    .map(str::parse)
+                    .collect::<std::result::Result<_, _>>()
+                    .map_err(|_| invalid("Incorrect vertex", i + 1))?;
+                let &[x, y, z] = coordinates.as_slice() else {
+                    return Err(invalid("A vertex needs 3 coordinates", i + 1));
+                };
+                loop_points.push(Point::new(x, y, z));
+            }
+            Some("endloop") => {
+                if loop_points.len() < 3 {
+                    return Err(invalid("A facet needs at least 3 vertices", i + 1));
+                }
+                for k in 1..loop_points.len() - 1 {
+                    facets.push(Facet {
+                        points: [loop_points[0], loop_points[k], loop_points[k + 1]],
+                        color: None,
+                    });
+                }
+                loop_points.clear();
+            }
+            _ => {}
+        }
+    }
+    Ok(facets)
+}
+
+// Welds the corners of `triangles` by position and drops degenerate ones,
+// counting them in the repairs of the mesh.
+// With a crease angle, in degrees, the normals are smoothed across edges
+// that bend less than the angle.
+fn build_mesh(triangles: &[[Point; 3]], crease_angle: Option<f64>) -> Option<TriangleMesh> {
+    let mut welded: HashMap<[u64; 3], u32> = HashMap::new();
+    let mut points: Vec<Point> = vec![];
+    let mut indices: Vec<[u32; 3]> = vec![];
+    let mut repairs = Repairs::default();
+    for triangle in triangles {
+        let corners = triangle.map(|point| {
+            // Adding zero turns -0 into 0, so both weld together.
+            let key = [point.x + 0.0, point.y + 0.0, point.z + 0.0].map(f64::to_bits);
+            *welded.entry(key).or_insert_with(|| {
+                points.push(point);
+                (points.len() - 1) as u32
+            })
+        });
+        let area = (triangle[1] - triangle[0]).cross(triangle[2] - triangle[0]);
+        if corners[0] != corners[1]
+            && corners[1] != corners[2]
+            && corners[0] != corners[2]
+            && area.length() > 0.0
+        {
+            indices.push(corners);
+        } else {
+            repairs.dropped_triangles += 1;
+        }
+    }
+    if indices.is_empty() {
+        return None;
+    }
+
+    let Some(crease_angle) = crease_angle else {
+        return Some(TriangleMesh::new(points, vec![], vec![], indices).with_repairs(repairs));
+    };
+    let (sources, normals, indices) = crease_normals(&points, &indices, crease_angle);
+    let positions = sources.iter().map(|&source| points[source]).collect();
+    Some(TriangleMesh::new(positions, normals, vec![], indices).with_repairs(repairs))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Binary file of `facets`, each three corners and an attribute.
+    fn binary(header: &[u8], facets: &[([[f32; 3]; 3], u16)]) -> Vec<u8> {
+        let mut bytes = header.to_vec();
+        bytes.resize(HEADER_SIZE, 0);
+        bytes.extend((facets.len() as u32).to_le_bytes());
+        for (corners, attribute) in facets {
+            bytes.extend([0u8; 12]);
+            for value in corners.iter().flatten() {
+                bytes.extend(value.to_le_bytes());
+            }
+            bytes.extend(attribute.to_le_bytes());
+        }
+        bytes
+    }
+
+    const SQUARE: [[[f32; 3]; 3]; 2] = [
+        [[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [1.0, 1.0, 0.0]],
+        [[0.0, 0.0, 0.0], [1.0, 1.0, 0.0], [0.0, 1.0, 0.0]],
+    ];
+
+    #[test]
+    fn ascii_loops_are_split_into_fans() {
+        let text = "solid square\n\
+                    facet normal 0 0 1\n outer loop\n\
+                    vertex 0 0 0\n vertex 1 0 0\n vertex 1 1 0\n vertex 0 1 0\n\
+                    endloop\n endfacet\n\
+                    endsolid square\n";
+        let facets = read_ascii(text.as_bytes()).unwrap();
+        assert_eq!(facets.len(), 2);
+        let corners = |facet: &Facet| facet.points.map(|point| (point.x, point.y));
+        assert_eq!(corners(&facets[0]), [(0.0, 0.0), (1.0, 0.0), (1.0, 1.0)]);
+        assert_eq!(corners(&facets[1]), [(0.0, 0.0), (1.0, 1.0), (0.0, 1.0)]);
+        assert!(facets.iter().all(|facet| facet.color.is_none()));
+    }
+
+    #[test]
+    fn malformed_ascii_names_the_line() {
+        for (text, line) in [
+            ("solid\nouter loop\nvertex 0 0\n", 3),
+            ("solid\nouter loop\nvertex 0 0 x\n", 3),
+            (
+                "solid\nouter loop\nvertex 0 0 0\nvertex 1 0 0\nendloop\n",
+                5,
+            ),
+        ] {
+            let Err(error) = read_ascii(text.as_bytes()) else {
+                panic!("{:?} parsed", text);
+            };
+            assert!(error
+                .to_string()
+                .ends_with(&format!("at line {} of the STL file", line)));
+        }
+    }
+
+    #[test]
+    fn binary_colors_follow_both_conventions() {
+        // VisCAM: top bit set for colored facets, blue in the low bits.
+        let bytes = binary(
+            b"solid but binary",
+            &[(SQUARE[0], 0x8000 | 31), (SQUARE[1], 0)],
+        );
+        assert_eq!(binary_size(&bytes), Some(bytes.len()));
+        let facets = read_binary(&bytes);
+        assert_eq!(facets[0].color, Some(Color::new(0.0, 0.0, 1.0)));
+        assert_eq!(facets[1].color, None);
+
+        // Magics: a default color in the header, red in the low bits of
+        // facets that clear the top bit.
+        let bytes = binary(
+            b"COLOR=\xff\x00\x00\xff",
+            &[(SQUARE[0], 31), (SQUARE[1], 0x8000)],
+        );
+        let facets = read_binary(&bytes);
+        assert_eq!(facets[0].color, Some(Color::new(1.0, 0.0, 0.0)));
+        assert_eq!(facets[1].color, Some(Color::new(1.0, 0.0, 0.0)));
+    }
+
+    #[test]
+    fn corners_are_welded_and_degenerate_triangles_dropped() {
+        let mut triangles: Vec<[Point; 3]> = SQUARE
+            .iter()
+            .map(|corners| corners.map(|[x, y, z]| Point::new(x as f64, y as f64, z as f64)))
+            .collect();
+        triangles.push([Point::new(-0.0, 0.0, 0.0); 3]);
+        let mesh = build_mesh(&triangles, None).unwrap();
+        assert_eq!(mesh.positions().len(), 4);
+        assert_eq!(mesh.indices(), [[0, 1, 2], [0, 2, 3]]);
+        assert_eq!(mesh.repairs().dropped_triangles, 1);
+        assert!(build_mesh(&triangles[2..], None).is_none());
+    }
+
+    #[test]
+    fn files_of_neither_kind_are_rejected() {
+        let path = std::env::temp_dir().join(format!("neither_{}.stl", std::process::id()));
+        let mut bytes = binary(b"binary", &SQUARE.map(|corners| (corners, 0)));
+        bytes.pop();
+        std::fs::write(&path, &bytes).unwrap();
+        let result = StlFile::new(path.clone()).load();
+        std::fs::remove_file(&path).unwrap();
+        let Err(error) = result else {
+            panic!("a truncated binary file loaded");
+        };
+        assert_eq!(error.kind(), ErrorKind::InvalidData);
+    }
+}
@@ -2,77 +2,1233 @@ mod geometry;
 mod io;
 mod renderer;
 
-use geometry::{Axis, Transformation};
+use geometry::mesh_check::MeshCheck;
+use geometry::motion::Motion;
 use geometry::normal::Normal;
+use geometry::plane::Plane;
 use geometry::point::Point;
-use geometry::sphere::Sphere;
-use geometry::triangle::Triangle;
-use renderer::camera::Camera;
+use geometry::trs::Trs;
+use geometry::vector::Vector;
+use io::animation_file::AnimationFile;
+use io::checkpoint_file::CheckpointFile;
+use io::console::Console;
+use io::{ImportOptions, Output, UpAxis, Verbosity};
+use renderer::animation::{Timeline, Turntable};
+use renderer::aov::Aov;
+use renderer::camera::{Aperture, Camera, Lens};
+use renderer::color::Color;
+use renderer::environment::{Environment, EnvironmentMap, Gradient, Sky};
+use renderer::frame::{Frame, Region};
 use renderer::light::Light;
+use renderer::material::Material;
 use renderer::scene::Scene;
-use renderer::viewframe::ViewFrame;
-use renderer::RayTracer;
+use renderer::texture::Texture;
+use renderer::{Budget, RayTracer};
 use std::ffi::OsStr;
-use std::path::PathBuf;
+use std::io::{Error, ErrorKind, Result};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+const HELP_MSG: &str = "./graphics <command> [options]
+                            render   renders a model to an image. The command may be left out.
+                            preview  renders a model as text in the terminal.
+                            info     prints the size of a model and checks its meshes.
+                            convert  writes a model as an OBJ or PLY file.
+                            Run ./graphics <command> --help for the options of a command.
+                            Errors are printed to stderr. The exit code is 1 when the work fails and 2 when the arguments are wrong.";
+
+const RENDER_HELP: &str = "./graphics render --source=path_to_object.obj --output=path_to_result.ppm [--resolution=WxH] [--region=x,y,w,h [--crop]]
+                            [--samples=N] [--pass-samples=N] [--progressive] [--time-limit=seconds] [--noise=level [--adaptive [--min-samples=N]]]
+                            [--sample-map=path] [--checkpoint=path [--checkpoint-interval=seconds]]
+                            [--threads=N] [--format=ppm|png|pam|exr] [--background=r,g,b] [--aov=pass[:path]]... [--layers=path.exr] [--export=path.obj|ply]
+                            [--up=y|z] [--flip-handedness] [--rotate=x,y,z] [--unit-scale=s] [--recenter] [--crease-angle=degrees] [--light=x,y,z]...
+                            [--environment=r,g,b | r,g,b:r,g,b | path.hdr|pfm | sky [--sun=x,y,z] [--turbidity=t]] [--environment-intensity=s] [--environment-samples=N]
+                            [--ground[=r,g,b]] [--aperture=radius | --f-stop=N] [--focus-distance=d | --autofocus=x,y] [--aperture-blades=n] [--aperture-rotation=degrees]
+                            [--motion=x,y,z] [--camera-motion=x,y,z] [--shutter=open,close]
+                            [--animation=path.anim] [--frames=first-last] [--skip-existing] [--turntable=frames]
+                            [--camera=x,y,z [--look-at=x,y,z] [--fov=degrees] | --view=x,y,z [--margin=m]] [--quiet | --verbose]
+                            --source names the model to render and --output the image to write.
+                            The source is a model in the Wavefront OBJ, PLY, STL or glTF 2.0 (.gltf or .glb) format.
+                            OBJ materials may take procedural colors and bump maps, e.g. Kd_checker scale [r g b r g b], from the checker,
+                            noise perlin|fractal|turbulence, marble and wood patterns, or bump_marble scale [-bm strength].
+                            The output is a PPM, PNG, PAM or EXR image, PNG, PAM and EXR keep the alpha channel.
+                            --format picks the image format instead of the output file extension.
+                            --export writes the meshes of the scene as placed for rendering to an OBJ or PLY file, the output may then be left out.
+                            --resolution sets the image size in pixels, 720x576 by default.
+                            --region renders only a rectangle of the image, from its top left corner at x,y, leaving the rest transparent.
+                            --crop writes just the region instead of the whole image.
+                            --threads sets how many rows are rendered at once, one per processor by default.
+                            --up gives the axis that points up in the model file, y by default. Z up models are turned to Y up.
+                            --flip-handedness mirrors the model along Z, for files with left-handed coordinates.
+                            --rotate turns the model about the X, Y and then Z axis by the given degrees once it is Y up.
+                            --unit-scale scales the model from file units to scene units, --recenter moves its center to the origin.
+                            --crease-angle smooths STL and PLY models without normals across edges that bend less than the angle, they are flat shaded otherwise.
+                            --light places a point light and may be repeated. It replaces the lights of the model file and the default light.
+                            --ground puts an endless floor of the color, 0.8,0.8,0.8 by default, under the model to catch its shadow.
+                            --environment lights the model from all around and shows behind it: a color, a gradient from the color below
+                            to the one above, an equirectangular HDR or PFM image or a daylight sky with the sun along --sun, 1,2,1 by default.
+                            --turbidity makes the sky hazier, from 2 for a clear sky to 10, 3 by default.
+                            --environment-intensity scales the environment, --environment-samples sets the rays gathering it at every hit, 16 by default.
+                            --samples sets the camera rays per pixel used for anti-aliasing.
+                            --pass-samples renders them in passes of this many rays per pixel that add up into the same image.
+                            --progressive doubles the samples per pixel with every pass and writes the image after each of them.
+                            --time-limit stops after the pass during which the time runs out, the render goes on until then without --samples.
+                            --noise stops once the estimated error of every pixel's brightness is below the level, 0.01 is 1% of white.
+                            --adaptive lets every pixel stop on its own once it is below the noise level and has --min-samples, 16 by default.
+                            The noisy pixels go on up to --samples, so edges and soft shadows get most of the rays.
+                            --sample-map writes a heatmap of the samples taken per pixel, from blue for the fewest to red for the most.
+                            --checkpoint saves the samples gathered to a file every --checkpoint-interval seconds, 60 by default, and when done.
+                            A render given an existing checkpoint goes on from it and needs the same scene and options.
+                            Passes are of one sample with --progressive, --time-limit, --noise or --checkpoint and hold all samples otherwise.
+                            --background composites the image over a color, channels in [0, 1], instead of keeping alpha.
+                            --aov renders an extra pass: depth, normal, albedo, object_id, material_id, uv, direct, indirect or shadow.
+                            --layers writes the image and all extra passes into one multi-layer EXR file.
+                            --aperture or --f-stop turn on depth of field, focused on the view frame unless --focus-distance is given.
+                            --f-stop is the f-number of a full-frame camera, 36 mm wide, with the same field of view, taking scene units as meters.
+                            --autofocus focuses on the object seen through a pixel. Use --samples to smooth the blur.
+                            --aperture-blades makes the aperture a polygon instead of a circle.
+                            --motion and --camera-motion move the model and the camera by a distance between time 0 and 1.
+                            --shutter sets when the exposure starts and ends, 0,1 by default when something moves.
+                            --animation reads keyframes for the camera, the model and the light, time is counted in frames.
+                            Rotations are Euler angles applied X, Y then Z unless an order such as zyx follows them.
+                            --frames renders every frame of the range to a numbered image, out.png becomes out_0001.png and so on.
+                            The shutter is then relative to the start of each frame.
+                            --skip-existing leaves frames whose image already exists alone, to resume an interrupted sequence.
+                            --turntable spins the model a full turn over a number of frames, numbered from 1, with the camera framing it.
+                            --camera places the camera, looking at --look-at or the center of the model with a vertical --fov, 40 by default.
+                            Otherwise the first camera of a glTF file is used, unless --view is given.
+                            Otherwise the camera looks along --view, 0,0,-1 by default, and is backed off until the whole model fits.
+                            --margin leaves room around the model as a share of the image size, 0.1 by default.
+                            --quiet leaves out progress messages, --verbose adds timings and details about the scene.";
+
+const PREVIEW_HELP: &str = "./graphics preview --source=path_to_object.obj [--resolution=WxH] [--samples=N] [--pass-samples=N] [--progressive] [--time-limit=seconds]
+                            [--noise=level [--adaptive [--min-samples=N]]] [--threads=N] [--light=x,y,z]...
+                            [--environment=r,g,b | r,g,b:r,g,b | path.hdr|pfm | sky [--sun=x,y,z] [--turbidity=t]] [--environment-intensity=s] [--environment-samples=N]
+                            [--up=y|z] [--flip-handedness] [--rotate=x,y,z] [--unit-scale=s] [--recenter] [--crease-angle=degrees]
+                            [--camera=x,y,z [--look-at=x,y,z] [--fov=degrees] | --view=x,y,z [--margin=m]] [--quiet | --verbose]
+                            Renders the model as text in the terminal, with the scene and camera options of render.
+                            --resolution is counted in characters, 80x32 by default. --progressive redraws the model after every pass.";
+
+const INFO_HELP: &str = "./graphics info --source=path_to_object.obj
+                            [--up=y|z] [--flip-handedness] [--rotate=x,y,z] [--unit-scale=s] [--recenter] [--crease-angle=degrees] [--quiet | --verbose]
+                            Loads the model the same way as for rendering and prints its vertex and triangle counts and bounds.
+                            Every mesh is checked for degenerate triangles, edges shared by more than two triangles,
+                            neighbours wound the other way round and normals that are not of unit length, including the
+                            ones the loader dropped or rescaled. Indices out of range fail the loading itself.
+                            These are errors and make the command exit with 1. Open edges and missing normals are reported as warnings.";
+
+const CONVERT_HELP: &str = "./graphics convert --source=path_to_object.obj --output=path_to_result.obj|ply
+                            [--up=y|z] [--flip-handedness] [--rotate=x,y,z] [--unit-scale=s] [--recenter] [--crease-angle=degrees] [--quiet | --verbose]
+                            Loads the model the same way as for rendering and writes its meshes to an OBJ or PLY file.";
+
+// Options of the render command that make no sense for a preview.
+const RENDER_ONLY: [&str; 13] = [
+    "--output",
+    "--region",
+    "--crop",
+    "--format",
+    "--export",
+    "--aov",
+    "--layers",
+    "--frames",
+    "--skip-existing",
+    "--turntable",
+    "--checkpoint",
+    "--checkpoint-interval",
+    "--sample-map",
+];
+
+// What the `--environment` option asks for, built once the scene is loaded.
+enum EnvironmentSource {
+    Constant(Color),
+    Gradient(Color, Color),
+    Map(PathBuf),
+    Sky,
+}
+
+struct Options {
+    source: PathBuf,
+    // Up axis, handedness, units and placement of the model file.
+    import: ImportOptions,
+    // Image to render, none when only exporting.
+    output: Option<PathBuf>,
+    // Image format, taken from the output file extension when not given.
+    format: Option<String>,
+    // Meshes of the scene as set up, written before rendering.
+    export: Option<PathBuf>,
+    // Image size in pixels, or in characters for previews.
+    width: usize,
+    height: usize,
+    // Part of the image to render, written alone when cropping.
+    region: Option<Region>,
+    crop: bool,
+    // Samples per pixel in total, none to render until the time limit.
+    samples: Option<usize>,
+    // Samples per pixel added by every progressive pass.
+    pass_samples: usize,
+    time_limit: Option<f64>,
+    // Stops once the estimated noise of every pixel is down to this.
+    noise: Option<f64>,
+    // Samples per pixel double with every pass and each pass writes the image.
+    progressive: bool,
+    // Pixels stop taking samples on their own once their noise is low enough
+    // and they have `min_samples`, `samples` is then the most they take.
+    adaptive: bool,
+    min_samples: usize,
+    // Heatmap of the samples taken per pixel.
+    sample_map: Option<PathBuf>,
+    // Accumulated samples, saved every `checkpoint_interval` seconds.
+    checkpoint: Option<PathBuf>,
+    checkpoint_interval: f64,
+    threads: usize,
+    // Replace the lights of the model file when given.
+    lights: Vec<Point>,
+    // Color of a floor under the model.
+    ground: Option<Color>,
+    environment: Option<EnvironmentSource>,
+    environment_intensity: f64,
+    // Towards the sun and haziness of the sky environment.
+    sun: Vector,
+    turbidity: f64,
+    // Rays gathering the environment at every hit.
+    environment_samples: usize,
+    background: Option<Color>,
+    // Extra passes, written to their own file when a path is given.
+    aovs: Vec<(Aov, Option<PathBuf>)>,
+    // Multi-layer EXR with the image and every extra pass.
+    layers: Option<PathBuf>,
+    aperture: Option<f64>,
+    f_stop: Option<f64>,
+    focus_distance: Option<f64>,
+    autofocus: Option<(usize, usize)>,
+    aperture_blades: u32,
+    aperture_rotation: f64,
+    shutter: Option<(f64, f64)>,
+    // Distances the model and the camera travel between time 0 and 1.
+    motion: Option<Vector>,
+    camera_motion: Option<Vector>,
+    // Keyframes for the model, the light and the camera.
+    animation: Option<PathBuf>,
+    // Renders a numbered image per frame instead of a single image.
+    frames: Option<(usize, usize)>,
+    skip_existing: bool,
+    // Frames of a full turn of the model.
+    turntable: Option<usize>,
+    camera_position: Option<Point>,
+    look_at: Option<Point>,
+    // Vertical field of view in degrees.
+    field_of_view: f64,
+    // Viewing direction and spare room for the automatic camera.
+    view: Option<Vector>,
+    margin: f64,
+}
 
 fn main() {
-    let (source, output) = parse_args();
-    let mut scene = Scene::from_obj_file(source).unwrap();
-    scene.add_light(Light::new(Point::new(50.0, 0.0, 150.0)));
-    for object in scene.objects_mut().iter_mut() {
-        object.transform(Transformation::Rotation(Axis::Y, 90.0));
-        object.transform(Transformation::Rotation(Axis::Z, 90.0));
-    }
-    let viewframe = ViewFrame::new(Point::new(20.0, 25.0, 80.0), 75.0, 42.0);
-    let camera = Camera::new(Point::new(20.0, 25.0, 130.0), viewframe);
-    let ray_tracer = RayTracer::new(scene, camera, 720, 576);
-    ray_tracer
-        .render(io::ppm_image::PPMImage::new(output))
-        .unwrap();
-}
-fn parse_args() -> (PathBuf, PathBuf) {
-    const HELP_MSG: &str = "./graphics --source=path_to_object.obj --output=path_to_result.ppm 
-                            The ratracer takes two arguments: the input file and the output file.
-                            The input file is a object file in the Wavefront OBJ format.
-                            The output file is a image fiile in the PPM file format.";
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let (command, args) = match args.first().map(String::as_str) {
+        None | Some("help" | "--help") => {
+            println!("{}", HELP_MSG);
+            return;
+        }
+        // Options without a command are for render, as before there were
+        // commands.
+        Some(first) if first.starts_with("--") => ("render", args.as_slice()),
+        Some(command) => (command, &args[1..]),
+    };
+    let result = match command {
+        "render" => render(args),
+        "preview" => preview(args),
+        "info" => info(args),
+        "convert" => convert(args),
+        _ => usage_error(&format!("Unknown command {}", command), HELP_MSG),
+    };
+    if let Err(error) = result {
+        eprintln!("error: {}", error);
+        std::process::exit(1);
+    }
+}
+
+fn render(args: &[String]) -> Result<()> {
+    let options = parse_args(args, RENDER_HELP, false);
+    let started = Instant::now();
+    let (mut scene, turntable) = load_scene(&options)?;
+    if let Some(export) = &options.export {
+        io::scene_output(export)?.save(&scene, 0.0)?;
+    }
+    let Some(output) = &options.output else {
+        return Ok(());
+    };
+    let aspect = options.width as f64 / options.height as f64;
+    let camera = place_camera(&options, &mut scene, aspect, turntable.is_some())?;
+    add_ground(&options, &mut scene);
+    let (mut ray_tracer, (open, close)) = build_ray_tracer(&options, scene, camera);
+    let format = options.format.as_deref();
+    let aovs = &options.aovs;
+
+    let turntable_frames = turntable.map(|turntable| {
+        let timeline = turntable.timeline();
+        (timeline.first, timeline.last)
+    });
+    // Leaves out everything but the region when cropping.
+    let crop = |frame: Frame| match options.region.filter(|_| options.crop) {
+        Some(region) => frame.crop(region),
+        None => frame,
+    };
+    let Some((first, last)) = options.frames.or(turntable_frames) else {
+        let ray_tracer = ray_tracer.with_shutter(open, close);
+        let checkpoint = options.checkpoint.as_deref();
+        accumulate(&ray_tracer, &options, checkpoint, |frame| {
+            write_frame(
+                &crop(frame),
+                output,
+                format,
+                aovs,
+                options.layers.as_deref(),
+                options.sample_map.as_deref(),
+            )
+        })?;
+        io::detail(&format!(
+            "Rendered in {:.2} s",
+            started.elapsed().as_secs_f64()
+        ));
+        return Ok(());
+    };
+    let timeline = Timeline::new(first, last).with_shutter(open, close);
+    for number in timeline.frames() {
+        let path = io::numbered_path(output, number);
+        if options.skip_existing && path.exists() {
+            io::status(&format!(
+                "Skipping frame {}, {} exists",
+                number,
+                path.display()
+            ));
+            continue;
+        }
+        io::status(&format!(
+            "Rendering frame {} of {}..{}",
+            number, first, last
+        ));
+        let (open, close) = timeline.exposure(number);
+        ray_tracer = ray_tracer.with_shutter(open, close);
+        let checkpoint = options
+            .checkpoint
+            .as_ref()
+            .map(|p| io::numbered_path(p, number));
+        let aovs: Vec<(Aov, Option<PathBuf>)> = aovs
+            .iter()
+            .map(|(aov, aov_path)| {
+                (
+                    *aov,
+                    aov_path.as_ref().map(|p| io::numbered_path(p, number)),
+                )
+            })
+            .collect();
+        let layers = options
+            .layers
+            .as_ref()
+            .map(|p| io::numbered_path(p, number));
+        let sample_map = options
+            .sample_map
+            .as_ref()
+            .map(|p| io::numbered_path(p, number));
+        accumulate(&ray_tracer, &options, checkpoint.as_deref(), |frame| {
+            write_frame(
+                &crop(frame),
+                &path,
+                format,
+                &aovs,
+                layers.as_deref(),
+                sample_map.as_deref(),
+            )
+        })?;
+    }
+    io::detail(&format!(
+        "Rendered in {:.2} s",
+        started.elapsed().as_secs_f64()
+    ));
+    Ok(())
+}
+
+fn preview(args: &[String]) -> Result<()> {
+    let options = parse_args(args, PREVIEW_HELP, true);
+    let (mut scene, _) = load_scene(&options)?;
+    // Terminal characters are about twice as tall as they are wide.
+    let aspect = options.width as f64 / (2 * options.height) as f64;
+    let camera = place_camera(&options, &mut scene, aspect, false)?;
+    add_ground(&options, &mut scene);
+    let (ray_tracer, (open, close)) = build_ray_tracer(&options, scene, camera);
+    let ray_tracer = ray_tracer.with_shutter(open, close);
+    accumulate(&ray_tracer, &options, None, |frame| {
+        Console {}.dump(&frame.pixels, frame.width, frame.height)
+    })
+}
+
+// Renders passes until the sample count, the time limit or the noise level is
+// reached and hands the image to `write`, after every pass when progressive
+// and once at the end otherwise. With a checkpoint that exists the render
+// goes on from the samples saved in it, and they are saved again every
+// checkpoint interval and at the end.
+fn accumulate(
+    ray_tracer: &RayTracer,
+    options: &Options,
+    checkpoint: Option<&Path>,
+    mut write: impl FnMut(Frame) -> Result<()>,
+) -> Result<()> {
+    let budget = Budget {
+        samples: options.samples,
+        time: options.time_limit.map(Duration::from_secs_f64),
+        noise: options.noise,
+        adaptive_min_samples: options.adaptive.then_some(options.min_samples),
+    };
+    let mut accumulation = match checkpoint.filter(|path| path.exists()) {
+        Some(path) => {
+            let accumulation = CheckpointFile::new(path.to_path_buf()).load()?;
+            if !ray_tracer.can_continue(&accumulation) {
+                return Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    format!(
+                        "The checkpoint {} was saved with another resolution, region, pass size or extra passes",
+                        path.display()
+                    ),
+                ));
+            }
+            io::status(&format!(
+                "Resuming from {}, {} samples per pixel done",
+                path.display(),
+                accumulation.min_samples()
+            ));
+            accumulation
+        }
+        None => ray_tracer.accumulation(),
+    };
+    let interval = Duration::from_secs_f64(options.checkpoint_interval);
+    let mut saved = Instant::now();
+    let resumed_passes = accumulation.passes;
+    ray_tracer.render_progressive(&mut accumulation, budget, |accumulation| {
+        if options.progressive {
+            write(ray_tracer.resolve(accumulation))?;
+        }
+        match checkpoint {
+            Some(path) if saved.elapsed() >= interval => {
+                CheckpointFile::new(path.to_path_buf()).save(accumulation)?;
+                saved = Instant::now();
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    })?;
+    if let Some(path) = checkpoint {
+        CheckpointFile::new(path.to_path_buf()).save(&accumulation)?;
+    }
+    // The last pass already wrote the image when progressive.
+    if !options.progressive || accumulation.passes == resumed_passes {
+        write(ray_tracer.resolve(&accumulation))?;
+    }
+    Ok(())
+}
+
+// Loads the model and sets up its lights and motion.
+fn load_scene(options: &Options) -> Result<(Scene, Option<Turntable>)> {
+    let mut scene = Scene::from_file(options.source.clone(), options.import)?;
+    if !options.lights.is_empty() {
+        scene.lights_mut().clear();
+        for &position in &options.lights {
+            scene.add_light(Light::new(position));
+        }
+    } else if scene.lights().is_empty() {
+        // Models without lights of their own get a default one.
+        scene.add_light(Light::new(Point::new(50.0, 0.0, 150.0)));
+    }
+    if let Some(environment) = &options.environment {
+        scene.set_environment(build_environment(options, environment)?);
+    }
+    if let Some(motion) = options.motion {
+        for object in 0..scene.objects().len() {
+            scene.set_motion(object, moving_by(motion));
+        }
+    }
+    let turntable = options.turntable.map(Turntable::new);
+    if let Some(turntable) = turntable {
+        turntable.apply(&mut scene);
+    }
+    io::detail(&format!(
+        "{} objects, {} materials, {} lights, {} cameras",
+        scene.objects().len(),
+        scene.materials().len(),
+        scene.lights().len(),
+        scene.viewpoints().len()
+    ));
+    Ok((scene, turntable))
+}
+
+// Floor touching the bottom of the model. It is added once the camera is
+// placed and the model animated, so it neither moves nor gets framed.
+fn add_ground(options: &Options, scene: &mut Scene) {
+    let Some(color) = options.ground else {
+        return;
+    };
+    let bounds = scene.bounds();
+    let height = if bounds.is_empty() {
+        0.0
+    } else {
+        bounds.min().y
+    };
+    let material = scene.add_material(Material::new(Texture::Constant(color)));
+    let plane = Plane::new(Normal::new(0.0, 1.0, 0.0), Point::new(0.0, height, 0.0));
+    scene.add_object_with_material(Box::new(plane), material);
+}
+
+// Environment of the `--environment` option, scaled by its intensity.
+fn build_environment(options: &Options, source: &EnvironmentSource) -> Result<Environment> {
+    let intensity = options.environment_intensity;
+    Ok(match source {
+        EnvironmentSource::Constant(color) => Environment::Constant(*color * intensity),
+        EnvironmentSource::Gradient(bottom, top) => {
+            Environment::Gradient(Gradient::new(*bottom * intensity, *top * intensity))
+        }
+        EnvironmentSource::Map(path) => {
+            let map = EnvironmentMap::load(path, intensity).map_err(|error| {
+                Error::new(
+                    error.kind(),
+                    format!("Cannot read the environment {}: {}", path.display(), error),
+                )
+            })?;
+            Environment::Map(map)
+        }
+        EnvironmentSource::Sky => {
+            let mut sky = Sky::new(options.sun, options.turbidity);
+            sky.intensity *= intensity;
+            sky.sun_intensity *= intensity;
+            let elevation = sky.sun_direction().y.clamp(-1.0, 1.0).asin();
+            io::detail(&format!(
+                "Sun {:.1} degrees above the horizon",
+                elevation.to_degrees()
+            ));
+            Environment::Sky(sky)
+        }
+    })
+}
+
+// Camera with its lens and motion. Keyframes of an animation file are
+// applied to the scene as well.
+fn place_camera(
+    options: &Options,
+    scene: &mut Scene,
+    aspect: f64,
+    turntable: bool,
+) -> Result<Camera> {
+    // Without a camera position the model file's first camera is used,
+    // otherwise everything is framed from the view direction.
+    let viewpoint = scene
+        .viewpoints()
+        .first()
+        .filter(|_| options.view.is_none() && !turntable);
+    let mut camera = match (options.camera_position, viewpoint) {
+        (Some(position), _) => {
+            let target = options.look_at.unwrap_or(scene.bounds().center());
+            if (target - position).length() == 0.0 {
+                return Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    "The camera is at the center of the model, give it a --look-at target",
+                ));
+            }
+            Camera::look_at(position, target, options.field_of_view, aspect)
+        }
+        (None, Some(viewpoint)) => viewpoint.camera(aspect),
+        (None, None) => {
+            let view = options.view.unwrap_or(Vector::new(0.0, 0.0, -1.0));
+            Camera::frame_all(scene.bounds(), view, options.margin, aspect)
+        }
+    };
+    let radius = options
+        .aperture
+        .or(options.f_stop.map(|f_stop| camera.aperture_radius(f_stop)));
+    if let Some(radius) = radius {
+        let shape = if options.aperture_blades >= 3 {
+            Aperture::Polygon {
+                blades: options.aperture_blades,
+                rotation: options.aperture_rotation,
+            }
+        } else {
+            Aperture::Circle
+        };
+        // Without a focus distance the view frame is in focus.
+        let focus_distance = options.focus_distance.unwrap_or(camera.view_distance());
+        camera = camera.with_lens(Lens::new(radius, focus_distance).with_aperture(shape));
+    }
+    if let Some(camera_motion) = options.camera_motion {
+        camera = camera.with_motion(moving_by(camera_motion));
+    }
+    if let Some(animation) = &options.animation {
+        camera = AnimationFile::new(animation.clone())
+            .load()
+            .map_err(|error| {
+                Error::new(
+                    error.kind(),
+                    format!(
+                        "Cannot read the animation {}: {}",
+                        animation.display(),
+                        error
+                    ),
+                )
+            })?
+            .apply(scene, camera)?;
+    }
+    Ok(camera)
+}
+
+// Ray tracer for the options and the interval the shutter is open for.
+fn build_ray_tracer(options: &Options, scene: Scene, camera: Camera) -> (RayTracer, (f64, f64)) {
+    let samples = options
+        .samples
+        .map_or("unlimited".to_string(), |samples| samples.to_string());
+    io::detail(&format!(
+        "{}x{} pixels, {} samples in passes of {}, {} threads",
+        options.width, options.height, samples, options.pass_samples, options.threads
+    ));
+    let mut ray_tracer = RayTracer::new(scene, camera, options.width, options.height)
+        .with_samples(options.pass_samples)
+        .with_threads(options.threads)
+        .with_environment_samples(options.environment_samples)
+        .with_aovs(options.aovs.iter().map(|(aov, _)| *aov).collect());
+    if let Some(region) = options.region {
+        ray_tracer = ray_tracer.with_region(region);
+    }
+    if let Some(background) = options.background {
+        ray_tracer = ray_tracer.with_background(background);
+    }
+    if options.progressive {
+        ray_tracer = ray_tracer.with_doubling_passes();
+    }
+    // Something that moves is blurred over its whole path unless the shutter
+    // says otherwise.
+    let moving =
+        options.motion.is_some() || options.camera_motion.is_some() || options.animation.is_some();
+    let shutter = options
+        .shutter
+        .unwrap_or(if moving { (0.0, 1.0) } else { (0.0, 0.0) });
+    if let Some((x, y)) = options.autofocus {
+        ray_tracer = ray_tracer
+            .with_shutter(shutter.0, shutter.1)
+            .with_autofocus(x, y);
+    }
+    (ray_tracer, shutter)
+}
+
+// Writes the image, every extra pass that has a path of its own and the
+// multi-layer EXR.
+fn write_frame(
+    frame: &Frame,
+    output: &Path,
+    format: Option<&str>,
+    aovs: &[(Aov, Option<PathBuf>)],
+    layers: Option<&Path>,
+    sample_map: Option<&Path>,
+) -> Result<()> {
+    let image = match format {
+        Some(format) => io::image_output_as(output, format)?,
+        None => io::image_output(output)?,
+    };
+    image.dump(&frame.pixels, frame.width, frame.height)?;
+    for (aov, path) in aovs {
+        if let (Some(path), Some(pixels)) = (path, frame.aov(*aov)) {
+            io::image_output(path)?.dump(pixels, frame.width, frame.height)?;
+        }
+    }
+    if let Some(layers) = layers {
+        io::exr_image::write_layers(layers, frame)?;
+    }
+    if let Some(sample_map) = sample_map {
+        io::image_output(sample_map)?.dump(&frame.sample_map(), frame.width, frame.height)?;
+    }
+    Ok(())
+}
+
+// Straight movement by `distance` from time 0 to 1.
+fn moving_by(distance: Vector) -> Motion {
+    let mut end = Trs::identity();
+    end.translation = distance;
+    Motion::linear(Trs::identity(), end)
+}
+
+// Loads a model and reports its size and the problems of its meshes. Fails
+// when the model has errors.
+fn info(args: &[String]) -> Result<()> {
+    let mut source = None;
+    let mut import = ImportOptions::default();
+    for arg in args {
+        if parse_import_option(arg, &mut import, INFO_HELP) || parse_verbosity(arg) {
+            continue;
+        }
+        if arg == "--help" {
+            println!("{}", INFO_HELP);
+            std::process::exit(0);
+        } else if let Some(path) = arg.strip_prefix("--source=") {
+            source = Some(parse_source(path, INFO_HELP));
+        } else {
+            usage_error(&format!("Unknown argument {}", arg), INFO_HELP);
+        }
+    }
+    let Some(source) = source else {
+        usage_error("The --source argument is required", INFO_HELP);
+    };
+
+    let scene = Scene::from_file(source, import)?;
+    let checks: Vec<(usize, MeshCheck)> = scene
+        .objects()
+        .iter()
+        .enumerate()
+        .filter_map(|(index, object)| Some((index, MeshCheck::new(&object.mesh_at(0.0)?))))
+        .collect();
+    let vertices: usize = checks.iter().map(|(_, check)| check.vertices).sum();
+    let triangles: usize = checks.iter().map(|(_, check)| check.triangles).sum();
+    println!(
+        "{} objects, {} of them triangle meshes",
+        scene.objects().len(),
+        checks.len()
+    );
+    println!("{} vertices, {} triangles", vertices, triangles);
+    let bounds = scene.bounds();
+    if bounds.is_finite() {
+        let (min, max) = (bounds.min(), bounds.max());
+        println!(
+            "Bounds from ({}, {}, {}) to ({}, {}, {})",
+            min.x, min.y, min.z, max.x, max.y, max.z
+        );
+    }
+
+    for (index, check) in &checks {
+        println!(
+            "Object {}: {} vertices, {} triangles",
+            index, check.vertices, check.triangles
+        );
+        let problems = [
+            ("error", check.degenerate, "degenerate triangles"),
+            ("error", check.non_manifold_edges, "non-manifold edges"),
+            (
+                "error",
+                check.inconsistent_winding,
+                "edges with inconsistent winding",
+            ),
+            ("error", check.unnormalized_normals, "unnormalized normals"),
+            ("warning", check.boundary_edges, "boundary edges"),
+        ];
+        for (severity, count, problem) in problems {
+            if count > 0 {
+                println!("  {}: {} {}", severity, count, problem);
+            }
+        }
+        if !check.has_normals {
+            println!("  warning: no vertex normals, the mesh is flat shaded");
+        }
+    }
+
+    let errors: usize = checks.iter().map(|(_, check)| check.errors()).sum();
+    if errors > 0 {
+        return Err(Error::new(
+            ErrorKind::InvalidData,
+            format!("{} errors found", errors),
+        ));
+    }
+    println!("No errors found");
+    Ok(())
+}
+
+// Loads a model and writes its meshes to another file.
+fn convert(args: &[String]) -> Result<()> {
+    let mut source = None;
+    let mut output = None;
+    let mut import = ImportOptions::default();
+    for arg in args {
+        if parse_import_option(arg, &mut import, CONVERT_HELP) || parse_verbosity(arg) {
+            continue;
+        }
+        if arg == "--help" {
+            println!("{}", CONVERT_HELP);
+            std::process::exit(0);
+        } else if let Some(path) = arg.strip_prefix("--source=") {
+            source = Some(parse_source(path, CONVERT_HELP));
+        } else if let Some(path) = arg.strip_prefix("--output=") {
+            let path = PathBuf::from(path);
+            let extension = path.extension().and_then(OsStr::to_str);
+            if !matches!(extension, Some("obj" | "ply")) {
+                usage_error("Incorrect output file format", CONVERT_HELP);
+            }
+            output = Some(path);
+        } else {
+            usage_error(&format!("Unknown argument {}", arg), CONVERT_HELP);
+        }
+    }
+    let (Some(source), Some(output)) = (source, output) else {
+        usage_error(
+            "The --source and --output arguments are required",
+            CONVERT_HELP,
+        );
+    };
 
+    let scene = Scene::from_file(source, import)?;
+    io::scene_output(&output)?.save(&scene, 0.0)
+}
+
+// Options of the render and preview commands.
+fn parse_args(args: &[String], help: &str, preview: bool) -> Options {
     let mut source: Option<PathBuf> = None;
+    let mut import = ImportOptions::default();
     let mut output: Option<PathBuf> = None;
-    for arg in std::env::args() {
+    let mut format = None;
+    let mut export = None;
+    let (mut width, mut height) = if preview { (80, 32) } else { (720, 576) };
+    let mut region = None;
+    let mut crop = false;
+    let mut samples = None;
+    let mut pass_samples = None;
+    let mut time_limit = None;
+    let mut noise = None;
+    let mut progressive = false;
+    let mut adaptive = false;
+    let mut min_samples = 16;
+    let mut sample_map = None;
+    let mut checkpoint = None;
+    let mut checkpoint_interval = 60.0;
+    let mut threads = std::thread::available_parallelism().map_or(1, usize::from);
+    let mut lights = vec![];
+    let mut background = None;
+    let mut ground = None;
+    let mut environment = None;
+    let mut environment_intensity = 1.0;
+    let mut sun = Vector::new(1.0, 2.0, 1.0);
+    let mut turbidity = 3.0;
+    let mut environment_samples = 16;
+    let mut aovs = vec![];
+    let mut layers = None;
+    let mut aperture = None;
+    let mut f_stop = None;
+    let mut focus_distance = None;
+    let mut autofocus = None;
+    let mut aperture_blades = 0;
+    let mut aperture_rotation = 0.0;
+    let mut shutter = None;
+    let mut motion = None;
+    let mut camera_motion = None;
+    let mut animation = None;
+    let mut frames = None;
+    let mut skip_existing = false;
+    let mut turntable = None;
+    let mut camera_position: Option<Point> = None;
+    let mut look_at: Option<Point> = None;
+    let mut field_of_view = 40.0;
+    let mut view = None;
+    let mut margin = 0.1;
+    for arg in args {
+        if parse_import_option(arg, &mut import, help) || parse_verbosity(arg) {
+            continue;
+        }
+        let name = arg.split('=').next().unwrap_or_default();
+        if preview && RENDER_ONLY.contains(&name) {
+            usage_error(&format!("{} is not available for previews", name), help);
+        }
         if arg == "--help" {
-            println!("{}", HELP_MSG);
+            println!("{}", help);
             std::process::exit(0);
-        } else if arg.starts_with("--source=") {
-            if let Some(path) = arg.split('=').nth(1) {
-                let path = PathBuf::from(path);
-                if Some(OsStr::new("obj")) == path.extension() {
-                    if path.exists() {
-                        source = Some(path);
-                    } else {
-                        println!("The source file does not exist.\n\n{}", HELP_MSG);
-                        std::process::exit(1);
+        } else if let Some(path) = arg.strip_prefix("--source=") {
+            source = Some(parse_source(path, help));
+        } else if let Some(path) = arg.strip_prefix("--output=") {
+            output = Some(PathBuf::from(path));
+        } else if let Some(value) = arg.strip_prefix("--format=") {
+            if !matches!(value, "ppm" | "png" | "pam" | "exr") {
+                usage_error("Incorrect image format", help);
+            }
+            format = Some(value.to_string());
+        } else if let Some(path) = arg.strip_prefix("--export=") {
+            let path = PathBuf::from(path);
+            let extension = path.extension().and_then(OsStr::to_str);
+            if matches!(extension, Some("obj" | "ply")) {
+                export = Some(path);
+            } else {
+                usage_error("Incorrect export file format", help);
+            }
+        } else if let Some(value) = arg.strip_prefix("--resolution=") {
+            let size = value.split_once('x').map(|(w, h)| (w.parse(), h.parse()));
+            match size {
+                Some((Ok(w), Ok(h))) if w > 0 && h > 0 => (width, height) = (w, h),
+                _ => usage_error("Incorrect resolution", help),
+            }
+        } else if let Some(value) = arg.strip_prefix("--region=") {
+            let values: std::result::Result<Vec<usize>, _> =
+                value.split(',').map(str::parse).collect();
+            match values.as_deref() {
+                Ok(&[x, y, w, h]) if w > 0 && h > 0 => region = Some(Region::new(x, y, w, h)),
+                _ => usage_error("Incorrect region", help),
+            }
+        } else if arg == "--crop" {
+            crop = true;
+        } else if let Some(value) = arg.strip_prefix("--samples=") {
+            match value.parse::<usize>() {
+                Ok(value) if value > 0 => samples = Some(value),
+                _ => usage_error("Incorrect number of samples", help),
+            }
+        } else if let Some(value) = arg.strip_prefix("--pass-samples=") {
+            match value.parse::<usize>() {
+                Ok(value) if value > 0 => pass_samples = Some(value),
+                _ => usage_error("Incorrect number of pass samples", help),
+            }
+        } else if let Some(value) = arg.strip_prefix("--time-limit=") {
+            match value.parse::<f64>() {
+                Ok(value) if value > 0.0 && value.is_finite() => time_limit = Some(value),
+                _ => usage_error("Incorrect time limit", help),
+            }
+        } else if let Some(value) = arg.strip_prefix("--noise=") {
+            match value.parse::<f64>() {
+                Ok(value) if value > 0.0 && value.is_finite() => noise = Some(value),
+                _ => usage_error("Incorrect noise level", help),
+            }
+        } else if arg == "--progressive" {
+            progressive = true;
+        } else if arg == "--adaptive" {
+            adaptive = true;
+        } else if let Some(value) = arg.strip_prefix("--min-samples=") {
+            match value.parse::<usize>() {
+                Ok(value) if value > 0 => min_samples = value,
+                _ => usage_error("Incorrect minimum number of samples", help),
+            }
+        } else if let Some(value) = arg.strip_prefix("--sample-map=") {
+            sample_map = Some(PathBuf::from(value));
+        } else if let Some(value) = arg.strip_prefix("--checkpoint=") {
+            checkpoint = Some(PathBuf::from(value));
+        } else if let Some(value) = arg.strip_prefix("--checkpoint-interval=") {
+            match value.parse::<f64>() {
+                Ok(value) if value >= 0.0 && value.is_finite() => checkpoint_interval = value,
+                _ => usage_error("Incorrect checkpoint interval", help),
+            }
+        } else if let Some(value) = arg.strip_prefix("--threads=") {
+            match value.parse::<usize>() {
+                Ok(value) if value > 0 => threads = value,
+                _ => usage_error("Incorrect number of threads", help),
+            }
+        } else if let Some(value) = arg.strip_prefix("--light=") {
+            lights.push(parse_vector(value, "light position", help).into());
+        } else if arg == "--ground" {
+            ground = Some(Color::gray(0.8));
+        } else if let Some(value) = arg.strip_prefix("--ground=") {
+            ground = Some(parse_color(value, "ground color", help));
+        } else if let Some(value) = arg.strip_prefix("--environment=") {
+            environment = Some(parse_environment(value, help));
+        } else if let Some(value) = arg.strip_prefix("--environment-intensity=") {
+            match value.parse::<f64>() {
+                Ok(value) if value >= 0.0 && value.is_finite() => environment_intensity = value,
+                _ => usage_error("Incorrect environment intensity", help),
+            }
+        } else if let Some(value) = arg.strip_prefix("--sun=") {
+            sun = parse_vector(value, "sun direction", help);
+            if sun.length() == 0.0 {
+                usage_error("Incorrect sun direction", help);
+            }
+        } else if let Some(value) = arg.strip_prefix("--turbidity=") {
+            match value.parse::<f64>() {
+                Ok(value) if (1.0..=20.0).contains(&value) => turbidity = value,
+                _ => usage_error("Incorrect turbidity", help),
+            }
+        } else if let Some(value) = arg.strip_prefix("--environment-samples=") {
+            match value.parse::<usize>() {
+                Ok(value) if value > 0 => environment_samples = value,
+                _ => usage_error("Incorrect number of environment samples", help),
+            }
+        } else if let Some(value) = arg.strip_prefix("--background=") {
+            let color = parse_vector(value, "background color", help);
+            if ![color.x, color.y, color.z]
+                .iter()
+                .all(|channel| (0.0..=1.0).contains(channel))
+            {
+                usage_error("Incorrect background color", help);
+            }
+            background = Some(Color::new(color.x, color.y, color.z));
+        } else if let Some(value) = arg.strip_prefix("--aov=") {
+            let (name, path) = match value.split_once(':') {
+                Some((name, path)) => (name, Some(PathBuf::from(path))),
+                None => (value, None),
+            };
+            match Aov::from_name(name) {
+                Some(aov) => aovs.push((aov, path)),
+                None => usage_error(&format!("Unknown pass {}", name), help),
+            }
+        } else if let Some(value) = arg.strip_prefix("--layers=") {
+            layers = Some(PathBuf::from(value));
+        } else if let Some(value) = arg.strip_prefix("--aperture=") {
+            aperture = Some(parse_positive(value, "aperture radius", help));
+        } else if let Some(value) = arg.strip_prefix("--f-stop=") {
+            f_stop = Some(parse_positive(value, "f-stop", help));
+        } else if let Some(value) = arg.strip_prefix("--focus-distance=") {
+            focus_distance = Some(parse_positive(value, "focus distance", help));
+        } else if let Some(value) = arg.strip_prefix("--autofocus=") {
+            match value.split_once(',') {
+                Some((x, y)) => {
+                    autofocus = Some((
+                        parse_value(x, "autofocus pixel", help),
+                        parse_value(y, "autofocus pixel", help),
+                    ))
+                }
+                None => usage_error("Incorrect autofocus pixel", help),
+            }
+        } else if let Some(value) = arg.strip_prefix("--aperture-blades=") {
+            match value.parse::<u32>() {
+                Ok(value) if value >= 3 => aperture_blades = value,
+                _ => usage_error("Incorrect number of aperture blades", help),
+            }
+        } else if let Some(value) = arg.strip_prefix("--aperture-rotation=") {
+            aperture_rotation = parse_value(value, "aperture rotation", help);
+        } else if let Some(value) = arg.strip_prefix("--shutter=") {
+            match value.split_once(',') {
+                Some((open, close)) => {
+                    let open = parse_value(open, "shutter interval", help);
+                    let close = parse_value(close, "shutter interval", help);
+                    if close < open {
+                        usage_error("The shutter closes before it opens", help);
                     }
-                } else {
-                    println!("Incorrect input file format\n\n{}", HELP_MSG);
-                    std::process::exit(0);
+                    shutter = Some((open, close));
                 }
+                None => usage_error("Incorrect shutter interval", help),
             }
-        } else if arg.starts_with("--output=") {
-            if let Some(path) = arg.split('=').nth(1) {
-                let path = PathBuf::from(path);
-                if Some(OsStr::new("ppm")) == path.extension() {
-                    output = Some(path);
-                } else {
-                    println!("Incorrect output file format\n\n{}", HELP_MSG);
-                    std::process::exit(0);
+        } else if let Some(value) = arg.strip_prefix("--motion=") {
+            motion = Some(parse_vector(value, "motion", help));
+        } else if let Some(value) = arg.strip_prefix("--camera-motion=") {
+            camera_motion = Some(parse_vector(value, "camera motion", help));
+        } else if let Some(value) = arg.strip_prefix("--animation=") {
+            animation = Some(PathBuf::from(value));
+        } else if let Some(value) = arg.strip_prefix("--frames=") {
+            match value.split_once('-') {
+                Some((first, last)) => {
+                    let first = parse_value(first, "frame range", help);
+                    let last = parse_value(last, "frame range", help);
+                    if last < first {
+                        usage_error("The frame range ends before it starts", help);
+                    }
+                    frames = Some((first, last));
                 }
+                None => {
+                    let frame = parse_value(value, "frame range", help);
+                    frames = Some((frame, frame));
+                }
+            }
+        } else if arg == "--skip-existing" {
+            skip_existing = true;
+        } else if let Some(value) = arg.strip_prefix("--turntable=") {
+            match value.parse::<usize>() {
+                Ok(value) if value > 0 => turntable = Some(value),
+                _ => usage_error("Incorrect number of turntable frames", help),
             }
+        } else if let Some(value) = arg.strip_prefix("--camera=") {
+            camera_position = Some(parse_vector(value, "camera position", help).into());
+        } else if let Some(value) = arg.strip_prefix("--look-at=") {
+            look_at = Some(parse_vector(value, "camera target", help).into());
+        } else if let Some(value) = arg.strip_prefix("--fov=") {
+            match value.parse::<f64>() {
+                Ok(value) if value > 0.0 && value < 180.0 => field_of_view = value,
+                _ => usage_error("Incorrect field of view", help),
+            }
+        } else if let Some(value) = arg.strip_prefix("--view=") {
+            let direction = parse_vector(value, "view direction", help);
+            if direction.length() == 0.0 {
+                usage_error("Incorrect view direction", help);
+            }
+            view = Some(direction);
+        } else if let Some(value) = arg.strip_prefix("--margin=") {
+            match value.parse::<f64>() {
+                Ok(value) if value >= 0.0 && value.is_finite() => margin = value,
+                _ => usage_error("Incorrect margin", help),
+            }
+        } else {
+            usage_error(&format!("Unknown argument {}", arg), help);
+        }
+    }
+
+    let Some(source) = source else {
+        usage_error("The --source argument is required", help);
+    };
+    if region.is_some_and(|region| !region.fits(width, height)) {
+        usage_error("The region does not fit in the image", help);
+    }
+    if autofocus.is_some_and(|(x, y)| x >= width || y >= height) {
+        usage_error("The autofocus pixel is outside the image", help);
+    }
+    if adaptive && noise.is_none() {
+        usage_error("The --adaptive argument needs a --noise level", help);
+    }
+    if let (Some(position), Some(target)) = (camera_position, look_at) {
+        if (target - position).length() == 0.0 {
+            usage_error("The camera cannot look at its own position", help);
+        }
+    }
+    if crop && region.is_none() {
+        usage_error("The --crop argument needs a --region", help);
+    }
+    if !preview && output.is_none() && export.is_none() {
+        usage_error("The --output or --export argument is required", help);
+    }
+    if let (Some(path), None) = (&output, &format) {
+        let extension = path.extension().and_then(OsStr::to_str);
+        if !matches!(extension, Some("ppm" | "png" | "pam" | "exr")) {
+            usage_error("Incorrect output file format", help);
+        }
+    }
+    // A time limit or noise level alone renders until it is reached, passes
+    // are kept small so that they and checkpoints are not overshot by much.
+    let open_ended = time_limit.is_some() || noise.is_some();
+    let samples = samples.or(if open_ended { None } else { Some(1) });
+    let in_passes = open_ended || checkpoint.is_some() || progressive;
+    let pass_samples = pass_samples.unwrap_or(match samples {
+        Some(samples) if !in_passes => samples,
+        _ => 1,
+    });
+    Options {
+        source,
+        import,
+        output,
+        format,
+        export,
+        width,
+        height,
+        region,
+        crop,
+        samples,
+        pass_samples,
+        time_limit,
+        noise,
+        progressive,
+        adaptive,
+        min_samples,
+        sample_map,
+        checkpoint,
+        checkpoint_interval,
+        threads,
+        lights,
+        ground,
+        environment,
+        environment_intensity,
+        sun,
+        turbidity,
+        environment_samples,
+        background,
+        aovs,
+        layers,
+        aperture,
+        f_stop,
+        focus_distance,
+        autofocus,
+        aperture_blades,
+        aperture_rotation,
+        shutter,
+        motion,
+        camera_motion,
+        animation,
+        frames,
+        skip_existing,
+        turntable,
+        camera_position,
+        look_at,
+        field_of_view,
+        view,
+        margin,
+    }
+}
+
+// Reports wrong arguments with the help of the command and exits.
+fn usage_error(message: &str, help: &str) -> ! {
+    eprintln!("error: {}\n\n{}", message, help);
+    std::process::exit(2);
+}
+
+// Model file given with `--source`, exits when its format is not supported.
+fn parse_source(path: &str, help: &str) -> PathBuf {
+    let path = PathBuf::from(path);
+    let extension = path.extension().and_then(OsStr::to_str);
+    if !matches!(extension, Some("glb" | "gltf" | "obj" | "ply" | "stl")) {
+        usage_error("Incorrect input file format", help);
+    }
+    path
+}
+
+// Reads the up axis, handedness, unit scale, recentering and crease angle
+// flags. Returns whether `arg` was one of them.
+fn parse_import_option(arg: &str, import: &mut ImportOptions, help: &str) -> bool {
+    if let Some(value) = arg.strip_prefix("--up=") {
+        import.up = match value {
+            "y" | "Y" => UpAxis::Y,
+            "z" | "Z" => UpAxis::Z,
+            _ => usage_error("Incorrect up axis", help),
+        };
+    } else if arg == "--flip-handedness" {
+        import.flip_handedness = true;
+    } else if let Some(value) = arg.strip_prefix("--rotate=") {
+        import.rotation = parse_vector(value, "rotation", help);
+    } else if let Some(value) = arg.strip_prefix("--unit-scale=") {
+        import.scale = parse_positive(value, "unit scale", help);
+    } else if arg == "--recenter" {
+        import.recenter = true;
+    } else if let Some(value) = arg.strip_prefix("--crease-angle=") {
+        match value.parse::<f64>() {
+            Ok(value) if value >= 0.0 && value.is_finite() => import.crease_angle = Some(value),
+            _ => usage_error("Incorrect crease angle", help),
         }
+    } else {
+        return false;
+    }
+    true
+}
+
+// Reads `--quiet` and `--verbose`. Returns whether `arg` was one of them.
+fn parse_verbosity(arg: &str) -> bool {
+    match arg {
+        "--quiet" => io::set_verbosity(Verbosity::Quiet),
+        "--verbose" => io::set_verbosity(Verbosity::Verbose),
+        _ => return false,
+    }
+    true
+}
+
+fn parse_value<T: std::str::FromStr>(value: &str, name: &str, help: &str) -> T {
+    value
+        .parse()
+        .unwrap_or_else(|_| usage_error(&format!("Incorrect {}", name), help))
+}
+
+// A finite number above zero.
+fn parse_positive(value: &str, name: &str, help: &str) -> f64 {
+    match value.parse::<f64>() {
+        Ok(value) if value > 0.0 && value.is_finite() => value,
+        _ => usage_error(&format!("Incorrect {}", name), help),
+    }
+}
+
+fn parse_color(value: &str, name: &str, help: &str) -> Color {
+    let Vector { x, y, z } = parse_vector(value, name, help);
+    Color::new(x, y, z)
+}
+
+// `sky`, an image file, a color or two colors of a gradient.
+fn parse_environment(value: &str, help: &str) -> EnvironmentSource {
+    if value == "sky" {
+        return EnvironmentSource::Sky;
+    }
+    let path = PathBuf::from(value);
+    let extension = path.extension().and_then(OsStr::to_str);
+    if matches!(extension, Some("hdr" | "pfm")) {
+        return EnvironmentSource::Map(path);
+    }
+    match value.split_once(':') {
+        Some((bottom, top)) => EnvironmentSource::Gradient(
+            parse_color(bottom, "environment color", help),
+            parse_color(top, "environment color", help),
+        ),
+        None => EnvironmentSource::Constant(parse_color(value, "environment color", help)),
     }
+}
 
-    if source.is_none() || output.is_none() {
-        println!("All required arguments is not provided.\n\n{}", HELP_MSG);
-        std::process::exit(0);
+fn parse_vector(value: &str, name: &str, help: &str) -> Vector {
+    let components: std::result::Result<Vec<f64>, _> = value.split(',').map(str::parse).collect();
+    match components.as_deref() {
+        Ok(&[x, y, z]) if [x, y, z].iter().all(|component| component.is_finite()) => {
+            Vector::new(x, y, z)
+        }
+        _ => usage_error(&format!("Incorrect {}", name), help),
     }
-    (source.unwrap(), output.unwrap())
 }
@@ -1,29 +1,86 @@
+pub(crate) mod accumulation;
+pub(crate) mod animation;
+pub(crate) mod aov;
 pub(crate) mod camera;
+pub(crate) mod color;
+pub(crate) mod environment;
+pub(crate) mod frame;
 pub(crate) mod light;
+pub(crate) mod material;
+pub(crate) mod procedural;
+pub(crate) mod random;
+pub(crate) mod sampling;
 pub(crate) mod scene;
+pub(crate) mod texture;
 pub(crate) mod viewframe;
 
+use std::f64::consts::PI;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+use accumulation::Accumulation;
+use aov::{Aov, Surface};
 use camera::Camera;
+use color::{Color, Pixel};
+use frame::{Frame, Region};
+use random::Random;
 use scene::Scene;
 
-use crate::geometry::normal::Normal;
-use crate::geometry::point::Point;
+use crate::geometry::hit::{Hit, Interval};
 use crate::geometry::ray::Ray;
+use crate::geometry::vector::Vector;
 use crate::geometry::Intersect;
-use crate::geometry::Intersection;
-use crate::geometry::NormalAtPoint;
 use crate::geometry::Transform;
 
-use crate::io::Output;
+use crate::io::{detail, status};
+
+const SHADOW_BIAS: f64 = 1e-6;
 
-pub(crate) trait RayTracable: Intersect + NormalAtPoint + Transform {}
-impl<T> RayTracable for T where T: Intersect + NormalAtPoint + Transform {}
+// Pixels of one image row, the squared luminance of their samples and the
+// same row of every extra pass.
+type Row = (Vec<Pixel>, Vec<f64>, Vec<Vec<Pixel>>);
+
+// When progressive rendering stops: once every pixel has `samples` samples,
+// once a pass ends after `time` has passed or once the estimated noise of
+// every pixel is down to `noise`. Without any limit it never stops.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Budget {
+    pub(crate) samples: Option<usize>,
+    pub(crate) time: Option<Duration>,
+    pub(crate) noise: Option<f64>,
+    // Samples adaptively when given with `noise`: a pixel stops on its own
+    // once it has at least this many samples and its noise is down to the
+    // level, instead of going on until every pixel is.
+    pub(crate) adaptive_min_samples: Option<usize>,
+}
+
+pub(crate) trait RayTracable: Intersect + Transform {}
+impl<T> RayTracable for T where T: Intersect + Transform {}
 
 pub(crate) struct RayTracer {
     scene: Scene,
     camera: Camera,
     width: usize,
     height: usize,
+    // Camera rays per pixel in every pass, spread over the pixel for
+    // anti-aliasing.
+    samples: usize,
+    // Directions gathered from the environment at every hit.
+    environment_samples: usize,
+    // When set, pixels are composited over this color and become opaque.
+    background: Option<Color>,
+    // Extra passes rendered into every frame.
+    aovs: Vec<Aov>,
+    // Times the shutter opens and closes. Camera rays are spread over the
+    // interval, which blurs anything that moves in between.
+    shutter: (f64, f64),
+    // Rows are rendered on this many threads at once.
+    threads: usize,
+    // Part of the image that is rendered, the rest stays transparent.
+    region: Region,
+    // Every progressive pass after the first takes as many samples as all
+    // passes before it, so the samples per pixel double with each pass.
+    doubling: bool,
 }
 
 impl RayTracer {
@@ -33,73 +90,560 @@ impl RayTracer {
             camera,
             width,
             height,
+            samples: 1,
+            environment_samples: 16,
+            background: None,
+            aovs: Vec::new(),
+            shutter: (0.0, 0.0),
+            threads: 1,
+            region: Region::new(0, 0, width, height),
+            doubling: false,
+        }
+    }
+
+    pub(crate) fn with_aovs(mut self, aovs: Vec<Aov>) -> RayTracer {
+        self.aovs = aovs;
+        self
+    }
+
+    pub(crate) fn with_samples(mut self, samples: usize) -> RayTracer {
+        self.samples = samples.max(1);
+        self
+    }
+
+    pub(crate) fn with_background(mut self, background: Color) -> RayTracer {
+        self.background = Some(background);
+        self
+    }
+
+    pub(crate) fn with_environment_samples(mut self, samples: usize) -> RayTracer {
+        self.environment_samples = samples;
+        self
+    }
+
+    pub(crate) fn with_threads(mut self, threads: usize) -> RayTracer {
+        self.threads = threads.max(1);
+        self
+    }
+
+    // Renders only the pixels inside `region`, which must fit the image.
+    pub(crate) fn with_region(mut self, region: Region) -> RayTracer {
+        assert!(
+            region.fits(self.width, self.height),
+            "Region {:?} is outside of the {}x{} image",
+            region,
+            self.width,
+            self.height
+        );
+        self.region = region;
+        self
+    }
+
+    pub(crate) fn with_doubling_passes(mut self) -> RayTracer {
+        self.doubling = true;
+        self
+    }
+
+    pub(crate) fn with_shutter(mut self, open: f64, close: f64) -> RayTracer {
+        self.shutter = (open, close.max(open));
+        self
+    }
+
+    // Focuses the camera on whatever is seen through the center of pixel
+    // `x`, `y`, counted from the top left. Nothing changes when the pixel
+    // shows no object.
+    pub(crate) fn with_autofocus(mut self, x: usize, y: usize) -> RayTracer {
+        // Measure through the lens center so the result does not depend on
+        // the aperture.
+        let point = self.camera.view_frame.point_on_pixel(
+            x as f64 + 0.5,
+            self.height as f64 - 0.5 - y as f64,
+            self.width,
+            self.height,
+        );
+        let ray = Ray::new(
+            self.camera.position,
+            (point - self.camera.position).normalize(),
+        )
+        .with_time(self.shutter.0);
+        if let Some((_, hit)) = self.trace(&ray, Interval::positive()) {
+            self.camera.lens.focus_distance = self.camera.depth(hit.point);
+        }
+        self
+    }
+
+    // Empty accumulation for progressive passes of this ray tracer.
+    pub(crate) fn accumulation(&self) -> Accumulation {
+        Accumulation::new(
+            self.width,
+            self.height,
+            self.region,
+            self.samples,
+            self.doubling,
+            &self.aovs,
+        )
+    }
+
+    // Whether `accumulation`, such as one read from a checkpoint, was made
+    // with the image size, region, pass size and extra passes of this ray
+    // tracer and can be continued by it.
+    pub(crate) fn can_continue(&self, accumulation: &Accumulation) -> bool {
+        accumulation.width == self.width
+            && accumulation.height == self.height
+            && accumulation.region == self.region
+            && accumulation.pass_samples == self.samples
+            && accumulation.doubling == self.doubling
+            && accumulation
+                .aovs
+                .iter()
+                .map(|(aov, _)| *aov)
+                .eq(self.aovs.iter().copied())
+    }
+
+    // Adds passes to `accumulation` until `budget` is reached, calling
+    // `after_pass` after each of them. The last pass is cut short to end on
+    // the sample budget.
+    pub(crate) fn render_progressive(
+        &self,
+        accumulation: &mut Accumulation,
+        budget: Budget,
+        mut after_pass: impl FnMut(&Accumulation) -> Result<(), std::io::Error>,
+    ) -> Result<(), std::io::Error> {
+        let started = Instant::now();
+        loop {
+            if budget.time.is_some_and(|time| started.elapsed() >= time) {
+                return Ok(());
+            }
+            let plan = self.plan_pass(accumulation, &budget);
+            let left = plan.iter().filter(|&&samples| samples > 0).count();
+            if left == 0 {
+                return Ok(());
+            }
+            let mut message = format!(
+                "Pass {}, {} samples per pixel so far",
+                accumulation.passes + 1,
+                accumulation.min_samples()
+            );
+            let noise = accumulation.max_noise();
+            if budget.noise.is_some() && noise.is_finite() {
+                message += &format!(", noise {:.4}", noise);
+            }
+            if budget.adaptive_min_samples.is_some() {
+                message += &format!(", {} pixels left", left);
+            }
+            status(&message);
+            self.render_pass(accumulation, &plan);
+            after_pass(accumulation)?;
+        }
+    }
+
+    // Samples every pixel of the image takes in the next pass, none when it
+    // is done and outside of the region. Pixels have the same number of
+    // samples unless sampling is adaptive, then noisy ones get ahead.
+    fn plan_pass(&self, accumulation: &Accumulation, budget: &Budget) -> Vec<usize> {
+        let limit = budget.samples.unwrap_or(usize::MAX);
+        let all_converged = budget
+            .noise
+            .is_some_and(|noise| accumulation.max_noise() <= noise);
+        let mut plan = vec![0; self.width * self.height];
+        let region = self.region;
+        for y in region.y..region.y + region.height {
+            for x in region.x..region.x + region.width {
+                let index = y * self.width + x;
+                let taken = accumulation.samples[index] as usize;
+                let converged = match (budget.adaptive_min_samples, budget.noise) {
+                    (Some(min_samples), Some(noise)) => {
+                        taken >= min_samples && accumulation.noise(index) <= noise
+                    }
+                    _ => all_converged,
+                };
+                if converged {
+                    continue;
+                }
+                let pass_samples = if self.doubling {
+                    self.samples.max(taken)
+                } else {
+                    self.samples
+                };
+                plan[index] = pass_samples.min(limit.saturating_sub(taken));
+            }
         }
+        plan
     }
 
-    pub(crate) fn render(&self, output: impl Output) -> Result<(), std::io::Error> {
-        let mut buff = vec![-1.0; self.width * self.height];
-        for y in 0..self.height {
-            println!("Ray-tracing row: {}/{}", y, self.height);
-            for x in 0..self.width {
-                let ray = self
-                    .camera
-                    .ray_for_pixel(x, self.height - y, self.width, self.height);
-                let traced = self.trace(&ray);
+    // Adds as many samples of every pixel as `plan` asks for. Threads take
+    // the next free row until none is left. Every pixel has its own random
+    // stream in every pass, so the image depends neither on the number of
+    // threads nor on the region.
+    pub(crate) fn render_pass(&self, accumulation: &mut Accumulation, plan: &[usize]) {
+        let region = self.region;
+        let pass = accumulation.passes;
+        let next_row = AtomicUsize::new(region.y);
+        let finished = AtomicUsize::new(0);
+        let rows: Vec<(usize, Row)> = std::thread::scope(|scope| {
+            let workers: Vec<_> = (0..self.threads.min(region.height.max(1)))
+                .map(|_| {
+                    scope.spawn(|| {
+                        let mut rows = vec![];
+                        loop {
+                            let y = next_row.fetch_add(1, Ordering::Relaxed);
+                            if y >= region.y + region.height {
+                                return rows;
+                            }
+                            rows.push((y, self.render_row(y, pass, plan)));
+                            let done = finished.fetch_add(1, Ordering::Relaxed) + 1;
+                            detail(&format!("Ray-tracing row: {}/{}", done, region.height));
+                        }
+                    })
+                })
+                .collect();
+            workers
+                .into_iter()
+                .flat_map(|worker| worker.join().unwrap())
+                .collect()
+        });
 
-                if let Some((index, intersection)) = traced {
-                    let object = self.scene.objects().get(index).unwrap();
-                    let point = ray.at(intersection.distance().unwrap());
-                    let normal = object.normal_at_point(&point, intersection);
-                    let intensity = self.light_value(normal, point, index);
-                    buff[y * self.width + x] = intensity;
+        for (y, (pixels, squares, passes)) in rows {
+            let start = y * self.width + region.x;
+            for (k, (pixel, square)) in pixels.into_iter().zip(squares).enumerate() {
+                accumulation.pixels[start + k] += pixel;
+                accumulation.squares[start + k] += square;
+                accumulation.samples[start + k] += plan[start + k] as u32;
+            }
+            for ((aov, buffer), pass) in accumulation.aovs.iter_mut().zip(passes) {
+                for (k, value) in pass.into_iter().enumerate() {
+                    let sum = &mut buffer[start + k];
+                    if aov.is_filtered() {
+                        *sum += value;
+                    } else if sum.alpha == 0.0 {
+                        *sum = value;
+                    }
                 }
             }
         }
-        output.dump(&buff, self.width, self.height)
+        accumulation.passes += 1;
     }
 
-    fn is_any_object_blocking(&self, ray: &Ray, object_id: usize) -> bool {
-        self.scene.objects().iter().enumerate().any(|(id, object)| {
-            if id == object_id {
-                return false;
+    // Image and extra passes of the samples gathered so far. Pixels without
+    // samples stay transparent.
+    pub(crate) fn resolve(&self, accumulation: &Accumulation) -> Frame {
+        let mut frame = Frame::new(accumulation.width, accumulation.height, &self.aovs);
+        for (index, &samples) in accumulation.samples.iter().enumerate() {
+            if samples == 0 {
+                continue;
             }
-            if let Some(distance) = object.intersect(ray).distance() {
-                distance > 0.
-            } else {
-                false
+            let mut pixel = accumulation.pixels[index] / samples as f64;
+            if let Some(background) = self.background {
+                pixel = Pixel::opaque(pixel.over(background));
             }
-        })
+            frame.pixels[index] = pixel;
+            frame.samples[index] = samples;
+            for ((aov, buffer), (_, sums)) in frame.aovs.iter_mut().zip(&accumulation.aovs) {
+                buffer[index] = if aov.is_filtered() {
+                    sums[index] / samples as f64
+                } else {
+                    sums[index]
+                };
+            }
+        }
+        frame
+    }
+
+    // Sums of the samples `plan` asks for of image row `y` inside the region,
+    // of their squared luminance and of every extra pass, unfiltered passes
+    // hold the first value seen.
+    fn render_row(&self, y: usize, pass: usize, plan: &[usize]) -> Row {
+        let Region { x: left, width, .. } = self.region;
+        let mut pixels = vec![Pixel::transparent(); width];
+        let mut squares = vec![0.0; width];
+        let mut rows = vec![vec![Pixel::transparent(); width]; self.aovs.len()];
+        for x in left..left + width {
+            let index = y * self.width + x;
+            let samples = plan[index];
+            let mut random = Random::with_stream(pass as u64, index as u64);
+            let mut pixel = Pixel::transparent();
+            let mut square = 0.0;
+            let mut passes = vec![Pixel::transparent(); self.aovs.len()];
+            for sample in 0..samples {
+                let (dx, dy) = subpixel_offset(sample, samples, pass, &mut random);
+                // Image rows go down while the view frame's y goes up.
+                let lens_sample = (random.next_f64(), random.next_f64());
+                let time = self.shutter_time(&mut random);
+                let ray = self.camera.ray_for_pixel(
+                    x as f64 + dx,
+                    (self.height - 1 - y) as f64 + dy,
+                    self.width,
+                    self.height,
+                    lens_sample,
+                    time,
+                );
+                let (color, surface) = self.shade(&ray, &mut random);
+                pixel += color;
+                square += color.color.luminance().powi(2);
+                let Some(surface) = surface else {
+                    continue;
+                };
+                for (aov, pass) in self.aovs.iter().zip(passes.iter_mut()) {
+                    if aov.is_filtered() {
+                        *pass += Pixel::opaque(aov.value(&surface));
+                    } else if pass.alpha == 0.0 {
+                        *pass = Pixel::opaque(aov.value(&surface));
+                    }
+                }
+            }
+            pixels[x - left] = pixel;
+            squares[x - left] = square;
+            for (row, pass) in rows.iter_mut().zip(passes) {
+                row[x - left] = pass;
+            }
+        }
+        (pixels, squares, rows)
     }
 
-    fn light_value(&self, normal: Normal, intersection_point: Point, object_id: usize) -> f64 {
+    // Moment a camera sample is taken at, uniform while the shutter is open.
+    fn shutter_time(&self, random: &mut Random) -> f64 {
+        let (open, close) = self.shutter;
+        if close > open {
+            open + (close - open) * random.next_f64()
+        } else {
+            open
+        }
+    }
+
+    // Radiance along a camera ray with the coverage it contributes, and the
+    // surface it hit. Missed rays are transparent unless there is an
+    // environment to see.
+    fn shade(&self, ray: &Ray, random: &mut Random) -> (Pixel, Option<Surface>) {
+        let Some((index, hit)) = self.trace(ray, Interval::positive()) else {
+            let pixel = match self.scene.environment() {
+                Some(environment) => Pixel::opaque(environment.background(ray.direction.into())),
+                None => Pixel::transparent(),
+            };
+            return (pixel, None);
+        };
+        let material = self.scene.material(index);
+        let hit = material.shade_normal(hit);
+        let albedo = material.diffuse.evaluate(&hit);
+        let (direct, shadow) = self.direct_light(&hit);
+        let indirect = self.environment_light(&hit, random);
+        let surface = Surface {
+            depth: self.camera.depth(hit.point),
+            normal: hit.shading_normal,
+            albedo,
+            object: index,
+            material: self.scene.material_index(index),
+            uv: hit.uv,
+            direct: albedo * direct,
+            indirect: albedo * indirect,
+            shadow,
+        };
+        (
+            Pixel::opaque(surface.direct + surface.indirect),
+            Some(surface),
+        )
+    }
+
+    fn is_any_object_blocking(&self, ray: &Ray, interval: Interval) -> bool {
         self.scene
+            .objects()
+            .iter()
+            .any(|object| object.intersect(ray, interval).is_some())
+    }
+
+    // Light from the point lights and the sun reflected by a white diffuse
+    // surface, and the share of those lights that are blocked.
+    fn direct_light(&self, hit: &Hit) -> (Color, f64) {
+        let mut blocked = 0;
+        let points = self
+            .scene
             .lights()
             .iter()
             .map(|light| {
-                let light_dir = (light.position - intersection_point).normalize();
-                let ray = Ray::new(intersection_point, light_dir);
-                if self.is_any_object_blocking(&ray, object_id) {
-                    (light_dir.dot(normal) * 0.5).max(0.0)
+                let to_light = light.position_at(hit.time) - hit.point;
+                let light_dir = to_light.normalize();
+                let ray = Ray::new(hit.point, light_dir).with_time(hit.time);
+                // Only objects between the surface and the light cast a shadow.
+                let interval = Interval::new(SHADOW_BIAS, to_light.length());
+                if self.is_any_object_blocking(&ray, interval) {
+                    blocked += 1;
+                    (light_dir.dot(hit.shading_normal) * 0.5).max(0.0)
                 } else {
-                    light_dir.dot(normal).max(0.0)
+                    light_dir.dot(hit.shading_normal).max(0.0)
                 }
             })
             .sum::<f64>()
-            .min(1.0)
+            .min(1.0);
+        let mut light = Color::gray(points);
+        let mut count = self.scene.lights().len();
+
+        let sun = self
+            .scene
+            .environment()
+            .and_then(|environment| environment.sun());
+        if let Some((direction, irradiance)) = sun {
+            count += 1;
+            let ray = Ray::new(hit.point, direction.normalize()).with_time(hit.time);
+            if self.is_any_object_blocking(&ray, Interval::new(SHADOW_BIAS, f64::INFINITY)) {
+                blocked += 1;
+            } else {
+                let cosine = Vector::from(hit.shading_normal).dot(direction);
+                light += irradiance * (cosine.max(0.0) / PI);
+            }
+        }
+        let shadow = if count == 0 {
+            0.0
+        } else {
+            blocked as f64 / count as f64
+        };
+        (light, shadow)
     }
 
-    fn trace(&self, ray: &Ray) -> Option<(usize, Intersection)> {
-        self.scene
-            .objects()
-            .iter()
-            .enumerate()
-            .map(|(i, object)| (i, object.intersect(ray)))
-            .filter(|&(_, intesection)| intesection != Intersection::DoesNotIntersect)
-            .min_by(|&(_, a), &(_, b)| {
-                a.distance()
-                    .unwrap()
-                    .partial_cmp(&b.distance().unwrap())
-                    .expect("Expected non NAN distance")
-            })
+    // Light reflected by a white diffuse surface from the environment, a
+    // Monte Carlo estimate over the hemisphere. The sun is direct light.
+    fn environment_light(&self, hit: &Hit, random: &mut Random) -> Color {
+        let Some(environment) = self.scene.environment() else {
+            return Color::black();
+        };
+        if self.environment_samples == 0 {
+            return Color::black();
+        }
+        let normal = Vector::from(hit.shading_normal);
+        let mut gathered = Color::black();
+        for _ in 0..self.environment_samples {
+            let Some(sample) = environment.sample(hit, (random.next_f64(), random.next_f64()))
+            else {
+                continue;
+            };
+            let cosine = normal.dot(sample.direction) / sample.direction.length();
+            if cosine <= 0.0 || sample.pdf <= 0.0 {
+                continue;
+            }
+            let ray = Ray::new(hit.point, sample.direction.normalize()).with_time(hit.time);
+            if !self.is_any_object_blocking(&ray, Interval::new(SHADOW_BIAS, f64::INFINITY)) {
+                gathered += sample.radiance * (cosine / (sample.pdf * PI));
+            }
+        }
+        gathered / self.environment_samples as f64
+    }
+
+    // Finds the closest hit among all objects, returning the object index.
+    fn trace(&self, ray: &Ray, interval: Interval) -> Option<(usize, Hit)> {
+        let mut closest = None;
+        let mut interval = interval;
+        for (i, object) in self.scene.objects().iter().enumerate() {
+            if let Some(hit) = object.intersect(ray, interval) {
+                interval = interval.with_max(hit.distance);
+                closest = Some((i, hit));
+            }
+        }
+        closest
+    }
+}
+
+// Position of a camera sample inside its pixel. Square sample counts are
+// jittered on a grid. A pass of a single sample goes through the pixel center
+// when it is the first pass, later ones pick a random position.
+fn subpixel_offset(sample: usize, samples: usize, pass: usize, random: &mut Random) -> (f64, f64) {
+    if samples == 1 && pass == 0 {
+        return (0.5, 0.5);
+    }
+    let strata = (samples as f64).sqrt() as usize;
+    if strata * strata == samples {
+        let (column, row) = (sample % strata, sample / strata);
+        (
+            (column as f64 + random.next_f64()) / strata as f64,
+            (row as f64 + random.next_f64()) / strata as f64,
+        )
+    } else {
+        (random.next_f64(), random.next_f64())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geometry::point::Point;
+
+    fn tracer(samples: usize) -> RayTracer {
+        let camera = Camera::look_at(
+            Point::new(0.0, 0.0, 5.0),
+            Point::new(0.0, 0.0, 0.0),
+            45.0,
+            2.0,
+        );
+        RayTracer::new(Scene::new(), camera, 4, 2).with_samples(samples)
+    }
+
+    fn budget(samples: Option<usize>, noise: Option<f64>, adaptive: Option<usize>) -> Budget {
+        Budget {
+            samples,
+            time: None,
+            noise,
+            adaptive_min_samples: adaptive,
+        }
+    }
+
+    // Adds samples of the given luminances to pixel `index`.
+    fn add_samples(accumulation: &mut Accumulation, index: usize, values: &[f64]) {
+        for &value in values {
+            accumulation.samples[index] += 1;
+            accumulation.squares[index] += value * value;
+            accumulation.pixels[index] += Pixel::new(Color::gray(value), 1.0);
+        }
+    }
+
+    #[test]
+    fn passes_end_on_the_sample_budget_inside_the_region() {
+        let tracer = tracer(4).with_region(Region::new(1, 0, 3, 1));
+        let mut accumulation = tracer.accumulation();
+        add_samples(&mut accumulation, 2, &[0.5; 8]);
+        add_samples(&mut accumulation, 3, &[0.5; 10]);
+        let plan = tracer.plan_pass(&accumulation, &budget(Some(10), None, None));
+        assert_eq!(plan, [0, 4, 2, 0, 0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn doubling_passes_match_the_samples_taken() {
+        let tracer = tracer(1).with_doubling_passes();
+        let mut accumulation = tracer.accumulation();
+        for index in 0..8 {
+            add_samples(&mut accumulation, index, &[0.5; 4]);
+        }
+        let plan = tracer.plan_pass(&accumulation, &budget(Some(6), None, None));
+        assert_eq!(plan, [2; 8]);
+        let plan = tracer.plan_pass(&accumulation, &budget(None, None, None));
+        assert_eq!(plan, [4; 8]);
+    }
+
+    #[test]
+    fn without_adaptive_sampling_every_pixel_waits_for_the_noisiest() {
+        let tracer = tracer(1);
+        let mut accumulation = tracer.accumulation();
+        for index in 0..8 {
+            add_samples(&mut accumulation, index, &[0.5; 8]);
+        }
+        let quiet = budget(None, Some(0.01), None);
+        assert_eq!(tracer.plan_pass(&accumulation, &quiet), [0; 8]);
+
+        // One noisy pixel keeps them all going.
+        add_samples(&mut accumulation, 5, &[0.0, 1.0]);
+        assert_eq!(tracer.plan_pass(&accumulation, &quiet), [1; 8]);
+    }
+
+    #[test]
+    fn adaptive_sampling_stops_pixels_below_the_noise_level() {
+        let tracer = tracer(2);
+        let mut accumulation = tracer.accumulation();
+        for index in 0..8 {
+            add_samples(&mut accumulation, index, &[0.5; 8]);
+        }
+        // Noisy, and quiet but short of the minimum samples.
+        add_samples(&mut accumulation, 1, &[0.0, 1.0].repeat(4));
+        accumulation.samples[6] = 4;
+        accumulation.pixels[6] = Pixel::new(Color::gray(2.0), 4.0);
+        accumulation.squares[6] = 1.0;
+
+        let plan = tracer.plan_pass(&accumulation, &budget(None, Some(0.01), Some(6)));
+        assert_eq!(plan, [0, 2, 0, 0, 0, 0, 2, 0]);
     }
 }
@@ -0,0 +1,132 @@
+use super::aov::Aov;
+use super::color::Pixel;
+use super::frame::Region;
+
+// Fewest samples a pixel needs before its noise is estimated. A couple of
+// alike samples, such as two misses at the edge of an object, say little.
+const NOISE_SAMPLES: u32 = 4;
+
+// Samples gathered so far by a progressive render, kept between passes and
+// saved to checkpoints. The image and the filtered passes hold sums of
+// samples, unfiltered passes the first value seen. `pass_samples`, `doubling`
+// and the region are those of the render, a checkpoint only continues a
+// render with the same ones.
+pub(crate) struct Accumulation {
+    pub(crate) width: usize,
+    pub(crate) height: usize,
+    pub(crate) region: Region,
+    pub(crate) pass_samples: usize,
+    pub(crate) doubling: bool,
+    // Passes rendered so far. Each pass draws its own random numbers.
+    pub(crate) passes: usize,
+    // Samples taken of every pixel.
+    pub(crate) samples: Vec<u32>,
+    // Sums of the squared luminance of the samples, for the noise estimate.
+    pub(crate) squares: Vec<f64>,
+    pub(crate) pixels: Vec<Pixel>,
+    pub(crate) aovs: Vec<(Aov, Vec<Pixel>)>,
+}
+
+impl Accumulation {
+    pub(crate) fn new(
+        width: usize,
+        height: usize,
+        region: Region,
+        pass_samples: usize,
+        doubling: bool,
+        aovs: &[Aov],
+    ) -> Accumulation {
+        let buffer = vec![Pixel::transparent(); width * height];
+        Accumulation {
+            width,
+            height,
+            region,
+            pass_samples,
+            doubling,
+            passes: 0,
+            samples: vec![0; width * height],
+            squares: vec![0.0; width * height],
+            pixels: buffer.clone(),
+            aovs: aovs.iter().map(|&aov| (aov, buffer.clone())).collect(),
+        }
+    }
+
+    // Fewest samples taken of a pixel in the region.
+    pub(crate) fn min_samples(&self) -> usize {
+        self.region_indices()
+            .map(|index| self.samples[index])
+            .min()
+            .unwrap_or(0) as usize
+    }
+
+    // Standard error of the mean luminance of a pixel, how far its value is
+    // likely off from the converged one. Infinite until it has enough
+    // samples.
+    pub(crate) fn noise(&self, index: usize) -> f64 {
+        let samples = self.samples[index];
+        if samples < NOISE_SAMPLES {
+            return f64::INFINITY;
+        }
+        let n = samples as f64;
+        let mean = self.pixels[index].color.luminance() / n;
+        let variance = (self.squares[index] / n - mean * mean).max(0.0) * n / (n - 1.0);
+        (variance / n).sqrt()
+    }
+
+    // Highest noise of a pixel in the region.
+    pub(crate) fn max_noise(&self) -> f64 {
+        self.region_indices()
+            .map(|index| self.noise(index))
+            .fold(0.0, f64::max)
+    }
+
+    fn region_indices(&self) -> impl Iterator<Item = usize> + '_ {
+        let region = self.region;
+        (region.y..region.y + region.height)
+            .flat_map(move |y| (0..region.width).map(move |x| y * self.width + region.x + x))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderer::color::Color;
+
+    // Adds samples of the given luminances to pixel `index`.
+    fn add_samples(accumulation: &mut Accumulation, index: usize, values: &[f64]) {
+        for &value in values {
+            accumulation.samples[index] += 1;
+            accumulation.squares[index] += value * value;
+            accumulation.pixels[index] += Pixel::new(Color::gray(value), 1.0);
+        }
+    }
+
+    #[test]
+    fn noise_is_the_standard_error_of_the_mean() {
+        let mut accumulation = Accumulation::new(3, 1, Region::new(0, 0, 3, 1), 1, false, &[]);
+        add_samples(&mut accumulation, 0, &[0.5; 3]);
+        assert_eq!(accumulation.noise(0), f64::INFINITY);
+
+        add_samples(&mut accumulation, 0, &[0.5]);
+        assert!(accumulation.noise(0) < 1e-9);
+
+        // Eight samples of 0 and 1: the variance is 8/7 * 0.25.
+        add_samples(&mut accumulation, 1, &[0.0, 1.0].repeat(4));
+        let expected = (0.25 * 8.0 / 7.0 / 8.0f64).sqrt();
+        assert!((accumulation.noise(1) - expected).abs() < 1e-9);
+
+        add_samples(&mut accumulation, 2, &[0.2; 6]);
+        assert_eq!(accumulation.min_samples(), 4);
+        assert!((accumulation.max_noise() - expected).abs() < 1e-9);
+    }
+
+    #[test]
+    fn pixels_outside_the_region_are_ignored() {
+        let mut accumulation = Accumulation::new(4, 2, Region::new(1, 1, 2, 1), 1, false, &[]);
+        add_samples(&mut accumulation, 5, &[0.1; 4]);
+        add_samples(&mut accumulation, 6, &[0.3; 5]);
+        add_samples(&mut accumulation, 0, &[0.0, 1.0]);
+        assert_eq!(accumulation.min_samples(), 4);
+        assert!(accumulation.max_noise() < 1e-9);
+    }
+}
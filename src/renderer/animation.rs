@@ -0,0 +1,245 @@
+use std::io::{Error, ErrorKind, Result};
+use std::ops::RangeInclusive;
+
+use crate::geometry::motion::{Keyframe, Motion};
+use crate::geometry::quaternion::Quaternion;
+use crate::geometry::trs::Trs;
+use crate::geometry::vector::Vector;
+use crate::geometry::Transformation;
+
+use super::camera::Camera;
+use super::scene::Scene;
+
+// What an animation track moves. Objects and lights are counted in the order
+// they were added to the scene.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Target {
+    Camera,
+    Object(usize),
+    AllObjects,
+    Light(usize),
+}
+
+// Keyframed motion for parts of a scene. Time is measured in frames.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Animation {
+    tracks: Vec<(Target, Motion)>,
+}
+
+impl Animation {
+    pub(crate) fn new() -> Animation {
+        Animation::default()
+    }
+
+    pub(crate) fn add_track(&mut self, target: Target, motion: Motion) {
+        self.tracks.push((target, motion));
+    }
+
+    #[cfg_attr(not(test), allow(dead_code))]
+    pub(crate) fn tracks(&self) -> &[(Target, Motion)] {
+        &self.tracks
+    }
+
+    // First and last keyframe over all tracks.
+    #[cfg_attr(not(test), allow(dead_code))]
+    pub(crate) fn time_range(&self) -> Option<(f64, f64)> {
+        self.tracks
+            .iter()
+            .flat_map(|(_, motion)| motion.keyframes())
+            .map(|key| key.time)
+            .fold(None, |range, time| match range {
+                Some((first, last)) => Some((f64::min(first, time), f64::max(last, time))),
+                None => Some((time, time)),
+            })
+    }
+
+    // Hands every track to the part it moves. Tracks for objects or lights
+    // that do not exist are reported as an error.
+    pub(crate) fn apply(self, scene: &mut Scene, mut camera: Camera) -> Result<Camera> {
+        for (target, motion) in self.tracks {
+            match target {
+                Target::Camera => camera = camera.with_motion(motion),
+                Target::Object(index) if index < scene.objects().len() => {
+                    scene.set_motion(index, motion)
+                }
+                Target::AllObjects => {
+                    for index in 0..scene.objects().len() {
+                        scene.set_motion(index, motion.clone());
+                    }
+                }
+                Target::Light(index) if index < scene.lights().len() => {
+                    scene.lights_mut()[index].motion = Some(motion);
+                }
+                Target::Object(index) => return Err(missing("object", index)),
+                Target::Light(index) => return Err(missing("light", index)),
+            }
+        }
+        Ok(camera)
+    }
+}
+
+fn missing(kind: &str, index: usize) -> Error {
+    Error::new(
+        ErrorKind::InvalidInput,
+        format!(
+            "animation track for {} {}, which does not exist",
+            kind, index
+        ),
+    )
+}
+
+// Frames rendered as an image sequence. Frame `n` is exposed from time
+// `n + shutter.0` to `n + shutter.1`.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Timeline {
+    pub(crate) first: usize,
+    pub(crate) last: usize,
+    pub(crate) shutter: (f64, f64),
+}
+
+impl Timeline {
+    pub(crate) fn new(first: usize, last: usize) -> Timeline {
+        Timeline {
+            first,
+            last: last.max(first),
+            shutter: (0.0, 0.0),
+        }
+    }
+
+    pub(crate) fn with_shutter(mut self, open: f64, close: f64) -> Timeline {
+        self.shutter = (open, close);
+        self
+    }
+
+    pub(crate) fn frames(&self) -> RangeInclusive<usize> {
+        self.first..=self.last
+    }
+
+    pub(crate) fn exposure(&self, frame: usize) -> (f64, f64) {
+        let time = frame as f64;
+        (time + self.shutter.0, time + self.shutter.1)
+    }
+}
+
+// Product turntable: every object spins a full turn around the vertical axis
+// through the center of their common bounds, one step per frame from frame 1
+// to `frames`.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Turntable {
+    pub(crate) frames: usize,
+}
+
+impl Turntable {
+    pub(crate) fn new(frames: usize) -> Turntable {
+        Turntable {
+            frames: frames.max(1),
+        }
+    }
+
+    pub(crate) fn timeline(&self) -> Timeline {
+        Timeline::new(1, self.frames)
+    }
+
+    // Sets the objects spinning. Their bounds then cover the whole turn, so
+    // `Camera::frame_all` keeps them in view.
+    pub(crate) fn apply(&self, scene: &mut Scene) {
+        let bounds = scene.bounds();
+        if bounds.is_empty() {
+            return;
+        }
+        let center = bounds.center();
+
+        // Slerp takes the short way round, so quarter turns are keyed. Frame
+        // `frames + 1` would repeat frame 1, which makes the sequence loop.
+        let keyframes = (0..=4)
+            .map(|quarter| {
+                let time = 1.0 + self.frames as f64 * quarter as f64 / 4.0;
+                let angle = quarter as f64 * std::f64::consts::FRAC_PI_2;
+                let rotation = Quaternion::from_axis_angle(Vector::new(0.0, 1.0, 0.0), angle);
+                let trs = Trs::new(
+                    Vector::new(0.0, 0.0, 0.0),
+                    rotation,
+                    Vector::new(1.0, 1.0, 1.0),
+                );
+                Keyframe::new(time, trs)
+            })
+            .collect();
+        let spin = Motion::new(keyframes);
+        for index in 0..scene.objects().len() {
+            scene.objects_mut()[index].transform(Transformation::Translation(-center.to_vector()));
+            scene.set_motion(index, spin.clone());
+            scene.objects_mut()[index].transform(Transformation::Translation(center.to_vector()));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geometry::point::Point;
+    use crate::renderer::light::Light;
+
+    fn at_x(x: f64) -> Trs {
+        Trs::new(
+            Vector::new(x, 0.0, 0.0),
+            Quaternion::identity(),
+            Vector::new(1.0, 1.0, 1.0),
+        )
+    }
+
+    fn camera() -> Camera {
+        Camera::look_at(
+            Point::new(0.0, 0.0, 5.0),
+            Point::new(0.0, 0.0, 0.0),
+            45.0,
+            1.0,
+        )
+    }
+
+    #[test]
+    fn time_range_spans_all_tracks() {
+        let mut animation = Animation::new();
+        assert_eq!(animation.time_range(), None);
+        animation.add_track(
+            Target::Camera,
+            Motion::new(vec![
+                Keyframe::new(2.0, at_x(0.0)),
+                Keyframe::new(5.0, at_x(1.0)),
+            ]),
+        );
+        animation.add_track(
+            Target::Light(0),
+            Motion::new(vec![
+                Keyframe::new(-1.0, at_x(0.0)),
+                Keyframe::new(3.0, at_x(1.0)),
+            ]),
+        );
+        assert_eq!(animation.tracks().len(), 2);
+        assert_eq!(animation.tracks()[1].0, Target::Light(0));
+        assert_eq!(animation.time_range(), Some((-1.0, 5.0)));
+    }
+
+    #[test]
+    fn tracks_move_lights_and_report_missing_targets() {
+        let mut scene = Scene::new();
+        scene.add_light(Light::new(Point::new(0.0, 1.0, 0.0)));
+
+        let mut animation = Animation::new();
+        animation.add_track(Target::Light(0), Motion::linear(at_x(0.0), at_x(4.0)));
+        animation.apply(&mut scene, camera()).unwrap();
+        assert_eq!(scene.lights()[0].position_at(0.5).x, 2.0);
+
+        let mut animation = Animation::new();
+        animation.add_track(Target::Light(1), Motion::still(at_x(1.0)));
+        let result = animation.apply(&mut scene, camera());
+        assert!(matches!(result, Err(error) if error.kind() == ErrorKind::InvalidInput));
+    }
+
+    #[test]
+    fn timeline_exposes_each_frame_from_shutter_open_to_close() {
+        let timeline = Timeline::new(3, 1).with_shutter(-0.25, 0.25);
+        assert_eq!(timeline.frames(), 3..=3);
+        assert_eq!(timeline.exposure(3), (2.75, 3.25));
+        assert_eq!(Turntable::new(0).timeline().frames(), 1..=1);
+    }
+}
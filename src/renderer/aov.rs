@@ -0,0 +1,104 @@
+use crate::geometry::normal::Normal;
+
+use super::color::Color;
+
+// Arbitrary output variables: extra passes rendered next to the lit image,
+// meant for compositing and debugging. Direct light comes from the scene
+// lights and the sun, indirect light is everything gathered from the
+// environment.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Aov {
+    // Distance along the camera's viewing direction.
+    Depth,
+    // World space shading normal facing the camera, in [-1, 1].
+    Normal,
+    Albedo,
+    ObjectId,
+    MaterialId,
+    Uv,
+    Direct,
+    Indirect,
+    // Share of the lights that are blocked, 1 is fully in shadow.
+    ShadowMask,
+}
+
+impl Aov {
+    pub(crate) const ALL: [Aov; 9] = [
+        Aov::Depth,
+        Aov::Normal,
+        Aov::Albedo,
+        Aov::ObjectId,
+        Aov::MaterialId,
+        Aov::Uv,
+        Aov::Direct,
+        Aov::Indirect,
+        Aov::ShadowMask,
+    ];
+
+    pub(crate) fn name(&self) -> &'static str {
+        match self {
+            Aov::Depth => "depth",
+            Aov::Normal => "normal",
+            Aov::Albedo => "albedo",
+            Aov::ObjectId => "object_id",
+            Aov::MaterialId => "material_id",
+            Aov::Uv => "uv",
+            Aov::Direct => "direct",
+            Aov::Indirect => "indirect",
+            Aov::ShadowMask => "shadow",
+        }
+    }
+
+    pub(crate) fn from_name(name: &str) -> Option<Aov> {
+        Aov::ALL.into_iter().find(|aov| aov.name() == name)
+    }
+
+    // Names of the channels stored in the red, green and blue components,
+    // as used by multi-layer outputs.
+    pub(crate) fn channels(&self) -> &'static [&'static str] {
+        match self {
+            Aov::Depth => &["Z"],
+            Aov::Normal => &["X", "Y", "Z"],
+            Aov::ObjectId | Aov::MaterialId => &["id"],
+            Aov::Uv => &["U", "V"],
+            Aov::ShadowMask => &["Y"],
+            Aov::Albedo | Aov::Direct | Aov::Indirect => &["R", "G", "B"],
+        }
+    }
+
+    // Whether samples within a pixel are averaged. Depth and ids take the
+    // first covered sample instead, blending them would invent values.
+    pub(crate) fn is_filtered(&self) -> bool {
+        !matches!(self, Aov::Depth | Aov::ObjectId | Aov::MaterialId)
+    }
+
+    pub(crate) fn value(&self, surface: &Surface) -> Color {
+        match self {
+            Aov::Depth => Color::gray(surface.depth),
+            Aov::Normal => {
+                let normal = surface.normal;
+                Color::new(normal.x, normal.y, normal.z)
+            }
+            Aov::Albedo => surface.albedo,
+            Aov::ObjectId => Color::gray(surface.object as f64),
+            Aov::MaterialId => Color::gray(surface.material as f64),
+            Aov::Uv => Color::new(surface.uv.0, surface.uv.1, 0.0),
+            Aov::Direct => surface.direct,
+            Aov::Indirect => surface.indirect,
+            Aov::ShadowMask => Color::gray(surface.shadow),
+        }
+    }
+}
+
+// What the passes record about the surface seen by one camera sample.
+pub(crate) struct Surface {
+    pub(crate) depth: f64,
+    pub(crate) normal: Normal,
+    pub(crate) albedo: Color,
+    pub(crate) object: usize,
+    pub(crate) material: usize,
+    pub(crate) uv: (f64, f64),
+    pub(crate) direct: Color,
+    pub(crate) indirect: Color,
+    pub(crate) shadow: f64,
+}
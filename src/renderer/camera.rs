@@ -1,16 +1,80 @@
-use crate::geometry::normal::Normal;
+use crate::geometry::aligned_box::AlignedBox;
+use crate::geometry::matrix::Matrix;
+use crate::geometry::motion::Motion;
 use crate::geometry::point::Point;
 use crate::geometry::ray::Ray;
 use crate::geometry::vector::Vector;
 use crate::geometry::{Transform, Transformation};
+use crate::renderer::sampling::{concentric_disk, regular_polygon};
 use crate::renderer::viewframe::ViewFrame;
 
+// Vertical field of view of cameras placed by `Camera::frame_all`, in
+// degrees.
+const FRAMING_FIELD_OF_VIEW: f64 = 40.0;
+
+// Width of a full-frame sensor, in scene units taken as meters. `--f-stop`
+// is the f-number of a camera with this sensor.
+const SENSOR_WIDTH: f64 = 0.036;
+
+// Shape of the lens opening, which is also the shape of out of focus
+// highlights.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Aperture {
+    Circle,
+    // Regular polygon with one corner at `rotation` degrees from the frame's
+    // horizontal axis.
+    Polygon { blades: u32, rotation: f64 },
+}
+
+// Thin lens centered on the camera position. Points `focus_distance` in front
+// of the camera along the viewing direction are sharp, everything else is
+// blurred more the larger the aperture `radius` is. A zero radius is a
+// pinhole camera.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Lens {
+    pub(crate) radius: f64,
+    pub(crate) focus_distance: f64,
+    pub(crate) aperture: Aperture,
+}
+
+impl Lens {
+    pub(crate) fn new(radius: f64, focus_distance: f64) -> Lens {
+        Lens {
+            radius,
+            focus_distance,
+            aperture: Aperture::Circle,
+        }
+    }
+
+    pub(crate) fn pinhole() -> Lens {
+        Lens::new(0.0, 1.0)
+    }
+
+    pub(crate) fn with_aperture(mut self, aperture: Aperture) -> Lens {
+        self.aperture = aperture;
+        self
+    }
+
+    // Point on the unit sized aperture.
+    fn sample(&self, random: (f64, f64)) -> (f64, f64) {
+        match self.aperture {
+            Aperture::Circle => concentric_disk(random),
+            Aperture::Polygon { blades, rotation } => {
+                regular_polygon(blades, rotation.to_radians(), random)
+            }
+        }
+    }
+}
+
 // Ray-tracing camera.
 pub(crate) struct Camera {
     // Camera position.
     pub position: Point,
     // Camera view frame.
     pub view_frame: ViewFrame,
+    pub(crate) lens: Lens,
+    // Movement of the camera during the exposure, relative to `position`.
+    pub(crate) motion: Option<Motion>,
 }
 
 impl Camera {
@@ -18,21 +82,208 @@ impl Camera {
         Camera {
             position,
             view_frame,
+            lens: Lens::pinhole(),
+            motion: None,
         }
     }
 
+    // Camera at `position` looking at `target` with Y up, for an image with
+    // `aspect` width over height. `field_of_view` is the vertical angle in
+    // degrees. The view frame goes through `target`, which makes it the
+    // default plane of focus.
+    pub(crate) fn look_at(
+        position: Point,
+        target: Point,
+        field_of_view: f64,
+        aspect: f64,
+    ) -> Camera {
+        let (forward, horizontal, vertical) = view_axes(target - position);
+        let distance = (target - position).length();
+        let height = 2.0 * distance * (field_of_view / 2.0).to_radians().tan();
+        let view_frame = ViewFrame::new(position + forward * distance, height * aspect, height)
+            .with_axes(horizontal, vertical);
+        Camera::new(position, view_frame)
+    }
+
+    // Camera looking along `direction` at the center of `bounds`, backed off
+    // until every corner of the box is in view with `margin` of the image
+    // size to spare on each side.
+    pub(crate) fn frame_all(
+        bounds: AlignedBox,
+        direction: Vector,
+        margin: f64,
+        aspect: f64,
+    ) -> Camera {
+        let bounds = if bounds.is_empty() || !bounds.is_finite() {
+            AlignedBox::new(Point::new(-1.0, -1.0, -1.0), Point::new(1.0, 1.0, 1.0))
+        } else {
+            bounds
+        };
+        let center = bounds.center();
+        let (forward, horizontal, vertical) = view_axes(direction);
+        let tan_y = (FRAMING_FIELD_OF_VIEW / 2.0).to_radians().tan() / (1.0 + 2.0 * margin);
+        let tan_x = tan_y * aspect;
+
+        // A corner fits once its sideways offsets are within the field of
+        // view at its depth, and the camera stays outside the box.
+        let distance = bounds
+            .corners()
+            .iter()
+            .map(|&corner| {
+                let offset = corner - center;
+                let fit =
+                    (offset.dot(horizontal).abs() / tan_x).max(offset.dot(vertical).abs() / tan_y);
+                let depth = offset.dot(forward);
+                (fit - depth).max(-depth * 1.01)
+            })
+            .fold(f64::EPSILON, f64::max);
+        Camera::look_at(
+            center + forward * -distance,
+            center,
+            FRAMING_FIELD_OF_VIEW,
+            aspect,
+        )
+    }
+
+    pub(crate) fn with_lens(mut self, lens: Lens) -> Camera {
+        self.lens = lens;
+        self
+    }
+
+    // Rotations in `motion` turn the camera around its own position.
+    pub(crate) fn with_motion(mut self, motion: Motion) -> Camera {
+        self.motion = Some(motion);
+        self
+    }
+
+    // Distance from the camera to the view frame's center, the plane in focus
+    // by default.
+    pub(crate) fn view_distance(&self) -> f64 {
+        (self.view_frame.origin - self.position).length()
+    }
+
+    // Focal length of a full-frame camera with the same horizontal field of
+    // view, in scene units.
+    pub(crate) fn focal_length(&self) -> f64 {
+        SENSOR_WIDTH * self.view_distance() / self.view_frame.width
+    }
+
+    // Aperture radius, in scene units, that corresponds to the f-number
+    // `f_stop`.
+    pub(crate) fn aperture_radius(&self, f_stop: f64) -> f64 {
+        self.focal_length() / (2.0 * f_stop)
+    }
+
+    // `x` and `y` are continuous image coordinates, y pointing up.
+    // `lens_sample` picks the point on the aperture the ray starts from,
+    // `time` where the camera is when the ray is sent.
     pub(super) fn ray_for_pixel(
         &self,
-        x: usize,
-        y: usize,
+        x: f64,
+        y: f64,
+        image_width: usize,
+        image_height: usize,
+        lens_sample: (f64, f64),
+        time: f64,
+    ) -> Ray {
+        let ray = self.ray_at_rest(x, y, image_width, image_height, lens_sample);
+        let Some(motion) = &self.motion else {
+            return ray.with_time(time);
+        };
+        let around_position = Matrix::<4, 4>::translation(self.position.to_vector())
+            * motion.matrix_at(time)
+            * Matrix::<4, 4>::translation(-self.position.to_vector());
+        Ray::new(
+            around_position * ray.origin,
+            around_position * ray.direction,
+        )
+        .with_time(time)
+    }
+
+    fn ray_at_rest(
+        &self,
+        x: f64,
+        y: f64,
         image_width: usize,
         image_height: usize,
+        lens_sample: (f64, f64),
     ) -> Ray {
         let point = self
             .view_frame
             .point_on_pixel(x, y, image_width, image_height);
         let direction = (point - self.position).normalize();
-        Ray::new(self.position, direction)
+        if self.lens.radius <= 0.0 {
+            return Ray::new(self.position, direction);
+        }
+
+        // Every ray through the same pixel meets the pinhole ray on the plane
+        // of focus.
+        let forward = Vector::from((self.view_frame.origin - self.position).normalize());
+        let direction = Vector::from(direction);
+        let focus = self.position + direction * (self.lens.focus_distance / direction.dot(forward));
+        let (u, v) = self.lens.sample(lens_sample);
+        let (horizontal, vertical) = self.view_frame.axes();
+        let origin = self.position + (horizontal * u + vertical * v) * self.lens.radius;
+        Ray::new(origin, (focus - origin).normalize())
+    }
+
+    // Distance of `point` in front of the camera along the viewing direction,
+    // which points at the view frame's center.
+    pub(crate) fn depth(&self, point: Point) -> f64 {
+        let forward = Vector::from((self.view_frame.origin - self.position).normalize());
+        (point - self.position).dot(forward)
+    }
+}
+
+// Unit viewing direction and the frame's horizontal and vertical axes, with
+// the vertical axis as close to +Y as the direction allows.
+fn view_axes(direction: Vector) -> (Vector, Vector, Vector) {
+    let forward = Vector::from(direction.normalize());
+    let up = if forward.y.abs() > 0.999 {
+        Vector::new(0.0, 0.0, forward.y.signum())
+    } else {
+        Vector::new(0.0, 1.0, 0.0)
+    };
+    let horizontal = Vector::from(forward.cross(up).normalize());
+    let vertical = horizontal.cross(forward);
+    (forward, horizontal, vertical)
+}
+
+// Camera that came with a model file. It looks down the local -Z axis of
+// `to_world` with the local +Y axis up. `field_of_view` is the vertical
+// angle in degrees.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Viewpoint {
+    pub(crate) to_world: Matrix<4, 4>,
+    pub(crate) field_of_view: f64,
+}
+
+impl Viewpoint {
+    pub(crate) fn new(to_world: Matrix<4, 4>, field_of_view: f64) -> Viewpoint {
+        Viewpoint {
+            to_world,
+            field_of_view,
+        }
+    }
+
+    // Camera for an image with `aspect` width over height, with the view
+    // frame one unit in front of it.
+    pub(crate) fn camera(&self, aspect: f64) -> Camera {
+        let position = self.to_world * Point::new(0.0, 0.0, 0.0);
+        let forward = Vector::from((self.to_world * Vector::new(0.0, 0.0, -1.0)).normalize());
+        let up = self.to_world * Vector::new(0.0, 1.0, 0.0);
+        let horizontal = Vector::from(forward.cross(up).normalize());
+        let vertical = horizontal.cross(forward);
+        let height = 2.0 * (self.field_of_view / 2.0).to_radians().tan();
+        let view_frame = ViewFrame::new(position + forward, height * aspect, height)
+            .with_axes(horizontal, vertical);
+        Camera::new(position, view_frame)
+    }
+}
+
+impl Transform for Viewpoint {
+    fn transform(&mut self, transform: Transformation) {
+        self.to_world = transform.transformation_to_matrix() * self.to_world;
     }
 }
 
@@ -42,3 +293,23 @@ impl Transform for Camera {
         self.position = matrix * self.position;
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn f_stop_follows_a_full_frame_focal_length() {
+        // A 90 degree horizontal field of view is an 18 mm lens on a 36 mm
+        // wide sensor, however far away the view frame is.
+        let camera = Camera::look_at(
+            Point::new(0.0, 0.0, 10.0),
+            Point::new(0.0, 0.0, 0.0),
+            90.0,
+            1.0,
+        );
+        assert!((camera.view_distance() - 10.0).abs() < 1e-12);
+        assert!((camera.focal_length() - 0.018).abs() < 1e-12);
+        assert!((camera.aperture_radius(2.0) - 0.0045).abs() < 1e-12);
+    }
+}
@@ -0,0 +1,141 @@
+use std::ops::{Add, AddAssign, Div, Mul};
+
+// Linear RGB color, components are usually but not necessarily in [0, 1].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct Color {
+    pub(crate) r: f64,
+    pub(crate) g: f64,
+    pub(crate) b: f64,
+}
+
+impl Color {
+    pub(crate) fn new(r: f64, g: f64, b: f64) -> Color {
+        Color { r, g, b }
+    }
+
+    pub(crate) fn gray(value: f64) -> Color {
+        Color::new(value, value, value)
+    }
+
+    pub(crate) fn black() -> Color {
+        Color::gray(0.0)
+    }
+
+    pub(crate) fn white() -> Color {
+        Color::gray(1.0)
+    }
+
+    pub(crate) fn from_bytes(r: u8, g: u8, b: u8) -> Color {
+        Color::new(r as f64 / 255.0, g as f64 / 255.0, b as f64 / 255.0)
+    }
+
+    pub(crate) fn to_bytes(self) -> [u8; 3] {
+        let channel = |value: f64| (value.clamp(0.0, 1.0) * 255.0) as u8;
+        [channel(self.r), channel(self.g), channel(self.b)]
+    }
+
+    pub(crate) fn luminance(&self) -> f64 {
+        0.2126 * self.r + 0.7152 * self.g + 0.0722 * self.b
+    }
+
+    pub(crate) fn lerp(&self, other: Color, t: f64) -> Color {
+        *self * (1.0 - t) + other * t
+    }
+}
+
+impl Add for Color {
+    type Output = Color;
+
+    fn add(self, other: Color) -> Color {
+        Color::new(self.r + other.r, self.g + other.g, self.b + other.b)
+    }
+}
+
+impl AddAssign for Color {
+    fn add_assign(&mut self, other: Color) {
+        *self = *self + other;
+    }
+}
+
+impl Mul for Color {
+    type Output = Color;
+
+    fn mul(self, other: Color) -> Color {
+        Color::new(self.r * other.r, self.g * other.g, self.b * other.b)
+    }
+}
+
+impl Mul<f64> for Color {
+    type Output = Color;
+
+    fn mul(self, other: f64) -> Color {
+        Color::new(self.r * other, self.g * other, self.b * other)
+    }
+}
+
+impl Div<f64> for Color {
+    type Output = Color;
+
+    fn div(self, other: f64) -> Color {
+        Color::new(self.r / other, self.g / other, self.b / other)
+    }
+}
+
+// Color together with how much of the pixel is covered by the scene. The
+// color is premultiplied by the coverage, so samples can be averaged and
+// composited directly.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct Pixel {
+    pub(crate) color: Color,
+    pub(crate) alpha: f64,
+}
+
+impl Pixel {
+    pub(crate) fn new(color: Color, alpha: f64) -> Pixel {
+        Pixel { color, alpha }
+    }
+
+    pub(crate) fn transparent() -> Pixel {
+        Pixel::new(Color::black(), 0.0)
+    }
+
+    pub(crate) fn opaque(color: Color) -> Pixel {
+        Pixel::new(color, 1.0)
+    }
+
+    // Color as it would look fully covered, black where nothing is.
+    pub(crate) fn unpremultiplied(&self) -> Color {
+        if self.alpha > 0.0 {
+            self.color / self.alpha
+        } else {
+            Color::black()
+        }
+    }
+
+    // Composites the pixel over an opaque background.
+    pub(crate) fn over(&self, background: Color) -> Color {
+        self.color + background * (1.0 - self.alpha)
+    }
+}
+
+impl Add for Pixel {
+    type Output = Pixel;
+
+    fn add(self, other: Pixel) -> Pixel {
+        Pixel::new(self.color + other.color, self.alpha + other.alpha)
+    }
+}
+
+impl AddAssign for Pixel {
+    fn add_assign(&mut self, other: Pixel) {
+        *self = *self + other;
+    }
+}
+
+impl Div<f64> for Pixel {
+    type Output = Pixel;
+
+    fn div(self, other: f64) -> Pixel {
+        Pixel::new(self.color / other, self.alpha / other)
+    }
+}
@@ -0,0 +1,394 @@
+use std::f64::consts::PI;
+use std::io::Result;
+use std::path::Path;
+
+use crate::geometry::hit::Hit;
+use crate::geometry::vector::Vector;
+use crate::io::{read_image, Image};
+
+use super::color::Color;
+use super::sampling::{cosine_hemisphere, Distribution2D};
+
+// Angular radius of the sun disk as seen from the ground.
+const SUN_ANGULAR_RADIUS: f64 = 0.00465;
+
+// Direction towards the environment picked for lighting a surface, with its
+// radiance and the solid angle density it was picked with.
+pub(crate) struct EnvironmentSample {
+    pub(crate) direction: Vector,
+    pub(crate) radiance: Color,
+    pub(crate) pdf: f64,
+}
+
+// Light arriving from infinitely far away, seen by rays that miss the scene
+// and gathered by surfaces. +Y is up.
+pub(crate) enum Environment {
+    Constant(Color),
+    Gradient(Gradient),
+    Map(EnvironmentMap),
+    Sky(Sky),
+}
+
+impl Environment {
+    // Radiance seen directly along `direction`, including the sun disk.
+    pub(crate) fn background(&self, direction: Vector) -> Color {
+        match self {
+            Environment::Sky(sky) => sky.background(direction),
+            _ => self.radiance(direction),
+        }
+    }
+
+    // Radiance arriving from `direction`. The sun is left out, it is
+    // sampled separately as a directional light.
+    pub(crate) fn radiance(&self, direction: Vector) -> Color {
+        match self {
+            Environment::Constant(color) => *color,
+            Environment::Gradient(gradient) => gradient.radiance(direction),
+            Environment::Map(map) => map.radiance(direction),
+            Environment::Sky(sky) => sky.radiance(direction),
+        }
+    }
+
+    // Picks a direction to gather light from at `hit`, by the cosine around
+    // the shading normal. Maps pick half of their directions by brightness
+    // instead, and report the density of that mix, so small bright spots and
+    // wide dim skies both converge.
+    pub(crate) fn sample(&self, hit: &Hit, random: (f64, f64)) -> Option<EnvironmentSample> {
+        let Environment::Map(map) = self else {
+            let direction = cosine_direction(hit, random)?;
+            let pdf = cosine_pdf(hit, direction);
+            return Some(EnvironmentSample {
+                direction,
+                radiance: self.radiance(direction),
+                pdf,
+            });
+        };
+        let direction = if random.0 < 0.5 {
+            map.sample((2.0 * random.0, random.1))?.direction
+        } else {
+            cosine_direction(hit, (2.0 * random.0 - 1.0, random.1))?
+        };
+        Some(EnvironmentSample {
+            direction,
+            radiance: map.radiance(direction),
+            pdf: 0.5 * (map.pdf(direction) + cosine_pdf(hit, direction)),
+        })
+    }
+
+    // Direction towards the sun and the irradiance it delivers to a surface
+    // facing it, when the environment has a sun above the horizon.
+    pub(crate) fn sun(&self) -> Option<(Vector, Color)> {
+        match self {
+            Environment::Sky(sky) => sky.sun(),
+            _ => None,
+        }
+    }
+}
+
+fn cosine_direction(hit: &Hit, random: (f64, f64)) -> Option<Vector> {
+    let local = cosine_hemisphere(random);
+    if local.z <= 0.0 {
+        return None;
+    }
+    Some(
+        hit.tangent * local.x
+            + hit.bitangent * local.y
+            + Vector::from(hit.shading_normal) * local.z,
+    )
+}
+
+fn cosine_pdf(hit: &Hit, direction: Vector) -> f64 {
+    let cosine = Vector::from(hit.shading_normal).dot(direction) / direction.length();
+    cosine.max(0.0) / PI
+}
+
+// Blend from `bottom` straight down to `top` straight up.
+pub(crate) struct Gradient {
+    pub(crate) bottom: Color,
+    pub(crate) top: Color,
+}
+
+impl Gradient {
+    pub(crate) fn new(bottom: Color, top: Color) -> Gradient {
+        Gradient { bottom, top }
+    }
+
+    fn radiance(&self, direction: Vector) -> Color {
+        let up = direction.y / direction.length();
+        self.bottom.lerp(self.top, 0.5 * (up + 1.0))
+    }
+}
+
+// Equirectangular (latitude/longitude) image covering every direction. The
+// image center looks down -Z, the top row is straight up.
+pub(crate) struct EnvironmentMap {
+    image: Image,
+    intensity: f64,
+    distribution: Distribution2D,
+}
+
+impl EnvironmentMap {
+    pub(crate) fn new(image: Image, intensity: f64) -> EnvironmentMap {
+        // Rows near the poles cover less solid angle.
+        let weights: Vec<f64> = image
+            .pixels
+            .iter()
+            .enumerate()
+            .map(|(i, pixel)| {
+                let row = i / image.width.max(1);
+                let theta = PI * (row as f64 + 0.5) / image.height as f64;
+                pixel.luminance().max(0.0) * theta.sin()
+            })
+            .collect();
+        let distribution = Distribution2D::new(&weights, image.width, image.height);
+        EnvironmentMap {
+            image,
+            intensity,
+            distribution,
+        }
+    }
+
+    pub(crate) fn load(path: &Path, intensity: f64) -> Result<EnvironmentMap> {
+        Ok(EnvironmentMap::new(read_image(path)?, intensity))
+    }
+
+    fn texel(&self, x: i64, y: i64) -> Color {
+        let x = x.rem_euclid(self.image.width as i64) as usize;
+        let y = y.clamp(0, self.image.height as i64 - 1) as usize;
+        self.image.pixels[y * self.image.width + x]
+    }
+
+    // Bilinear lookup, wrapping around horizontally.
+    fn radiance(&self, direction: Vector) -> Color {
+        if self.image.pixels.is_empty() {
+            return Color::black();
+        }
+        let (u, v) = direction_to_uv(direction);
+        let x = u * self.image.width as f64 - 0.5;
+        let y = v * self.image.height as f64 - 0.5;
+        let (x0, y0) = (x.floor(), y.floor());
+        let (fx, fy) = (x - x0, y - y0);
+        let (x0, y0) = (x0 as i64, y0 as i64);
+
+        let top = self.texel(x0, y0).lerp(self.texel(x0 + 1, y0), fx);
+        let bottom = self.texel(x0, y0 + 1).lerp(self.texel(x0 + 1, y0 + 1), fx);
+        top.lerp(bottom, fy) * self.intensity
+    }
+
+    fn sample(&self, random: (f64, f64)) -> Option<EnvironmentSample> {
+        if self.image.pixels.is_empty() {
+            return None;
+        }
+        let (uv, pdf) = self.distribution.sample(random);
+        let sin_theta = (PI * uv.1).sin();
+        if pdf <= 0.0 || sin_theta <= 0.0 {
+            return None;
+        }
+        let direction = uv_to_direction(uv);
+        Some(EnvironmentSample {
+            direction,
+            radiance: self.radiance(direction),
+            // The map covers 2 pi by pi radians of longitude and latitude.
+            pdf: pdf / (2.0 * PI * PI * sin_theta),
+        })
+    }
+
+    // Solid angle density `sample` picks `direction` with.
+    fn pdf(&self, direction: Vector) -> f64 {
+        if self.image.pixels.is_empty() {
+            return 0.0;
+        }
+        let uv = direction_to_uv(direction);
+        let sin_theta = (PI * uv.1).sin();
+        if sin_theta <= 0.0 {
+            return 0.0;
+        }
+        self.distribution.pdf(uv) / (2.0 * PI * PI * sin_theta)
+    }
+}
+
+fn direction_to_uv(direction: Vector) -> (f64, f64) {
+    let direction = direction / direction.length();
+    let u = 0.5 + direction.x.atan2(-direction.z) / (2.0 * PI);
+    let v = direction.y.clamp(-1.0, 1.0).acos() / PI;
+    (u, v)
+}
+
+fn uv_to_direction((u, v): (f64, f64)) -> Vector {
+    let phi = 2.0 * PI * (u - 0.5);
+    let theta = PI * v;
+    Vector::new(
+        theta.sin() * phi.sin(),
+        theta.cos(),
+        -theta.sin() * phi.cos(),
+    )
+}
+
+// Preetham et al. analytic daylight model. The model's luminance in kcd/m2
+// is multiplied by `intensity`, the default puts a clear noon zenith at about
+// 1. The sun is a directional light whose color is a rough approximation of
+// atmospheric extinction. Below the horizon there is a uniformly colored
+// ground.
+pub(crate) struct Sky {
+    sun_direction: Vector,
+    turbidity: f64,
+    pub(crate) intensity: f64,
+    pub(crate) sun_intensity: f64,
+    pub(crate) ground: Color,
+    // Perez coefficients and zenith values for luminance and chromaticity.
+    coefficients: [[f64; 5]; 3],
+    zenith: [f64; 3],
+}
+
+impl Sky {
+    // `turbidity` ranges from about 2 for a clear sky to 10 for haze.
+    pub(crate) fn new(sun_direction: Vector, turbidity: f64) -> Sky {
+        let t = turbidity;
+        let sun_direction = sun_direction / sun_direction.length();
+        let theta_s = sun_direction.y.clamp(-1.0, 1.0).acos().min(PI / 2.0);
+
+        let coefficients = [
+            [
+                0.1787 * t - 1.4630,
+                -0.3554 * t + 0.4275,
+                -0.0227 * t + 5.3251,
+                0.1206 * t - 2.5771,
+                -0.0670 * t + 0.3703,
+            ],
+            [
+                -0.0193 * t - 0.2592,
+                -0.0665 * t + 0.0008,
+                -0.0004 * t + 0.2125,
+                -0.0641 * t - 0.8989,
+                -0.0033 * t + 0.0452,
+            ],
+            [
+                -0.0167 * t - 0.2608,
+                -0.0950 * t + 0.0092,
+                -0.0079 * t + 0.2102,
+                -0.0441 * t - 1.6537,
+                -0.0109 * t + 0.0529,
+            ],
+        ];
+
+        let chi = (4.0 / 9.0 - t / 120.0) * (PI - 2.0 * theta_s);
+        let luminance = (4.0453 * t - 4.9710) * chi.tan() - 0.2155 * t + 2.4192;
+        let (s1, s2, s3) = (theta_s, theta_s * theta_s, theta_s * theta_s * theta_s);
+        let x = t * t * (0.00166 * s3 - 0.00375 * s2 + 0.00209 * s1)
+            + t * (-0.02903 * s3 + 0.06377 * s2 - 0.03202 * s1 + 0.00394)
+            + (0.11693 * s3 - 0.21196 * s2 + 0.06052 * s1 + 0.25886);
+        let y = t * t * (0.00275 * s3 - 0.00610 * s2 + 0.00317 * s1)
+            + t * (-0.04214 * s3 + 0.08970 * s2 - 0.04153 * s1 + 0.00516)
+            + (0.15346 * s3 - 0.26756 * s2 + 0.06670 * s1 + 0.26688);
+
+        Sky {
+            sun_direction,
+            turbidity,
+            intensity: 0.04,
+            sun_intensity: 3.0,
+            ground: Color::gray(0.2),
+            coefficients,
+            zenith: [luminance, x, y],
+        }
+    }
+
+    pub(crate) fn sun_direction(&self) -> Vector {
+        self.sun_direction
+    }
+
+    fn radiance(&self, direction: Vector) -> Color {
+        let direction = direction / direction.length();
+        if direction.y < 0.0 {
+            return self.ground;
+        }
+        let cos_theta = direction.y.max(0.01);
+        let cos_gamma = direction.dot(self.sun_direction).clamp(-1.0, 1.0);
+        let theta_s = self.sun_direction.y.clamp(-1.0, 1.0).acos().min(PI / 2.0);
+
+        let [luminance, x, y] = [0, 1, 2].map(|i| {
+            let coefficients = &self.coefficients[i];
+            let value = perez(cos_theta, cos_gamma, coefficients);
+            let zenith = perez(1.0, theta_s.cos(), coefficients);
+            self.zenith[i] * value / zenith
+        });
+        xyy_to_rgb(x, y, luminance * self.intensity)
+    }
+
+    fn background(&self, direction: Vector) -> Color {
+        match self.sun() {
+            Some((sun, irradiance))
+                if direction.dot(sun) / direction.length() > SUN_ANGULAR_RADIUS.cos() =>
+            {
+                let solid_angle = 2.0 * PI * (1.0 - SUN_ANGULAR_RADIUS.cos());
+                irradiance / solid_angle
+            }
+            _ => self.radiance(direction),
+        }
+    }
+
+    fn sun(&self) -> Option<(Vector, Color)> {
+        let cos_zenith = self.sun_direction.y;
+        if cos_zenith <= 0.0 || self.sun_intensity <= 0.0 {
+            return None;
+        }
+        // Kasten and Young relative air mass.
+        let zenith_degrees = cos_zenith.acos().to_degrees();
+        let air_mass = 1.0 / (cos_zenith + 0.50572 * (96.07995 - zenith_degrees).powf(-1.6364));
+        let haze = self.turbidity / 2.0;
+        let extinction = |coefficient: f64| (-coefficient * haze * air_mass).exp();
+        let color = Color::new(extinction(0.06), extinction(0.12), extinction(0.26));
+        Some((self.sun_direction, color * self.sun_intensity))
+    }
+}
+
+fn perez(cos_theta: f64, cos_gamma: f64, [a, b, c, d, e]: &[f64; 5]) -> f64 {
+    let gamma = cos_gamma.clamp(-1.0, 1.0).acos();
+    (1.0 + a * (b / cos_theta).exp()) * (1.0 + c * (d * gamma).exp() + e * cos_gamma * cos_gamma)
+}
+
+// CIE xyY to linear sRGB.
+fn xyy_to_rgb(x: f64, y: f64, luminance: f64) -> Color {
+    if y <= 0.0 {
+        return Color::black();
+    }
+    let big_x = x / y * luminance;
+    let big_z = (1.0 - x - y) / y * luminance;
+    Color::new(
+        (3.2406 * big_x - 1.5372 * luminance - 0.4986 * big_z).max(0.0),
+        (-0.9689 * big_x + 1.8758 * luminance + 0.0415 * big_z).max(0.0),
+        (0.0557 * big_x - 0.2040 * luminance + 1.0570 * big_z).max(0.0),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn map_pdf_matches_the_sampled_density() {
+        let pixels = (0..32).map(|i| Color::gray(1.0 + i as f64)).collect();
+        let image = Image {
+            width: 8,
+            height: 4,
+            pixels,
+        };
+        let map = EnvironmentMap::new(image, 1.0);
+        for random in [(0.1, 0.3), (0.5, 0.5), (0.8, 0.9)] {
+            let sample = map.sample(random).unwrap();
+            let pdf = map.pdf(sample.direction);
+            assert!(
+                (pdf - sample.pdf).abs() < 1e-9 * pdf,
+                "{} != {}",
+                pdf,
+                sample.pdf
+            );
+        }
+    }
+
+    #[test]
+    fn uv_and_direction_round_trip() {
+        let direction = Vector::new(0.3, -0.4, 0.5);
+        let back = uv_to_direction(direction_to_uv(direction));
+        assert!((back - direction / direction.length()).length() < 1e-12);
+    }
+}
@@ -0,0 +1,144 @@
+use super::aov::Aov;
+use super::color::{Color, Pixel};
+
+// Rectangle of pixels, with `x` and `y` counted from the top left.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct Region {
+    pub(crate) x: usize,
+    pub(crate) y: usize,
+    pub(crate) width: usize,
+    pub(crate) height: usize,
+}
+
+impl Region {
+    pub(crate) fn new(x: usize, y: usize, width: usize, height: usize) -> Region {
+        Region {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    // Whether the region lies within an image of `width` by `height`.
+    pub(crate) fn fits(&self, width: usize, height: usize) -> bool {
+        self.x + self.width <= width && self.y + self.height <= height
+    }
+}
+
+// Rendered image together with the requested extra passes, all stored row
+// by row starting at the top left.
+pub(crate) struct Frame {
+    pub(crate) width: usize,
+    pub(crate) height: usize,
+    pub(crate) pixels: Vec<Pixel>,
+    pub(crate) aovs: Vec<(Aov, Vec<Pixel>)>,
+    // Samples taken of every pixel.
+    pub(crate) samples: Vec<u32>,
+}
+
+impl Frame {
+    pub(crate) fn new(width: usize, height: usize, aovs: &[Aov]) -> Frame {
+        let buffer = vec![Pixel::transparent(); width * height];
+        Frame {
+            width,
+            height,
+            pixels: buffer.clone(),
+            aovs: aovs.iter().map(|&aov| (aov, buffer.clone())).collect(),
+            samples: vec![0; width * height],
+        }
+    }
+
+    pub(crate) fn aov(&self, aov: Aov) -> Option<&[Pixel]> {
+        self.aovs
+            .iter()
+            .find(|(candidate, _)| *candidate == aov)
+            .map(|(_, pixels)| pixels.as_slice())
+    }
+
+    // Copy of the pixels inside `region`, which must fit the frame.
+    pub(crate) fn crop(&self, region: Region) -> Frame {
+        assert!(
+            region.fits(self.width, self.height),
+            "Region {:?} is outside of the {}x{} frame",
+            region,
+            self.width,
+            self.height
+        );
+        let rows = |y: usize| {
+            let start = y * self.width + region.x;
+            start..start + region.width
+        };
+        let crop = |pixels: &[Pixel]| -> Vec<Pixel> {
+            (region.y..region.y + region.height)
+                .flat_map(|y| pixels[rows(y)].iter().copied())
+                .collect()
+        };
+        Frame {
+            width: region.width,
+            height: region.height,
+            pixels: crop(&self.pixels),
+            aovs: self
+                .aovs
+                .iter()
+                .map(|(aov, pixels)| (*aov, crop(pixels)))
+                .collect(),
+            samples: (region.y..region.y + region.height)
+                .flat_map(|y| self.samples[rows(y)].iter().copied())
+                .collect(),
+        }
+    }
+
+    // Heatmap of the samples taken, from blue for the fewest through green
+    // and yellow to red for the most, on a log scale since sample counts
+    // grow by doubling. Pixels without samples are transparent.
+    pub(crate) fn sample_map(&self) -> Vec<Pixel> {
+        const RAMP: [Color; 4] = [
+            Color {
+                r: 0.0,
+                g: 0.0,
+                b: 1.0,
+            },
+            Color {
+                r: 0.0,
+                g: 1.0,
+                b: 0.0,
+            },
+            Color {
+                r: 1.0,
+                g: 1.0,
+                b: 0.0,
+            },
+            Color {
+                r: 1.0,
+                g: 0.0,
+                b: 0.0,
+            },
+        ];
+        let most = self.samples.iter().copied().max().unwrap_or(0);
+        let fewest = self
+            .samples
+            .iter()
+            .copied()
+            .filter(|&n| n > 0)
+            .min()
+            .unwrap_or(0);
+        let span = (most as f64 / fewest as f64).ln();
+        self.samples
+            .iter()
+            .map(|&samples| {
+                if samples == 0 {
+                    return Pixel::transparent();
+                }
+                let t = if span > 0.0 {
+                    (samples as f64 / fewest as f64).ln() / span
+                } else {
+                    0.0
+                };
+                let position = t * (RAMP.len() - 1) as f64;
+                let stop = (position as usize).min(RAMP.len() - 2);
+                Pixel::opaque(RAMP[stop].lerp(RAMP[stop + 1], position - stop as f64))
+            })
+            .collect()
+    }
+}
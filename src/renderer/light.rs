@@ -1,12 +1,59 @@
+use crate::geometry::motion::Motion;
 use crate::geometry::point::Point;
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone)]
 pub(crate) struct Light {
     pub(crate) position: Point,
+    // Moves the light's position over time.
+    pub(crate) motion: Option<Motion>,
 }
 
 impl Light {
     pub(crate) fn new(position: Point) -> Light {
-        Light { position }
+        Light {
+            position,
+            motion: None,
+        }
+    }
+
+    #[cfg_attr(not(test), allow(dead_code))]
+    pub(crate) fn with_motion(mut self, motion: Motion) -> Light {
+        self.motion = Some(motion);
+        self
+    }
+
+    pub(crate) fn position_at(&self, time: f64) -> Point {
+        match &self.motion {
+            Some(motion) => motion.matrix_at(time) * self.position,
+            None => self.position,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geometry::quaternion::Quaternion;
+    use crate::geometry::trs::Trs;
+    use crate::geometry::vector::Vector;
+
+    #[test]
+    fn moving_light_follows_its_motion() {
+        let light = Light::new(Point::new(1.0, 0.0, 0.0));
+        assert_eq!(light.position_at(7.0).x, 1.0);
+
+        let start = Trs::new(
+            Vector::new(0.0, 0.0, 0.0),
+            Quaternion::identity(),
+            Vector::new(1.0, 1.0, 1.0),
+        );
+        let end = Trs::new(
+            Vector::new(0.0, 2.0, 0.0),
+            Quaternion::identity(),
+            Vector::new(1.0, 1.0, 1.0),
+        );
+        let light = light.with_motion(Motion::linear(start, end));
+        let position = light.position_at(0.5);
+        assert_eq!((position.x, position.y, position.z), (1.0, 1.0, 0.0));
     }
 }
@@ -0,0 +1,121 @@
+use crate::geometry::hit::Hit;
+use crate::geometry::vector::Vector;
+
+use super::color::Color;
+use super::texture::Texture;
+
+// Height field that perturbs the shading normal without changing geometry.
+// `strength` scales the slope given by `Texture::height_gradient`.
+pub(crate) struct BumpMap {
+    pub(crate) height: Texture,
+    pub(crate) strength: f64,
+}
+
+impl BumpMap {
+    pub(crate) fn new(height: Texture, strength: f64) -> BumpMap {
+        BumpMap { height, strength }
+    }
+}
+
+// Surface appearance of an object. The normal map stores tangent space
+// normals with the usual `color * 2 - 1` encoding and green pointing along v.
+// `metallic` and `roughness` describe the surface in the metallic-roughness
+// model, both in [0, 1]. Only the diffuse color is shaded so far.
+pub(crate) struct Material {
+    pub(crate) diffuse: Texture,
+    pub(crate) normal_map: Option<Texture>,
+    pub(crate) bump_map: Option<BumpMap>,
+    pub(crate) metallic: f64,
+    pub(crate) roughness: f64,
+}
+
+impl Material {
+    pub(crate) fn new(diffuse: Texture) -> Material {
+        Material {
+            diffuse,
+            normal_map: None,
+            bump_map: None,
+            metallic: 0.0,
+            roughness: 1.0,
+        }
+    }
+
+    pub(crate) fn with_normal_map(mut self, normal_map: Texture) -> Material {
+        self.normal_map = Some(normal_map);
+        self
+    }
+
+    #[cfg_attr(not(test), allow(dead_code))]
+    pub(crate) fn with_bump_map(mut self, bump_map: BumpMap) -> Material {
+        self.bump_map = Some(bump_map);
+        self
+    }
+
+    pub(crate) fn with_metallic_roughness(mut self, metallic: f64, roughness: f64) -> Material {
+        self.metallic = metallic;
+        self.roughness = roughness;
+        self
+    }
+
+    // Applies the bump map and then the normal map to the shading normal of
+    // `hit`. Hits on materials without either are returned unchanged.
+    pub(crate) fn shade_normal(&self, mut hit: Hit) -> Hit {
+        if let Some(bump) = &self.bump_map {
+            let (du, dv) = bump.height.height_gradient(&hit);
+            let normal = Vector::from(hit.shading_normal)
+                - (hit.tangent * du + hit.bitangent * dv) * bump.strength;
+            hit = hit.with_perturbed_normal(normal.normalize());
+        }
+        if let Some(normal_map) = &self.normal_map {
+            let color = normal_map.evaluate(&hit);
+            let normal = hit.tangent * (color.r * 2.0 - 1.0)
+                + hit.bitangent * (color.g * 2.0 - 1.0)
+                + Vector::from(hit.shading_normal) * (color.b * 2.0 - 1.0);
+            if normal.length() > 1e-12 {
+                hit = hit.with_perturbed_normal(normal.normalize());
+            }
+        }
+        hit
+    }
+}
+
+impl Default for Material {
+    fn default() -> Material {
+        Material::new(Texture::Constant(Color::white()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geometry::normal::Normal;
+    use crate::geometry::point::Point;
+    use crate::geometry::ray::Ray;
+
+    fn hit() -> Hit {
+        let ray = Ray::new(Point::new(0.0, 0.0, 1.0), Normal::new(0.0, 0.0, -1.0));
+        Hit::new(&ray, 1.0, Normal::new(0.0, 0.0, 1.0))
+    }
+
+    #[test]
+    fn flat_bump_map_keeps_the_normal() {
+        let material = Material::default()
+            .with_bump_map(BumpMap::new(Texture::Constant(Color::gray(0.5)), 2.0));
+        let shaded = material.shade_normal(hit());
+        assert!(
+            (Vector::from(shaded.shading_normal) - Vector::new(0.0, 0.0, 1.0)).length() < 1e-12
+        );
+    }
+
+    #[test]
+    fn normal_map_tilts_along_the_tangent() {
+        // Red above one half leans the normal towards the tangent.
+        let material =
+            Material::default().with_normal_map(Texture::Constant(Color::new(1.0, 0.5, 0.5)));
+        let hit = hit();
+        let shaded = material.shade_normal(hit);
+        let normal = Vector::from(shaded.shading_normal);
+        assert!(normal.dot(hit.tangent) > 0.5);
+        assert!(normal.dot(hit.bitangent).abs() < 1e-12);
+    }
+}
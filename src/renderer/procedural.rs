@@ -0,0 +1,253 @@
+use crate::geometry::point::Point;
+
+use super::color::Color;
+use super::random::Random;
+
+// Improved Perlin gradient noise.
+pub(crate) struct Perlin {
+    permutation: [u8; 512],
+}
+
+impl Perlin {
+    pub(crate) fn new(seed: u64) -> Perlin {
+        let mut values: Vec<u8> = (0..=255).collect();
+        let mut random = Random::new(seed);
+        // Fisher-Yates shuffle.
+        for i in (1..values.len()).rev() {
+            values.swap(i, random.next_below(i + 1));
+        }
+        let mut permutation = [0; 512];
+        for (i, value) in permutation.iter_mut().enumerate() {
+            *value = values[i % 256];
+        }
+        Perlin { permutation }
+    }
+
+    // Smooth noise in [-1, 1], zero at every integer lattice point.
+    pub(crate) fn noise(&self, point: Point) -> f64 {
+        let (xf, yf, zf) = (point.x.floor(), point.y.floor(), point.z.floor());
+        let (x, y, z) = (point.x - xf, point.y - yf, point.z - zf);
+        let (xi, yi, zi) = (
+            (xf as i64 & 255) as usize,
+            (yf as i64 & 255) as usize,
+            (zf as i64 & 255) as usize,
+        );
+        let (u, v, w) = (fade(x), fade(y), fade(z));
+
+        let p = &self.permutation;
+        let a = p[xi] as usize + yi;
+        let aa = p[a] as usize + zi;
+        let ab = p[a + 1] as usize + zi;
+        let b = p[xi + 1] as usize + yi;
+        let ba = p[b] as usize + zi;
+        let bb = p[b + 1] as usize + zi;
+
+        lerp(
+            w,
+            lerp(
+                v,
+                lerp(u, gradient(p[aa], x, y, z), gradient(p[ba], x - 1.0, y, z)),
+                lerp(
+                    u,
+                    gradient(p[ab], x, y - 1.0, z),
+                    gradient(p[bb], x - 1.0, y - 1.0, z),
+                ),
+            ),
+            lerp(
+                v,
+                lerp(
+                    u,
+                    gradient(p[aa + 1], x, y, z - 1.0),
+                    gradient(p[ba + 1], x - 1.0, y, z - 1.0),
+                ),
+                lerp(
+                    u,
+                    gradient(p[ab + 1], x, y - 1.0, z - 1.0),
+                    gradient(p[bb + 1], x - 1.0, y - 1.0, z - 1.0),
+                ),
+            ),
+        )
+    }
+
+    // Fractal Brownian motion: octaves of noise with doubling frequency and
+    // halving amplitude, normalized back to roughly [-1, 1].
+    pub(crate) fn fractal(&self, point: Point, octaves: u32) -> f64 {
+        self.octaves(point, octaves, |n| n)
+    }
+
+    // Like `fractal` but sums absolute values, which gives sharp creases.
+    // Returns values in [0, 1].
+    pub(crate) fn turbulence(&self, point: Point, octaves: u32) -> f64 {
+        self.octaves(point, octaves, f64::abs)
+    }
+
+    fn octaves(&self, point: Point, octaves: u32, shape: impl Fn(f64) -> f64) -> f64 {
+        let mut sum = 0.0;
+        let mut total_amplitude = 0.0;
+        let mut amplitude = 1.0;
+        let mut frequency = 1.0;
+        for _ in 0..octaves.max(1) {
+            let p = Point::new(
+                point.x * frequency,
+                point.y * frequency,
+                point.z * frequency,
+            );
+            sum += shape(self.noise(p)) * amplitude;
+            total_amplitude += amplitude;
+            amplitude *= 0.5;
+            frequency *= 2.0;
+        }
+        sum / total_amplitude
+    }
+}
+
+fn fade(t: f64) -> f64 {
+    t * t * t * (t * (t * 6.0 - 15.0) + 10.0)
+}
+
+fn lerp(t: f64, a: f64, b: f64) -> f64 {
+    a + t * (b - a)
+}
+
+// Dot product with one of 12 gradient directions picked by the hash.
+fn gradient(hash: u8, x: f64, y: f64, z: f64) -> f64 {
+    let h = hash & 15;
+    let u = if h < 8 { x } else { y };
+    let v = if h < 4 {
+        y
+    } else if h == 12 || h == 14 {
+        x
+    } else {
+        z
+    };
+    (if h & 1 == 0 { u } else { -u }) + (if h & 2 == 0 { v } else { -v })
+}
+
+fn scaled(point: Point, scale: f64) -> Point {
+    Point::new(point.x * scale, point.y * scale, point.z * scale)
+}
+
+// Alternating cubes of two colors, `scale` cubes per unit.
+pub(crate) struct Checker {
+    pub(crate) even: Color,
+    pub(crate) odd: Color,
+    pub(crate) scale: f64,
+}
+
+impl Checker {
+    pub(crate) fn new(even: Color, odd: Color, scale: f64) -> Checker {
+        Checker { even, odd, scale }
+    }
+
+    pub(crate) fn evaluate(&self, point: Point) -> Color {
+        let p = scaled(point, self.scale);
+        // A small offset keeps faces lying exactly on cell borders stable.
+        let sum = (p.x + 1e-9).floor() + (p.y + 1e-9).floor() + (p.z + 1e-9).floor();
+        if sum.rem_euclid(2.0) < 1.0 {
+            self.even
+        } else {
+            self.odd
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum NoiseKind {
+    Perlin,
+    Fractal,
+    Turbulence,
+}
+
+// Blend between two colors driven by noise.
+pub(crate) struct Noise {
+    pub(crate) perlin: Perlin,
+    pub(crate) kind: NoiseKind,
+    pub(crate) scale: f64,
+    pub(crate) octaves: u32,
+    pub(crate) low: Color,
+    pub(crate) high: Color,
+}
+
+impl Noise {
+    pub(crate) fn new(kind: NoiseKind, scale: f64, low: Color, high: Color) -> Noise {
+        Noise {
+            perlin: Perlin::new(0),
+            kind,
+            scale,
+            octaves: 6,
+            low,
+            high,
+        }
+    }
+
+    pub(crate) fn evaluate(&self, point: Point) -> Color {
+        let p = scaled(point, self.scale);
+        let value = match self.kind {
+            NoiseKind::Perlin => 0.5 * (self.perlin.noise(p) + 1.0),
+            NoiseKind::Fractal => 0.5 * (self.perlin.fractal(p, self.octaves) + 1.0),
+            NoiseKind::Turbulence => self.perlin.turbulence(p, self.octaves),
+        };
+        self.low.lerp(self.high, value.clamp(0.0, 1.0))
+    }
+}
+
+// Veins along the X axis distorted by turbulence.
+pub(crate) struct Marble {
+    pub(crate) perlin: Perlin,
+    pub(crate) scale: f64,
+    pub(crate) distortion: f64,
+    pub(crate) octaves: u32,
+    pub(crate) base: Color,
+    pub(crate) vein: Color,
+}
+
+impl Marble {
+    pub(crate) fn new(scale: f64, base: Color, vein: Color) -> Marble {
+        Marble {
+            perlin: Perlin::new(0),
+            scale,
+            distortion: 5.0,
+            octaves: 6,
+            base,
+            vein,
+        }
+    }
+
+    pub(crate) fn evaluate(&self, point: Point) -> Color {
+        let p = scaled(point, self.scale);
+        let turbulence = self.perlin.turbulence(p, self.octaves);
+        let value = 0.5 * (1.0 + (p.x + self.distortion * turbulence).sin());
+        // Sharpen the transition so the veins stay thin.
+        self.vein.lerp(self.base, value.powf(0.5))
+    }
+}
+
+// Concentric rings around the Y axis, `rings` per unit of radius.
+pub(crate) struct Wood {
+    pub(crate) perlin: Perlin,
+    pub(crate) rings: f64,
+    pub(crate) distortion: f64,
+    pub(crate) light: Color,
+    pub(crate) dark: Color,
+}
+
+impl Wood {
+    pub(crate) fn new(rings: f64, light: Color, dark: Color) -> Wood {
+        Wood {
+            perlin: Perlin::new(0),
+            rings,
+            distortion: 0.1,
+            light,
+            dark,
+        }
+    }
+
+    pub(crate) fn evaluate(&self, point: Point) -> Color {
+        let radius = (point.x * point.x + point.z * point.z).sqrt();
+        let grain = self.perlin.fractal(scaled(point, self.rings), 4);
+        let ring = (radius * self.rings + self.distortion * self.rings * grain).fract();
+        // Early wood fades smoothly into the dark late wood.
+        let value = (ring * std::f64::consts::PI).sin().powi(2);
+        self.light.lerp(self.dark, value)
+    }
+}
@@ -0,0 +1,46 @@
+// Small, fast and reproducible pseudo random generator (PCG32). Rendering only
+// needs well distributed numbers, not cryptographic quality.
+#[derive(Debug, Clone)]
+pub(crate) struct Random {
+    state: u64,
+    increment: u64,
+}
+
+impl Random {
+    pub(crate) fn new(seed: u64) -> Random {
+        Random::with_stream(seed, 0)
+    }
+
+    // Generators with the same seed but different streams are independent,
+    // which gives every pixel its own sequence.
+    pub(crate) fn with_stream(seed: u64, stream: u64) -> Random {
+        let mut random = Random {
+            state: 0,
+            increment: (stream << 1) | 1,
+        };
+        random.next_u32();
+        random.state = random.state.wrapping_add(seed);
+        random.next_u32();
+        random
+    }
+
+    pub(crate) fn next_u32(&mut self) -> u32 {
+        let old = self.state;
+        self.state = old
+            .wrapping_mul(6_364_136_223_846_793_005)
+            .wrapping_add(self.increment);
+        let xorshifted = (((old >> 18) ^ old) >> 27) as u32;
+        let rotation = (old >> 59) as u32;
+        xorshifted.rotate_right(rotation)
+    }
+
+    // Uniform number in [0, 1).
+    pub(crate) fn next_f64(&mut self) -> f64 {
+        self.next_u32() as f64 / (u32::MAX as f64 + 1.0)
+    }
+
+    // Uniform integer in [0, bound).
+    pub(crate) fn next_below(&mut self, bound: usize) -> usize {
+        (self.next_f64() * bound as f64) as usize
+    }
+}
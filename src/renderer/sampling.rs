@@ -0,0 +1,207 @@
+use std::f64::consts::PI;
+
+use crate::geometry::vector::Vector;
+
+// Piecewise constant distribution over [0, 1) proportional to `weights`.
+pub(crate) struct Distribution1D {
+    weights: Vec<f64>,
+    // Running sums normalized to end at 1, one entry longer than `weights`.
+    cdf: Vec<f64>,
+    total: f64,
+}
+
+impl Distribution1D {
+    // All zero weights fall back to a uniform distribution.
+    pub(crate) fn new(weights: Vec<f64>) -> Distribution1D {
+        let count = weights.len().max(1) as f64;
+        let mut cdf = vec![0.0; weights.len() + 1];
+        for (i, weight) in weights.iter().enumerate() {
+            cdf[i + 1] = cdf[i] + weight.max(0.0) / count;
+        }
+        let total = cdf[weights.len()];
+        for (i, value) in cdf.iter_mut().enumerate() {
+            *value = if total > 0.0 {
+                *value / total
+            } else {
+                i as f64 / count
+            };
+        }
+        Distribution1D {
+            weights,
+            cdf,
+            total,
+        }
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.weights.len()
+    }
+
+    // Sum of the weights divided by their count.
+    pub(crate) fn total(&self) -> f64 {
+        self.total
+    }
+
+    // Maps a uniform number to a point in [0, 1), returning the point, its
+    // density and the index of the piece it fell into.
+    pub(crate) fn sample(&self, random: f64) -> (f64, f64, usize) {
+        let index = self
+            .cdf
+            .partition_point(|&value| value <= random)
+            .clamp(1, self.weights.len())
+            - 1;
+        let width = self.cdf[index + 1] - self.cdf[index];
+        let offset = if width > 0.0 {
+            (random - self.cdf[index]) / width
+        } else {
+            0.0
+        };
+        let x = (index as f64 + offset) / self.weights.len() as f64;
+        (x, self.pdf(index), index)
+    }
+
+    pub(crate) fn pdf(&self, index: usize) -> f64 {
+        if self.total > 0.0 {
+            self.weights[index].max(0.0) / self.total
+        } else {
+            1.0
+        }
+    }
+}
+
+// Distribution over the unit square, given as rows of weights: a marginal
+// distribution picks the row, and the row's own distribution the column.
+pub(crate) struct Distribution2D {
+    rows: Vec<Distribution1D>,
+    marginal: Distribution1D,
+}
+
+impl Distribution2D {
+    pub(crate) fn new(weights: &[f64], width: usize, height: usize) -> Distribution2D {
+        let rows: Vec<Distribution1D> = weights
+            .chunks_exact(width)
+            .take(height)
+            .map(|row| Distribution1D::new(row.to_vec()))
+            .collect();
+        let marginal = Distribution1D::new(rows.iter().map(Distribution1D::total).collect());
+        Distribution2D { rows, marginal }
+    }
+
+    // Returns the sampled point and its density.
+    pub(crate) fn sample(&self, random: (f64, f64)) -> ((f64, f64), f64) {
+        let (v, pdf_v, row) = self.marginal.sample(random.1);
+        let (u, pdf_u, _) = self.rows[row].sample(random.0);
+        ((u, v), pdf_u * pdf_v)
+    }
+
+    pub(crate) fn pdf(&self, (u, v): (f64, f64)) -> f64 {
+        let row = ((v * self.marginal.len() as f64) as usize).min(self.marginal.len() - 1);
+        let columns = self.rows[row].len();
+        let column = ((u * columns as f64) as usize).min(columns - 1);
+        self.marginal.pdf(row) * self.rows[row].pdf(column)
+    }
+}
+
+// Direction around the local +z axis with density `cos(theta) / pi`.
+pub(crate) fn cosine_hemisphere(random: (f64, f64)) -> Vector {
+    let radius = random.0.sqrt();
+    let angle = 2.0 * PI * random.1;
+    Vector::new(
+        radius * angle.cos(),
+        radius * angle.sin(),
+        (1.0 - random.0).max(0.0).sqrt(),
+    )
+}
+
+// Uniform point on the unit disk. Concentric mapping keeps neighbouring
+// random numbers close together on the disk.
+pub(crate) fn concentric_disk(random: (f64, f64)) -> (f64, f64) {
+    let (a, b) = (2.0 * random.0 - 1.0, 2.0 * random.1 - 1.0);
+    if a == 0.0 && b == 0.0 {
+        return (0.0, 0.0);
+    }
+    let (radius, angle) = if a.abs() > b.abs() {
+        (a, PI / 4.0 * (b / a))
+    } else {
+        (b, PI / 2.0 - PI / 4.0 * (a / b))
+    };
+    (radius * angle.cos(), radius * angle.sin())
+}
+
+// Uniform point inside a regular polygon inscribed in the unit circle, with
+// its first corner at `rotation` radians.
+pub(crate) fn regular_polygon(sides: u32, rotation: f64, random: (f64, f64)) -> (f64, f64) {
+    let sides = sides.max(3);
+    // The first number picks one of the equal triangles around the center
+    // and is then reused inside it.
+    let scaled = random.0 * sides as f64;
+    let triangle = (scaled as u32).min(sides - 1);
+    let (u, v) = (scaled - triangle as f64, random.1);
+    let step = 2.0 * PI / sides as f64;
+    let start = rotation + triangle as f64 * step;
+    let (a, b) = (
+        (start.cos(), start.sin()),
+        ((start + step).cos(), (start + step).sin()),
+    );
+    // Uniform in the triangle spanned by the center and the two corners.
+    let root = u.sqrt();
+    let (wa, wb) = (root * (1.0 - v), root * v);
+    (a.0 * wa + b.0 * wb, a.1 * wa + b.1 * wb)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn samples_fall_into_pieces_by_weight() {
+        let distribution = Distribution1D::new(vec![1.0, 3.0, 0.0, 4.0]);
+        assert_eq!(distribution.len(), 4);
+        assert_eq!(distribution.total(), 2.0);
+        let (x, pdf, index) = distribution.sample(0.1);
+        assert_eq!(index, 0);
+        assert!((x - 0.2).abs() < 1e-12);
+        assert!((pdf - 0.5).abs() < 1e-12);
+        // The empty piece is skipped.
+        let (_, pdf, index) = distribution.sample(0.6);
+        assert_eq!(index, 3);
+        assert!((pdf - 2.0).abs() < 1e-12);
+        assert_eq!(distribution.pdf(2), 0.0);
+    }
+
+    #[test]
+    fn zero_weights_sample_uniformly() {
+        let distribution = Distribution1D::new(vec![0.0; 4]);
+        let (x, pdf, index) = distribution.sample(0.6);
+        assert_eq!(index, 2);
+        assert!((x - 0.6).abs() < 1e-12);
+        assert_eq!(pdf, 1.0);
+    }
+
+    #[test]
+    fn pdf_2d_matches_the_sampled_density_and_integrates_to_one() {
+        let weights = [1.0, 2.0, 3.0, 0.0, 5.0, 1.0];
+        let distribution = Distribution2D::new(&weights, 3, 2);
+        for random in [(0.1, 0.2), (0.5, 0.5), (0.9, 0.95)] {
+            let (point, pdf) = distribution.sample(random);
+            assert!((distribution.pdf(point) - pdf).abs() < 1e-12);
+        }
+        let mut integral = 0.0;
+        for row in 0..2 {
+            for column in 0..3 {
+                let center = ((column as f64 + 0.5) / 3.0, (row as f64 + 0.5) / 2.0);
+                integral += distribution.pdf(center) / 6.0;
+            }
+        }
+        assert!((integral - 1.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn cosine_hemisphere_stays_above_the_plane() {
+        for random in [(0.0, 0.0), (0.3, 0.7), (0.999, 0.5)] {
+            let direction = cosine_hemisphere(random);
+            assert!(direction.z >= 0.0);
+            assert!((direction.length() - 1.0).abs() < 1e-12);
+        }
+    }
+}
@@ -1,25 +1,65 @@
 use std::path::PathBuf;
 
-use crate::io::Input;
+use crate::geometry::aligned_box::AlignedBox;
+use crate::geometry::instance::Instance;
+use crate::geometry::motion::Motion;
+use crate::io::{ImportOptions, Input};
 
+use super::camera::Viewpoint;
+use super::environment::Environment;
 use super::light::Light;
+use super::material::Material;
 use super::RayTracable;
 
 pub(crate) struct Scene {
     objects: Vec<Box<dyn RayTracable>>,
+    // Index into `materials` for every object.
+    object_materials: Vec<usize>,
+    materials: Vec<Material>,
     lights: Vec<Light>,
+    // Seen by rays that miss every object and lights the scene. Without one
+    // misses are left empty.
+    environment: Option<Environment>,
+    // Cameras that came with the model file.
+    viewpoints: Vec<Viewpoint>,
 }
 
 impl Scene {
     pub(crate) fn new() -> Scene {
         Scene {
             objects: Vec::new(),
+            object_materials: Vec::new(),
+            materials: vec![Material::default()],
             lights: Vec::new(),
+            environment: None,
+            viewpoints: Vec::new(),
         }
     }
 
+    // Adds an object with the default material.
+    #[cfg_attr(not(test), allow(dead_code))]
     pub(crate) fn add_object(&mut self, object: Box<dyn RayTracable>) {
+        self.add_object_with_material(object, 0);
+    }
+
+    pub(crate) fn add_object_with_material(
+        &mut self,
+        object: Box<dyn RayTracable>,
+        material: usize,
+    ) {
+        assert!(
+            material < self.materials.len(),
+            "Material {} does not exist",
+            material
+        );
         self.objects.push(object);
+        self.object_materials.push(material);
+    }
+
+    // Returns the index to use with `add_object_with_material`.
+    pub(crate) fn add_material(&mut self, material: Material) -> usize {
+        self.materials.push(material);
+        self.materials.len() - 1
     }
 
     pub(crate) fn add_light(&mut self, light: Light) {
@@ -30,16 +70,155 @@ impl Scene {
         &self.objects
     }
 
+    // Box around every bounded object over its whole motion. Unbounded
+    // objects such as planes are left out, an empty scene has an empty box.
+    pub(crate) fn bounds(&self) -> AlignedBox {
+        self.objects
+            .iter()
+            .map(|object| object.bounds())
+            .filter(AlignedBox::is_finite)
+            .fold(AlignedBox::empty(), |all, bounds| all.union(&bounds))
+    }
+
     pub(crate) fn objects_mut(&mut self) -> &mut Vec<Box<dyn RayTracable>> {
         &mut self.objects
     }
 
+    // Makes `object` follow `motion`, on top of its current placement.
+    pub(crate) fn set_motion(&mut self, object: usize, motion: Motion) {
+        let still = self.objects.remove(object);
+        self.objects
+            .insert(object, Box::new(Instance::new(still, motion)));
+    }
+
+    pub(crate) fn material(&self, object: usize) -> &Material {
+        &self.materials[self.object_materials[object]]
+    }
+
+    // Index of the material used by `object`.
+    pub(crate) fn material_index(&self, object: usize) -> usize {
+        self.object_materials[object]
+    }
+
+    pub(crate) fn materials(&self) -> &Vec<Material> {
+        &self.materials
+    }
+
+    #[cfg_attr(not(test), allow(dead_code))]
+    pub(crate) fn materials_mut(&mut self) -> &mut Vec<Material> {
+        &mut self.materials
+    }
+
     pub(crate) fn lights(&self) -> &Vec<Light> {
         &self.lights
     }
 
+    pub(crate) fn lights_mut(&mut self) -> &mut Vec<Light> {
+        &mut self.lights
+    }
+
+    pub(crate) fn add_viewpoint(&mut self, viewpoint: Viewpoint) {
+        self.viewpoints.push(viewpoint);
+    }
+
+    pub(crate) fn viewpoints(&self) -> &Vec<Viewpoint> {
+        &self.viewpoints
+    }
+
+    pub(crate) fn viewpoints_mut(&mut self) -> &mut Vec<Viewpoint> {
+        &mut self.viewpoints
+    }
+
+    pub(crate) fn set_environment(&mut self, environment: Environment) {
+        self.environment = Some(environment);
+    }
+
+    pub(crate) fn environment(&self) -> Option<&Environment> {
+        self.environment.as_ref()
+    }
+
+    #[cfg_attr(not(test), allow(dead_code))]
     pub(crate) fn from_obj_file(path: PathBuf) -> Result<Scene, std::io::Error> {
         let loader = crate::io::obj_file::ObjectFile::new(path);
         loader.load()
     }
+
+    // Loads a model file, picking the loader from the file extension. Errors
+    // name the file.
+    pub(crate) fn from_file(
+        path: PathBuf,
+        options: ImportOptions,
+    ) -> Result<Scene, std::io::Error> {
+        let name = path.display().to_string();
+        let extension = path
+            .extension()
+            .and_then(std::ffi::OsStr::to_str)
+            .map(str::to_ascii_lowercase);
+        let scene = match extension.as_deref() {
+            Some("gltf" | "glb") => crate::io::gltf_file::GltfFile::new(path)
+                .with_options(options)
+                .load(),
+            Some("obj") => crate::io::obj_file::ObjectFile::new(path)
+                .with_options(options)
+                .load(),
+            Some("ply") => crate::io::ply_file::PlyFile::new(path)
+                .with_options(options)
+                .load(),
+            Some("stl") => crate::io::stl_file::StlFile::new(path)
+                .with_options(options)
+                .load(),
+            _ => Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "Unsupported model format",
+            )),
+        };
+        scene.map_err(|error| {
+            std::io::Error::new(error.kind(), format!("Cannot read {}: {}", name, error))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geometry::point::Point;
+    use crate::geometry::sphere::Sphere;
+    use crate::renderer::color::Color;
+    use crate::renderer::texture::Texture;
+
+    #[test]
+    fn materials_can_be_edited_after_loading() {
+        let mut scene = Scene::new();
+        let index = scene.add_material(Material::new(Texture::Constant(Color::gray(0.5))));
+        scene.materials_mut()[index].roughness = 0.25;
+        assert_eq!(scene.materials()[index].roughness, 0.25);
+    }
+
+    #[test]
+    fn objects_without_a_material_use_the_default() {
+        let mut scene = Scene::new();
+        scene.add_material(Material::new(Texture::Constant(Color::gray(0.5))));
+        scene.add_object(Box::new(Sphere::new(Point::new(0.0, 0.0, 0.0), 1.0)));
+        assert_eq!(scene.material_index(0), 0);
+    }
+
+    #[test]
+    fn obj_files_load_directly_or_by_extension() {
+        let path = PathBuf::from("src/samples/teapot.obj");
+        let direct = Scene::from_obj_file(path.clone()).unwrap();
+        let by_extension = Scene::from_file(path, ImportOptions::default()).unwrap();
+        assert!(!direct.objects().is_empty());
+        assert_eq!(direct.objects().len(), by_extension.objects().len());
+        assert_eq!(direct.bounds().center().y, by_extension.bounds().center().y);
+    }
+
+    #[test]
+    fn unknown_extensions_are_rejected_with_the_file_name() {
+        let result = Scene::from_file(PathBuf::from("model.xyz"), ImportOptions::default());
+        let Err(error) = result else {
+            panic!("model.xyz loaded");
+        };
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidInput);
+        assert!(error.to_string().starts_with("Cannot read model.xyz"));
+    }
 }
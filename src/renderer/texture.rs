@@ -0,0 +1,211 @@
+use std::io::Result;
+use std::path::Path;
+
+use crate::geometry::hit::Hit;
+use crate::geometry::vector::Vector;
+use crate::io::{read_image, Image};
+
+use super::color::Color;
+use super::procedural::{Checker, Marble, Noise, Wood};
+
+// How texture coordinates outside of [0, 1] are mapped back into the image.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum WrapMode {
+    Repeat,
+    MirroredRepeat,
+    Clamp,
+}
+
+impl WrapMode {
+    fn wrap(&self, index: i64, size: usize) -> usize {
+        let size = size as i64;
+        let index = match self {
+            WrapMode::Repeat => index.rem_euclid(size),
+            WrapMode::MirroredRepeat => {
+                let period = index.rem_euclid(2 * size);
+                if period < size {
+                    period
+                } else {
+                    2 * size - 1 - period
+                }
+            }
+            WrapMode::Clamp => index.clamp(0, size - 1),
+        };
+        index as usize
+    }
+}
+
+pub(crate) struct ImageTexture {
+    image: Image,
+    wrap: WrapMode,
+}
+
+impl ImageTexture {
+    pub(crate) fn new(image: Image, wrap: WrapMode) -> ImageTexture {
+        ImageTexture { image, wrap }
+    }
+
+    pub(crate) fn load(path: &Path, wrap: WrapMode) -> Result<ImageTexture> {
+        Ok(ImageTexture::new(read_image(path)?, wrap))
+    }
+
+    pub(crate) fn size(&self) -> (usize, usize) {
+        (self.image.width, self.image.height)
+    }
+
+    fn texel(&self, x: i64, y: i64) -> Color {
+        let x = self.wrap.wrap(x, self.image.width);
+        let y = self.wrap.wrap(y, self.image.height);
+        self.image.pixels[y * self.image.width + x]
+    }
+
+    // Bilinear lookup. `v` points up while image rows go down, so it is
+    // flipped, matching the OBJ convention.
+    pub(crate) fn sample(&self, (u, v): (f64, f64)) -> Color {
+        if self.image.pixels.is_empty() {
+            return Color::black();
+        }
+        let x = u * self.image.width as f64 - 0.5;
+        let y = (1.0 - v) * self.image.height as f64 - 0.5;
+        let (x0, y0) = (x.floor(), y.floor());
+        let (fx, fy) = (x - x0, y - y0);
+        let (x0, y0) = (x0 as i64, y0 as i64);
+
+        let top = self.texel(x0, y0).lerp(self.texel(x0 + 1, y0), fx);
+        let bottom = self.texel(x0, y0 + 1).lerp(self.texel(x0 + 1, y0 + 1), fx);
+        top.lerp(bottom, fy)
+    }
+}
+
+// Source of a color that can vary over a surface. Image textures are looked
+// up by texture coordinates, procedural ones by the object space hit point so
+// they move together with the object.
+pub(crate) enum Texture {
+    Constant(Color),
+    // Color painted on the mesh vertices, white where there is none.
+    VertexColor,
+    Image(ImageTexture),
+    Checker(Checker),
+    Noise(Noise),
+    Marble(Marble),
+    Wood(Wood),
+}
+
+impl Texture {
+    pub(crate) fn evaluate(&self, hit: &Hit) -> Color {
+        match self {
+            Texture::Constant(color) => *color,
+            Texture::VertexColor => hit.color.map_or(Color::white(), |color| {
+                Color::new(color.x, color.y, color.z)
+            }),
+            Texture::Image(image) => image.sample(hit.uv),
+            Texture::Checker(checker) => checker.evaluate(hit.object_point),
+            Texture::Noise(noise) => noise.evaluate(hit.object_point),
+            Texture::Marble(marble) => marble.evaluate(hit.object_point),
+            Texture::Wood(wood) => wood.evaluate(hit.object_point),
+        }
+    }
+
+    // Change of the luminance along the hit tangent and bitangent, used as a
+    // height field by bump mapping, per unit of the texture's own
+    // coordinates. Image textures are differenced one texel apart and scaled
+    // to the change per unit of uv. Procedural ones are differenced along the
+    // tangent frame taken into object space and scaled to the change per unit
+    // of object space length.
+    pub(crate) fn height_gradient(&self, hit: &Hit) -> (f64, f64) {
+        let height = self.evaluate(hit).luminance();
+        match self {
+            Texture::Constant(_) | Texture::VertexColor => (0.0, 0.0),
+            Texture::Image(image) => {
+                let (width, image_height) = image.size();
+                let (u, v) = hit.uv;
+                let along_u = image.sample((u + 1.0 / width.max(1) as f64, v));
+                let along_v = image.sample((u, v + 1.0 / image_height.max(1) as f64));
+                (
+                    (along_u.luminance() - height) * width.max(1) as f64,
+                    (along_v.luminance() - height) * image_height.max(1) as f64,
+                )
+            }
+            _ => {
+                const STEP: f64 = 1e-3;
+                let shifted = |direction: Vector| {
+                    let direction = hit.world_to_object * direction;
+                    let mut shifted = *hit;
+                    shifted.object_point = hit.object_point + direction / direction.length() * STEP;
+                    self.evaluate(&shifted).luminance()
+                };
+                (
+                    (shifted(hit.tangent) - height) / STEP,
+                    (shifted(hit.bitangent) - height) / STEP,
+                )
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geometry::matrix::Matrix;
+    use crate::geometry::normal::Normal;
+    use crate::geometry::point::Point;
+    use crate::geometry::ray::Ray;
+    use crate::renderer::procedural::NoiseKind;
+
+    // Hit on the z = 0 plane at `point`, seen from above, with the tangent
+    // frame along `tangent` and `bitangent`.
+    fn hit_at(point: Point, tangent: Vector, bitangent: Vector) -> Hit {
+        let ray = Ray::new(
+            Point::new(point.x, point.y, 1.0),
+            Normal::new(0.0, 0.0, -1.0),
+        );
+        Hit::new(&ray, 1.0, Normal::new(0.0, 0.0, 1.0)).with_tangents(tangent, bitangent)
+    }
+
+    #[test]
+    fn procedural_gradient_steps_along_the_object_space_frame() {
+        let noise = Texture::Noise(Noise::new(
+            NoiseKind::Perlin,
+            3.0,
+            Color::black(),
+            Color::white(),
+        ));
+        let (x, y) = (Vector::new(1.0, 0.0, 0.0), Vector::new(0.0, 1.0, 0.0));
+        let rotation = Matrix::rotation_z(0.7);
+
+        // The same surface point, once on a rotated object and once placed
+        // in object space directly.
+        let rotated = hit_at(Point::new(0.3, 0.1, 0.0), x, y).with_world_to_object(rotation);
+        let direct = hit_at(rotated.object_point, rotation * x, rotation * y);
+        let (du, dv) = noise.height_gradient(&rotated);
+        let (expected_du, expected_dv) = noise.height_gradient(&direct);
+        assert!(du != 0.0 || dv != 0.0);
+        assert!((du - expected_du).abs() < 1e-9);
+        assert!((dv - expected_dv).abs() < 1e-9);
+    }
+
+    #[test]
+    fn image_gradient_is_per_unit_of_uv_at_any_resolution() {
+        for width in [4, 16] {
+            // Luminance equal to u along every row.
+            let pixels = (0..2 * width)
+                .map(|i| Color::gray(((i % width) as f64 + 0.5) / width as f64))
+                .collect();
+            let image = Image {
+                width,
+                height: 2,
+                pixels,
+            };
+            let texture = Texture::Image(ImageTexture::new(image, WrapMode::Clamp));
+            let mut hit = hit_at(
+                Point::new(0.0, 0.0, 0.0),
+                Vector::new(1.0, 0.0, 0.0),
+                Vector::new(0.0, 1.0, 0.0),
+            );
+            hit.uv = (0.5, 0.5);
+            let (du, dv) = texture.height_gradient(&hit);
+            assert!((du - 1.0).abs() < 1e-9, "{} texels: {}", width, du);
+            assert!(dv.abs() < 1e-9);
+        }
+    }
+}
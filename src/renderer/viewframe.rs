@@ -1,9 +1,15 @@
 use crate::geometry::point::Point;
+use crate::geometry::vector::Vector;
 
+// Rectangle in front of the camera that the image is projected on, centered
+// on `origin`. By default it faces +Z with its width along X and its height
+// along Y.
 pub(crate) struct ViewFrame {
     pub(crate) origin: Point,
     pub(crate) width: f64,
     pub(crate) height: f64,
+    horizontal: Vector,
+    vertical: Vector,
 }
 
 impl ViewFrame {
@@ -12,25 +18,36 @@ impl ViewFrame {
             origin,
             width,
             height,
+            horizontal: Vector::new(1.0, 0.0, 0.0),
+            vertical: Vector::new(0.0, 1.0, 0.0),
         }
     }
 
+    // Turns the frame so its width runs along `horizontal` and its height
+    // along `vertical`, which must be perpendicular unit vectors.
+    pub(crate) fn with_axes(mut self, horizontal: Vector, vertical: Vector) -> ViewFrame {
+        self.horizontal = horizontal;
+        self.vertical = vertical;
+        self
+    }
+
+    // Unit directions of the frame's width and height.
+    pub(crate) fn axes(&self) -> (Vector, Vector) {
+        (self.horizontal, self.vertical)
+    }
+
     pub(crate) fn point_on_pixel(
         &self,
-        x: usize,
-        y: usize,
+        x: f64,
+        y: f64,
         image_width: usize,
         image_height: usize,
     ) -> Point {
         let x_factor = self.width / (image_width as f64);
         let y_factor = self.height / (image_height as f64);
 
-        let x_offset = x as f64 * x_factor;
-        let y_offset = y as f64 * y_factor;
-        Point::new(
-            self.origin.x - self.width / 2.0 + x_offset,
-            self.origin.y - self.height / 2.0 + y_offset,
-            self.origin.z,
-        )
+        let x_offset = x * x_factor - self.width / 2.0;
+        let y_offset = y * y_factor - self.height / 2.0;
+        self.origin + self.horizontal * x_offset + self.vertical * y_offset
     }
-}
\ No newline at end of file
+}